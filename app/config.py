@@ -9,6 +9,7 @@ class GeneralConfig(BaseModel):
     client_id: str = ""
     client_secret: str = ""
     language: str = "system"
+    download_simplification_level: str = "full" # One of full, fine, medium and coarse
 
 class NavigationConfig(BaseModel):
     disallow_leaving_roads: bool = True
@@ -9,6 +9,8 @@ from PySide6.QtCore import QLocale
 from .services import config
 
 OPPOSITE_TURN_THRESHOLD = 175
+# The field types parsed into a structure which keeps the original value as well
+PARSED_TYPES = {"conditional", "opening_hours"}
 
 class TemplateType(enum.Enum):
     long = 1
@@ -39,6 +41,11 @@ def underscored_to_words(underscored):
     underscored = re.sub(r"^\w", lambda m: m.group(0).upper(), underscored)
     return _(underscored)
 
+def list_item_type(type_name):
+    if type_name.startswith("list[") and type_name.endswith("]"):
+        return type_name[5:-1]
+    return None
+
 def get_field_type(key, fields):
     if key in fields:
         return fields[key].type_name
@@ -46,6 +53,15 @@ def get_field_type(key, fields):
         return "str"
 
 def format_field_value(field_value, field_type, template_type=TemplateType.long):
+    item_type = list_item_type(field_type)
+    if item_type:
+        # The values stored before the field became a list are still plain strings.
+        if not isinstance(field_value, list):
+            return field_value
+        return ", ".join(str(format_field_value(item, item_type, template_type)) for item in field_value)
+    if field_type in PARSED_TYPES:
+        # The values stored before the type change are still the raw strings.
+        return field_value["raw"] if isinstance(field_value, dict) else field_value
     if field_type in known_enums:
         try:
             if isinstance(field_value, str):
@@ -31,7 +31,7 @@ class Map:
             candidate_ids = [e.id for e in candidates]
         effectively_inside = effective_width_filter(candidates, position)
         with measure("Intersection query"):
-            intersecting = self._db.get_entities_really_intersecting(candidate_ids, x, y, fast)
+            intersecting = self._db.get_entities_really_intersecting(candidate_ids, x, y, fast, "fine" if fast else "full")
             return list(intersecting) + effectively_inside
     
     def roughly_within_distance(self, position, distance, fast=True):
@@ -1,2 +1,2 @@
 from .registry import operator_for, operators_for_column_class
-from . import eq, neq, gt, ge, lt, le, is_null, is_not_null, contains, starts_with, ends_with
\ No newline at end of file
+from . import eq, neq, gt, ge, lt, le, is_null, is_not_null, contains, starts_with, ends_with, list_contains, list_does_not_contain
\ No newline at end of file
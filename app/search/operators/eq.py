@@ -3,6 +3,7 @@ from .operator import Operator
 
 @operator_for("*", priority=-1)
 class Equals(Operator):
+    applies_to_lists = False
     label = _("Equals")
 
     @classmethod
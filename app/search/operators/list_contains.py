@@ -0,0 +1,10 @@
+from . import operator_for
+from .operator import Operator
+
+@operator_for("list")
+class ListContains(Operator):
+    label = _("Contains")
+
+    @classmethod
+    def get_comparison_expression(cls, field, value_expr, value_widget):
+        return value_expr.contains(cls.get_value_for_query(field, value_widget))
\ No newline at end of file
@@ -3,6 +3,7 @@ from .operator import Operator
 
 @operator_for("*", priority=-1)
 class NotEquals(Operator):
+    applies_to_lists = False
     label = _("Is not equal")
 
     @classmethod
@@ -1,6 +1,8 @@
 from ..widgets import widget_for_column_class
 
 class Operator:
+    # Whether the operator can compare a list field as a whole
+    applies_to_lists = True
 
     @staticmethod
     def get_value_widget(parent, column):
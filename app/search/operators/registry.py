@@ -1,4 +1,6 @@
 from collections import defaultdict
+from ...humanization_utils import list_item_type, PARSED_TYPES
+from .widgetless_operator import WidgetlessOperator
 OPERATORS = defaultdict(list)
 def _do_operator_registration(operator_class, column_classes, priority):
     for class_ in column_classes:
@@ -12,6 +14,17 @@ def operator_for(*classes, priority=0):
 
 def operators_for_column_class(column_class):
     candidates = []
+    if list_item_type(column_class):
+        # The items of a list are searched through the list operators
+        candidates += OPERATORS["list"]
+        candidates += [item for item in OPERATORS["*"] if item[0].applies_to_lists]
+        candidates.sort(key=lambda item: item[1])
+        return [item[0] for item in candidates]
+    if column_class in PARSED_TYPES:
+        # The parsed values have no editable form, so only their presence can be searched for
+        candidates = [item for item in OPERATORS["*"] if issubclass(item[0], WidgetlessOperator)]
+        candidates.sort(key=lambda item: item[1])
+        return [item[0] for item in candidates]
     for registered_class in OPERATORS.keys():
         if registered_class == "*":
             continue
@@ -1,4 +1,5 @@
 class WidgetlessOperator:
+    applies_to_lists = True
     
     @staticmethod
     def get_value_widget(parent, column):
@@ -1,7 +1,7 @@
 from PySide6.QtWidgets import QComboBox
 from osm_db import Enum
 from . import widget_for
-from ...humanization_utils import underscored_to_words
+from ...humanization_utils import underscored_to_words, list_item_type
 
 @widget_for("Enum")
 class Choice:
@@ -10,7 +10,7 @@ class Choice:
     @staticmethod
     def get_value_widget(parent, column):
         choice = QComboBox(parent, editable=False)
-        enum = Enum.with_name(column.type_name)
+        enum = Enum.with_name(list_item_type(column.type_name) or column.type_name)
         for name in enum.members.keys():
             choice.addItem(underscored_to_words(name))
         return choice
@@ -1,4 +1,5 @@
 from osm_db import Enum
+from ...humanization_utils import list_item_type
 
 WIDGETS = {}
 
@@ -15,6 +16,10 @@ def widget_for_column_class(column_class):
     # Try a standard field
     if column_class in WIDGETS:
         return WIDGETS[column_class]
+    # A list field is searched by its items, so the widget of the item type is used
+    item_type = list_item_type(column_class)
+    if item_type:
+        return widget_for_column_class(item_type)
     # Try the enum wildcard (we don't want to name all the known enums in the widget registration)
     if column_class in Enum.all_known():
         return WIDGETS["Enum"]
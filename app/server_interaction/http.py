@@ -7,7 +7,7 @@ import atomicwrites
 from ..services import config
 from ..local_utils import cache_response, get_cached_response, get_local_area_ids
 from .motd import Motd
-from osm_db import AreaDatabase
+from osm_db import AreaDatabase, CHANGES_VERSION
 
 API_ENDPOINT = os.environ.get("API_ENDPOINT", "https://fts.trycht.cz/api")
 HAS_API_CONNECTIVITY = None
@@ -70,7 +70,10 @@ class AreaDatabaseDownloader(QThread):
         self._area = area
 
     def run(self):
-        resp = session.get(url_for("areas/{0}/download".format(self._area)), stream=True, params={"client_id": config().general.client_id})
+        params = {"client_id": config().general.client_id, "changes_version": CHANGES_VERSION}
+        if config().general.download_simplification_level != "full":
+            params["level"] = config().general.download_simplification_level
+        resp = session.get(url_for("areas/{0}/download".format(self._area)), stream=True, params=params)
         if resp.status_code == 200:
             total = int(resp.headers.get("content-length", 0))
             chunk_size = 32*1024
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use diesel::{Connection, SqliteConnection};
+use osm_api::cache_maintenance;
+use osm_api::object_manager::open_cache;
+use osm_db::area_db::AreaDatabase;
+use server::area::Area;
+use std::collections::HashMap;
+
+fn area_names() -> Result<HashMap<i64, String>> {
+    let conn = SqliteConnection::establish("server.db")?;
+    Ok(Area::all(&conn)?
+        .into_iter()
+        .map(|a| (a.osm_id, a.name))
+        .collect())
+}
+
+pub(crate) fn cache_stats() -> Result<()> {
+    let names = area_names()?;
+    let stats = cache_maintenance::cache_stats(&open_cache()?)?;
+    println!("Size on disk: {} MiB", stats.size_on_disk / (1024 * 1024));
+    println!(
+        "Cached objects: {} ({} nodes, {} ways, {} relations)",
+        stats.objects(),
+        stats.nodes,
+        stats.ways,
+        stats.relations
+    );
+    println!("Indexed node locations: {}", stats.node_locations);
+    println!("Objects belonging to no area: {}", stats.without_area);
+    println!(
+        "Objects cached before the tracking of their metadata: {}",
+        stats.untracked
+    );
+    if let Some(oldest) = stats.oldest_seen {
+        println!("The least recently seen object was seen at {}", oldest);
+    }
+    if stats.queries > 0 {
+        println!(
+            "Out of {} cache queries {} were hits ({:.1} %).",
+            stats.queries,
+            stats.hits,
+            stats.hits as f64 / stats.queries as f64 * 100.0
+        );
+    }
+    println!("Objects per area:");
+    for (area, count) in &stats.objects_per_area {
+        match names.get(area) {
+            Some(name) => println!("{} ({}): {}", name, area, count),
+            None => println!("{} (no longer exists): {}", area, count),
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn cache_gc(max_age_days: i64, dry_run: bool, compact: bool) -> Result<()> {
+    let live_areas = area_names()?.into_keys().collect();
+    let cache = open_cache()?;
+    // The areas created before the metadata tracking know their objects only from their databases.
+    let mut added_memberships = 0;
+    for &area in &live_areas {
+        // An area whose database does not exist yet tracks its objects already.
+        if !AreaDatabase::path_for(area, true).exists() {
+            continue;
+        }
+        let ids = AreaDatabase::open_existing(area, true)?.get_entity_ids_with_prefix("")?;
+        added_memberships += cache_maintenance::add_area_memberships(&cache, area, ids, dry_run)?;
+    }
+    let report = cache_maintenance::collect_garbage(
+        &cache,
+        &live_areas,
+        chrono::Duration::days(max_age_days),
+        dry_run,
+    )?;
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    if added_memberships > 0 {
+        println!(
+            "{} {} missing memberships of the objects in the areas.",
+            if dry_run { "Would add" } else { "Added" },
+            added_memberships
+        );
+    }
+    println!("Examined {} cached objects.", report.examined);
+    println!("{} {} objects.", verb, report.removed);
+    println!(
+        "{} the metadata of {} objects and {} node locations which had no object.",
+        verb, report.removed_metadata, report.removed_node_locations
+    );
+    if report.newly_tracked > 0 {
+        println!(
+            "Started tracking {} objects cached before the metadata tracking, they will be collectable in {} days unless seen again.",
+            report.newly_tracked, max_age_days
+        );
+    }
+    if compact && !dry_run {
+        println!("Compacting the cache...");
+        let (before, after) = cache_maintenance::compact_cache()?;
+        println!(
+            "The cache shrank from {} MiB to {} MiB.",
+            before / (1024 * 1024),
+            after / (1024 * 1024)
+        );
+    }
+    Ok(())
+}
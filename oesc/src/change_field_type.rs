@@ -3,9 +3,11 @@ use diesel::{Connection, SqliteConnection};
 use osm_db::area_db::AreaDatabase;
 use osm_db::entities_query::EntitiesQuery;
 use osm_db::entities_query_condition::{Condition, FieldCondition};
+use osm_db::entity_metadata::{EntityMetadata, Enum};
 use osm_db::semantic_change::{EntryChange, SemanticChange};
 use osm_db::translation::{conversions, record::TranslationRecord};
 use redis_api::ChangesStream;
+use serde_json::Value;
 use server::area::Area;
 use std::process;
 
@@ -16,19 +18,33 @@ pub fn change_field_type(
     force: bool,
 ) -> Result<()> {
     let _dotenv_path = dotenv::dotenv()?;
+    if !conversions::is_known_type(&new_type) {
+        eprintln!("Unknown field type {}.", new_type);
+        process::exit(1);
+    }
+    // The entities inheriting the field store it the same way.
+    let discriminators = match EntityMetadata::for_discriminator(&entity) {
+        Some(metadata) => metadata.discriminators_with_descendants(),
+        None => {
+            eprintln!("Unknown entity {}.", entity);
+            process::exit(1);
+        }
+    };
     let server_conn = SqliteConnection::establish("server.db")?;
     for area in Area::all_updated(&server_conn)? {
         println!("Processing area {} (id {})...", area.name, area.osm_id);
         let mut area_db = AreaDatabase::open_existing(area.osm_id, true)?;
         let mut query = EntitiesQuery::default();
-        query.set_included_discriminators(vec![entity.clone()]);
+        query.set_included_discriminators(discriminators.clone());
         query.add_condition(FieldCondition::new(field.clone(), Condition::IsNotNull));
         let mut changes = vec![];
         let mut record = TranslationRecord::new();
+        record.set_current_discriminator(&entity);
+        record.set_current_field(&field);
         for mut entity in area_db.get_entities(&query)?.into_iter() {
             let old_val = entity.value_of_field(&field).clone();
-            let old_val_str = match old_val.as_str() {
-                Some(s) => s,
+            let old_val_str = match textual_value(&old_val, &new_type) {
+                Some(value) => value,
                 None => {
                     if force {
                         eprintln!(
@@ -44,7 +60,7 @@ pub fn change_field_type(
             };
 
             if let Some(new_val) =
-                conversions::convert_field_value(old_val_str, &new_type, &mut record)
+                conversions::convert_field_value(&old_val_str, &new_type, &mut record)
             {
                 changes.push(SemanticChange::updating(
                     entity
@@ -79,3 +95,18 @@ pub fn change_field_type(
     println!("Cleaning up...");
     Ok(())
 }
+
+/// Numbers are converted through their textual form, so e.g. a plain float can become a unit-aware value, and an enum member a list of them.
+fn textual_value(value: &Value, new_type: &str) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => {
+            let item_type = conversions::list_item_type(new_type).unwrap_or(new_type);
+            match Enum::with_name(item_type) {
+                Some(spec) => spec.name_for_value(n.as_i64()? as i32).cloned(),
+                None => Some(n.to_string()),
+            }
+        }
+        _ => None,
+    }
+}
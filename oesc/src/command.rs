@@ -10,11 +10,11 @@ pub struct Args {
 pub enum Command {
     /// Changes an entity field's type. Note that it must be run in production where the area databases are stored, so it can actually do the changes. In addition, the entities.yml file must be modified manually.
     ChangeFieldType {
-        /// The entity to change the field type for.
+        /// The entity to change the field type for, the entities inheriting from it are changed as well.
         entity: String,
         /// The field upon which the change should be executed.
         field: String,
-        /// The new type for the field. Note that the conversions must all pass before the change will be actually executed.
+        /// The new type for the field, e.g. meters, tons, kmh, minutes, percent, volts, hertz, an enum name or a list of these, like list[str]. Note that the conversions must all pass before the change will be actually executed.
         new_type: String,
         /// If specified, ignores the type conversion failures and continues nonetheless.
         #[clap(long)]
@@ -53,4 +53,71 @@ pub enum Command {
     },
     /// Regenerates the parent osm ids for all areas.
     RegenerateParentOSMIds,
+    /// Proposes changes of entities.yml, enums.yml and translation_specs.yml based on translation records and prints them as a diff. It must be run from the directory with the YAML files.
+    SuggestSchema {
+        /// The translation record files to analyse, e.g. the creation_<area>.jsonl and area_updates_<area>.jsonl files of the server.
+        records: Vec<String>,
+        /// Analyse the translation records stored by the server as well.
+        #[clap(long)]
+        stored: bool,
+        /// How many times an enum value or an unknown field must occur to be proposed.
+        #[clap(long, default_value = "10")]
+        min_occurrences: usize,
+        /// How many untranslated objects with the same main tag are needed to propose a new entity.
+        #[clap(long, default_value = "20")]
+        min_cluster_size: usize,
+    },
+    /// Exports an area, or only some of its entities, for GIS tools like QGIS.
+    ExportArea {
+        /// The id of the area to export.
+        area_id: i64,
+        /// The output file, its extension, .geojson or .gpkg, selects the format.
+        output: String,
+        /// Export only the entities with this discriminator, can be repeated.
+        #[clap(long = "discriminator")]
+        discriminators: Vec<String>,
+        /// Export the geometries simplified to this level, one of fine, medium and coarse.
+        #[clap(long)]
+        level: Option<String>,
+    },
+    /// Imports the features of a GeoJSON file as the entities of an overlay, e.g. indoor paths, which survive the OSM updates of the area. Importing the file again makes the overlay match it.
+    ImportOverlay {
+        /// The id of the area to import into.
+        area_id: i64,
+        /// The GeoJSON feature collection, every feature needs an id and a discriminator property.
+        geojson: String,
+        /// The namespace of the overlay entity ids, it must not start with n, w or r.
+        namespace: String,
+        /// Where to save the translation record with the details about the features which failed the checks.
+        #[clap(long)]
+        record: Option<String>,
+    },
+    /// Prints the statistics of the entities cache. The server and the area tools must not be running, they keep the cache locked.
+    CacheStats,
+    /// Removes the objects which belong to no existing area and were not seen for a while from the entities cache. The server and the area tools must not be running, they keep the cache locked.
+    CacheGc {
+        /// How many days the objects belonging to no area are kept after they were last seen.
+        #[clap(long, default_value = "30")]
+        max_age_days: i64,
+        /// Only report what would be removed.
+        #[clap(long)]
+        dry_run: bool,
+        /// Rewrite the cache afterwards to free the disk space, it needs about as much free space as the cache takes.
+        #[clap(long)]
+        compact: bool,
+    },
+    /// Trains a new version of a zstd dictionary from samples of the current data and writes it next to the older ones.
+    TrainDict {
+        /// Which dictionary to train, cache for the entities cache or changes for the area changes in Redis.
+        kind: String,
+        /// Where to write the dictionary, by default to the file name of its next version in the current directory.
+        #[clap(long)]
+        output: Option<String>,
+        /// How many samples to train on at most.
+        #[clap(long, default_value = "100000")]
+        samples: usize,
+        /// The maximum size of the dictionary in bytes.
+        #[clap(long, default_value = "112640")]
+        max_size: usize,
+    },
 }
@@ -0,0 +1,30 @@
+use anyhow::{anyhow, Result};
+use osm_db::entities_query::EntitiesQuery;
+use osm_db::export;
+use osm_db::simplification::SimplificationLevel;
+use osm_db::AreaDatabase;
+use std::path::Path;
+
+pub fn export_area(
+    area_id: i64,
+    output: String,
+    discriminators: Vec<String>,
+    level: Option<String>,
+) -> Result<()> {
+    let area_db = AreaDatabase::open_existing(area_id, true)?;
+    let mut query = EntitiesQuery::default();
+    if !discriminators.is_empty() {
+        query.set_included_discriminators(discriminators);
+    }
+    if let Some(level) = level {
+        query.set_simplification_level(
+            level
+                .parse::<SimplificationLevel>()
+                .map_err(|e| anyhow!(e))?,
+        );
+    }
+    println!("Exporting area {} to {}...", area_id, output);
+    let count = export::export(&area_db, &query, Path::new(&output))?;
+    println!("Successfully exported {} entities.", count);
+    Ok(())
+}
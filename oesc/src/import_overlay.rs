@@ -0,0 +1,39 @@
+use anyhow::Result;
+use osm_db::translation::overlay;
+use osm_db::translation::record::TranslationRecord;
+use osm_db::AreaDatabase;
+use server::background_tasks::area_db_update;
+use std::fs;
+
+pub fn import_overlay(
+    area_id: i64,
+    geojson: String,
+    namespace: String,
+    record_path: Option<String>,
+) -> Result<()> {
+    let _dotenv_path = dotenv::dotenv()?;
+    let overlay_data = serde_json::from_str(&fs::read_to_string(&geojson)?)?;
+    let mut area_db = AreaDatabase::open_existing(area_id, true)?;
+    let mut record = TranslationRecord::new();
+    let overlay = overlay::overlay_changes(&area_db, &overlay_data, &namespace, &mut record)?;
+    for id in &overlay.rejected_ids {
+        println!(
+            "Feature {} failed the entity checks, its previous version, if any, was kept.",
+            id
+        );
+    }
+    if let Some(path) = record_path {
+        record.save_to_file(&path)?;
+    }
+    println!("Applying {} changes...", overlay.changes.len());
+    area_db.begin()?;
+    for change in &overlay.changes {
+        area_db.apply_change(change)?;
+    }
+    area_db.apply_deferred_relationship_additions()?;
+    area_db.commit()?;
+    println!("Publishing the changes...");
+    area_db_update::publish_changes(area_id, &overlay.changes)?;
+    println!("Success.");
+    Ok(())
+}
@@ -1,12 +1,17 @@
 use anyhow::Result;
 use clap::Parser;
 
+mod cache_maintenance;
 mod change_field_type;
 mod command;
 mod create_frozen_copy;
+mod export_area;
+mod import_overlay;
 mod regenerate_parent_osm_ids;
 mod remove_field;
 mod request_redownload;
+mod suggest_schema;
+mod train_dict;
 mod view_field_usage;
 
 use command::{Args, Command};
@@ -34,6 +39,36 @@ fn main() -> Result<()> {
         Command::CreateFrozenCopy { area_id, new_name } => {
             create_frozen_copy::create_frozen_copy(area_id, new_name)
         },
-        Command::RegenerateParentOSMIds => regenerate_parent_osm_ids::regenerate_parent_osm_ids()
+        Command::RegenerateParentOSMIds => regenerate_parent_osm_ids::regenerate_parent_osm_ids(),
+        Command::SuggestSchema {
+            records,
+            stored,
+            min_occurrences,
+            min_cluster_size,
+        } => suggest_schema::suggest_schema(records, stored, min_occurrences, min_cluster_size),
+        Command::ExportArea {
+            area_id,
+            output,
+            discriminators,
+            level,
+        } => export_area::export_area(area_id, output, discriminators, level),
+        Command::ImportOverlay {
+            area_id,
+            geojson,
+            namespace,
+            record,
+        } => import_overlay::import_overlay(area_id, geojson, namespace, record),
+        Command::CacheStats => cache_maintenance::cache_stats(),
+        Command::CacheGc {
+            max_age_days,
+            dry_run,
+            compact,
+        } => cache_maintenance::cache_gc(max_age_days, dry_run, compact),
+        Command::TrainDict {
+            kind,
+            output,
+            samples,
+            max_size,
+        } => train_dict::train_dict(kind, output, samples, max_size),
     }
 }
@@ -0,0 +1,524 @@
+use anyhow::Result;
+use diesel::{Connection, SqliteConnection};
+use osm_db::entity_metadata::{EntityMetadata, Enum};
+use osm_db::translation::{conversions, record::TranslationRecord};
+use serde_json::Value;
+use server::translation_report::StoredTranslationRecord;
+use similar::TextDiff;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+// The types tried when inferring a field type from its values, the more specific ones first. The ones the conversions do not know are skipped.
+const CANDIDATE_TYPES: &[&str] = &[
+    "bool",
+    "int",
+    "float",
+    "meters",
+    "tons",
+    "kmh",
+    "minutes",
+    "percent",
+    "volts",
+    "hertz",
+    "opening_hours",
+    "conditional",
+];
+// Which part of the sampled values must convert for a type to be proposed.
+const INFERENCE_THRESHOLD: f64 = 0.95;
+const IGNORED_TAGS: &[&str] = &["created_by", "source", "note", "fixme", "FIXME"];
+const YAML_RESERVED_WORDS: &[&str] = &[
+    "yes", "no", "y", "n", "on", "off", "true", "false", "null", "~",
+];
+
+/// A YAML file which we patch textually, so the diff touches only the proposed lines.
+struct YamlFile {
+    name: String,
+    original: String,
+    lines: Vec<String>,
+}
+
+impl YamlFile {
+    fn load(name: &str) -> Result<Self> {
+        let original = fs::read_to_string(name)?;
+        let lines = original.lines().map(|l| l.to_string()).collect();
+        Ok(Self {
+            name: name.to_string(),
+            original,
+            lines,
+        })
+    }
+
+    fn render(&self) -> String {
+        let eol = if self.original.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let mut ret = self.lines.join(eol);
+        if self.original.ends_with('\n') {
+            ret.push_str(eol);
+        }
+        ret
+    }
+
+    fn diff(&self) -> String {
+        let new = self.render();
+        if new == self.original {
+            return String::new();
+        }
+        TextDiff::from_lines(&self.original, &new)
+            .unified_diff()
+            .header(&format!("a/{}", self.name), &format!("b/{}", self.name))
+            .to_string()
+    }
+
+    fn has_top_level_key(&self, key: &str) -> bool {
+        self.find_key_in(0, self.lines.len(), 0, key).is_some()
+    }
+
+    fn find_key_in(&self, start: usize, end: usize, indent: usize, key: &str) -> Option<usize> {
+        let prefix = format!("{}{}:", " ".repeat(indent), key);
+        (start..end).find(|idx| {
+            let line = &self.lines[*idx];
+            line.starts_with(&prefix)
+                && matches!(line[prefix.len()..].chars().next(), None | Some(' '))
+        })
+    }
+
+    /// Returns the index after the last line nested under the line at `start`.
+    fn block_end(&self, start: usize, indent: usize) -> usize {
+        let mut end = start + 1;
+        let mut idx = start + 1;
+        while idx < self.lines.len() {
+            let line = &self.lines[idx];
+            if !line.trim().is_empty() {
+                if line.len() - line.trim_start().len() <= indent {
+                    break;
+                }
+                end = idx + 1;
+            }
+            idx += 1;
+        }
+        end
+    }
+
+    /// Appends the lines to the mapping at the given key path, returns false when the mapping could not be found.
+    fn append_to_mapping(&mut self, path: &[&str], new_lines: Vec<String>) -> bool {
+        let mut start = 0;
+        let mut end = self.lines.len();
+        let mut key_idx = None;
+        for (depth, key) in path.iter().enumerate() {
+            match self.find_key_in(start, end, depth * 2, key) {
+                Some(idx) => {
+                    key_idx = Some(idx);
+                    start = idx + 1;
+                    end = self.block_end(idx, depth * 2);
+                }
+                None => return false,
+            }
+        }
+        let key_idx = match key_idx {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let key_line = self.lines[key_idx].clone();
+        let (key_part, inline_value) = key_line
+            .split_once(':')
+            .expect("The key line must contain a colon");
+        match inline_value.trim() {
+            "" => {}
+            // An empty flow mapping, turn it into a block one.
+            "{}" => self.lines[key_idx] = format!("{}:", key_part),
+            _ => return false,
+        }
+        for (offset, line) in new_lines.into_iter().enumerate() {
+            self.lines.insert(end + offset, line);
+        }
+        true
+    }
+
+    fn append(&mut self, new_lines: Vec<String>) {
+        self.lines.extend(new_lines);
+    }
+}
+
+pub fn suggest_schema(
+    records: Vec<String>,
+    stored: bool,
+    min_occurrences: usize,
+    min_cluster_size: usize,
+) -> Result<()> {
+    let mut record = TranslationRecord::new();
+    for path in &records {
+        TranslationRecord::load_from_file(path)?.merge_to(&mut record);
+    }
+    if stored {
+        let server_conn = SqliteConnection::establish("server.db")?;
+        for stored_record in StoredTranslationRecord::all(&server_conn)? {
+            stored_record.parsed()?.merge_to(&mut record);
+        }
+    }
+    let mut enums = YamlFile::load("enums.yml")?;
+    let mut entities = YamlFile::load("entities.yml")?;
+    let mut specs = YamlFile::load("translation_specs.yml")?;
+    suggest_enum_members(&record, min_occurrences, &mut enums);
+    suggest_fields(&record, min_occurrences, &mut entities, &mut specs);
+    suggest_discriminators(&record, min_cluster_size, &mut entities, &mut specs);
+    for file in &[&enums, &entities, &specs] {
+        print!("{}", file.diff());
+    }
+    Ok(())
+}
+
+fn suggest_enum_members(record: &TranslationRecord, min_occurrences: usize, enums: &mut YamlFile) {
+    let mut enum_names: Vec<_> = record.missing_enum_members().keys().collect();
+    enum_names.sort();
+    for enum_name in enum_names {
+        let spec = match Enum::with_name(enum_name) {
+            Some(spec) => spec,
+            None => continue,
+        };
+        // Values with semicolons are lists, not a new member.
+        let mut candidates: Vec<_> = record.missing_enum_members()[enum_name]
+            .iter()
+            .map(|(member, reservoir)| (member, reservoir.count()))
+            .filter(|(member, occurrences)| {
+                *occurrences as usize >= min_occurrences && !member.contains(';')
+            })
+            .collect();
+        if candidates.is_empty() {
+            continue;
+        }
+        candidates.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let first_value = spec.members.values().max().map_or(0, |v| v + 1);
+        let lines = (first_value..)
+            .zip(candidates)
+            .map(|(value, (member, occurrences))| {
+                format!(
+                    "  {}: {} # {} occurrences",
+                    yaml_scalar(member),
+                    value,
+                    occurrences
+                )
+            })
+            .collect();
+        if !enums.append_to_mapping(&[enum_name.as_str()], lines) {
+            eprintln!("Could not find enum {} in {}.", enum_name, enums.name);
+        }
+    }
+}
+
+fn suggest_fields(
+    record: &TranslationRecord,
+    min_occurrences: usize,
+    entities: &mut YamlFile,
+    specs: &mut YamlFile,
+) {
+    let mut discriminators: Vec<_> = record.unknown_fields().keys().collect();
+    discriminators.sort();
+    for discriminator in discriminators {
+        let metadata = match EntityMetadata::for_discriminator(discriminator) {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+        let known_fields = metadata.all_fields();
+        let mut fields: Vec<_> = record.unknown_fields()[discriminator]
+            .iter()
+            .filter(|(_, reservoir)| reservoir.count() as usize >= min_occurrences)
+            .collect();
+        fields.sort_by(|a, b| b.1.count().cmp(&a.1.count()).then_with(|| a.0.cmp(b.0)));
+        let mut field_lines = vec![];
+        let mut rename_lines = vec![];
+        for (field, reservoir) in fields {
+            let name = field_name_for(field);
+            if name != *field {
+                rename_lines.push(format!("    {}: {}", yaml_scalar(field), name));
+            }
+            // The tag may only need a rename to an already existing field.
+            if known_fields.contains_key(&name) {
+                continue;
+            }
+            let values: Vec<_> = reservoir
+                .sample()
+                .iter()
+                .map(|e| unquoted(&e.value))
+                .collect();
+            field_lines.push(format!(
+                "    {}: {} # {} occurrences",
+                name,
+                infer_type(discriminator, &name, &values),
+                reservoir.count()
+            ));
+        }
+        if !field_lines.is_empty()
+            && !entities.append_to_mapping(&[discriminator.as_str(), "fields"], field_lines)
+        {
+            eprintln!(
+                "Could not find the fields of {} in {}.",
+                discriminator, entities.name
+            );
+        }
+        if !rename_lines.is_empty()
+            && !specs.append_to_mapping(&[discriminator.as_str(), "renames"], rename_lines)
+        {
+            eprintln!(
+                "Could not find the renames of {} in {}, add them to the relevant specification manually.",
+                discriminator, specs.name
+            );
+        }
+    }
+}
+
+fn suggest_discriminators(
+    record: &TranslationRecord,
+    min_cluster_size: usize,
+    entities: &mut YamlFile,
+    specs: &mut YamlFile,
+) {
+    // The objects are grouped by their main tag, groups without one have a wildcard value.
+    let mut clusters: Vec<_> = record
+        .potentially_interesting_objects()
+        .iter()
+        .filter(|(_, reservoir)| reservoir.count() as usize >= min_cluster_size)
+        .filter_map(|(group, reservoir)| {
+            group
+                .split_once('=')
+                .filter(|(_, value)| *value != "*")
+                .map(|(key, value)| (key.to_string(), value.to_string(), reservoir))
+        })
+        .collect();
+    clusters.sort_by(|a, b| {
+        b.2.count()
+            .cmp(&a.2.count())
+            .then_with(|| (&a.0, &a.1).cmp(&(&b.0, &b.1)))
+    });
+    for (key, value, reservoir) in clusters {
+        let objects = reservoir.sample();
+        let discriminator = match discriminator_name_for(&key, &value) {
+            Some(name) => name,
+            None => continue,
+        };
+        if entities.has_top_level_key(&discriminator) || specs.has_top_level_key(&discriminator) {
+            eprintln!(
+                "{} objects tagged {}={} are not translated, but {} already exists, consider extending its accepts_when conditions.",
+                reservoir.count(), key, value, discriminator
+            );
+            continue;
+        }
+        let mut tag_values: HashMap<&str, Vec<String>> = HashMap::new();
+        // The common tags are decided on the sampled objects.
+        for object in objects {
+            for (tag, tag_value) in object.tags.iter() {
+                if *tag != key {
+                    tag_values
+                        .entry(tag.as_str())
+                        .or_default()
+                        .push(tag_value.clone());
+                }
+            }
+        }
+        let is_common = |count: usize| count * 2 >= objects.len();
+        let named = is_common(tag_values.get("name").map_or(0, |v| v.len()));
+        let address_aware = is_common(
+            objects
+                .iter()
+                .filter(|o| o.tags.keys().any(|k| k.starts_with("addr:")))
+                .count(),
+        );
+        let mut common_tags: Vec<_> = tag_values
+            .into_iter()
+            .filter(|(tag, values)| {
+                is_common(values.len())
+                    && *tag != "name"
+                    && !tag.starts_with("addr:")
+                    && !IGNORED_TAGS.contains(tag)
+            })
+            .collect();
+        common_tags.sort_by(|a, b| a.0.cmp(b.0));
+        let mut field_lines = vec![];
+        let mut rename_lines = vec![];
+        for (tag, values) in common_tags {
+            let name = field_name_for(tag);
+            if name != tag {
+                rename_lines.push(format!("    {}: {}", yaml_scalar(tag), name));
+            }
+            field_lines.push(format!(
+                "    {}: {}",
+                name,
+                infer_type(&discriminator, &name, &values)
+            ));
+        }
+        let mut entity_lines = vec![
+            format!(
+                "{}: # {} untranslated objects tagged {}={}",
+                discriminator,
+                reservoir.count(),
+                key,
+                value
+            ),
+            format!("  inherits: {}", if named { "Named" } else { "OSMEntity" }),
+        ];
+        if field_lines.is_empty() {
+            entity_lines.push("  fields: {}".to_string());
+        } else {
+            entity_lines.push("  fields:".to_string());
+            entity_lines.append(&mut field_lines);
+        }
+        entities.append(entity_lines);
+        let mut spec_lines = vec![format!("{}:", discriminator)];
+        if rename_lines.is_empty() {
+            spec_lines.push("  renames: {}".to_string());
+        } else {
+            spec_lines.push("  renames:".to_string());
+            spec_lines.append(&mut rename_lines);
+        }
+        spec_lines.push("  unprefixes: []".to_string());
+        spec_lines.push("  replaces_property_value: {}".to_string());
+        if address_aware {
+            spec_lines.push("  address_aware: true".to_string());
+        }
+        spec_lines.push(format!(
+            "  accepts_when: [{{property_equals: {{{}: [{}]}}}}]",
+            yaml_scalar(&key),
+            yaml_scalar(&value)
+        ));
+        specs.append(spec_lines);
+    }
+}
+
+fn infer_type(discriminator: &str, field: &str, values: &[String]) -> String {
+    if values.is_empty() {
+        return "str".to_string();
+    }
+    let mut record = TranslationRecord::new();
+    record.set_current_discriminator(discriminator);
+    record.set_current_field(field);
+    let required = values.len() as f64 * INFERENCE_THRESHOLD;
+    for candidate in CANDIDATE_TYPES
+        .iter()
+        .filter(|c| conversions::is_known_type(c))
+    {
+        let converted = values
+            .iter()
+            .filter(|v| conversions::convert_field_value(v, candidate, &mut record).is_some())
+            .count();
+        if converted as f64 >= required {
+            return candidate.to_string();
+        }
+    }
+    let distinct: HashSet<_> = values.iter().map(|v| v.as_str()).collect();
+    let mut enum_names = Enum::all_known();
+    enum_names.sort();
+    for enum_name in enum_names {
+        if let Some(spec) = Enum::with_name(enum_name) {
+            if distinct.iter().all(|v| spec.value_for_name(v).is_some()) {
+                return enum_name.to_string();
+            }
+        }
+    }
+    let with_separator = values.iter().filter(|v| v.contains(';')).count();
+    if with_separator as f64 >= required / 2.0 {
+        let items: Vec<String> = values
+            .iter()
+            .flat_map(|v| v.split(';'))
+            .map(|i| i.trim().to_string())
+            .filter(|i| !i.is_empty())
+            .collect();
+        let item_type = infer_type(discriminator, field, &items);
+        if !item_type.starts_with("list[") {
+            return format!("list[{}]", item_type);
+        }
+    }
+    "str".to_string()
+}
+
+/// The recorded values are serialized JSON values, we want the original strings.
+fn unquoted(value: &str) -> String {
+    match serde_json::from_str::<Value>(value) {
+        Ok(Value::String(s)) => s,
+        _ => value.to_string(),
+    }
+}
+
+fn field_name_for(tag: &str) -> String {
+    tag.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn discriminator_name_for(key: &str, value: &str) -> Option<String> {
+    let base = if value == "yes" { key } else { value };
+    let name: String = base
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            let first = chars.next().expect("Empty words were filtered out");
+            first.to_ascii_uppercase().to_string() + chars.as_str()
+        })
+        .collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn yaml_scalar(value: &str) -> String {
+    let plain = !value.is_empty()
+        && !YAML_RESERVED_WORDS.contains(&value.to_lowercase().as_str())
+        && value.starts_with(|c: char| c.is_ascii_alphabetic())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    if plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::Path;
+
+    fn use_repository_definitions() {
+        env::set_current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("..")).unwrap();
+    }
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn infers_str_for_plain_strings() {
+        use_repository_definitions();
+        let names = values(&["Main Street", "Old Town Bakery", "U Zlatého tygra"]);
+        assert_eq!(infer_type("Shop", "name", &names), "str");
+    }
+
+    #[test]
+    fn infers_the_most_specific_type() {
+        use_repository_definitions();
+        assert_eq!(
+            infer_type("Shop", "level", &values(&["1", "2", "-1"])),
+            "int"
+        );
+        assert_eq!(
+            infer_type("Road", "maxspeed", &values(&["50 mph", "30", "20 mph"])),
+            "kmh"
+        );
+        assert_eq!(
+            infer_type("Shop", "brand", &values(&["A;B", "C", "D; E"])),
+            "list[str]"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{bail, Result};
+use diesel::{Connection, SqliteConnection};
+use osm_api::cache_maintenance;
+use osm_api::compression::Dictionaries;
+use osm_api::object_manager::{open_cache, CACHE_DICTIONARIES};
+use osm_db::semantic_change::{ChangeFormat, CHANGES_DICTIONARIES};
+use redis_api::ChangesStream;
+use server::area::Area;
+use std::fs;
+
+// Fewer samples would give a dictionary worse than the current one.
+const MIN_SAMPLES: usize = 1000;
+const COMPRESSION_LEVEL: i32 = 10;
+
+fn change_samples(count: usize) -> Result<Vec<Vec<u8>>> {
+    let _dotenv_path = dotenv::dotenv()?;
+    let conn = SqliteConnection::establish("server.db")?;
+    let areas = Area::all(&conn)?;
+    let per_area = count / areas.len().max(1) + 1;
+    let mut samples = vec![];
+    for area in areas {
+        let mut stream = ChangesStream::new_from_env(area.osm_id)?;
+        for change in stream.recent_changes(per_area)? {
+            // The newest dictionary compresses mostly the binary changes of the current clients.
+            samples.push(change.encode_as(ChangeFormat::Binary)?);
+        }
+    }
+    samples.truncate(count);
+    Ok(samples)
+}
+
+fn compressed_size(dictionary: &[u8], samples: &[Vec<u8>]) -> Result<usize> {
+    let mut compressor = zstd::bulk::Compressor::with_dictionary(COMPRESSION_LEVEL, dictionary)?;
+    let mut size = 0;
+    for sample in samples {
+        size += compressor.compress(sample)?.len();
+    }
+    Ok(size)
+}
+
+pub(crate) fn train_dict(
+    kind: String,
+    output: Option<String>,
+    samples: usize,
+    max_size: usize,
+) -> Result<()> {
+    let (dictionaries, training_samples): (&Dictionaries, _) = match kind.as_str() {
+        "cache" => (
+            &*CACHE_DICTIONARIES,
+            cache_maintenance::sample_objects(&open_cache()?, samples)?,
+        ),
+        "changes" => (&*CHANGES_DICTIONARIES, change_samples(samples)?),
+        _ => bail!("Unknown dictionary kind {}, use cache or changes.", kind),
+    };
+    if training_samples.len() < MIN_SAMPLES {
+        bail!(
+            "Only {} samples are available, at least {} are needed.",
+            training_samples.len(),
+            MIN_SAMPLES
+        );
+    }
+    println!("Training on {} samples...", training_samples.len());
+    let dictionary = zstd::dict::from_samples(&training_samples, max_size)?;
+    let current_id = dictionaries.current_id();
+    let id = current_id + 1;
+    let output = output.unwrap_or_else(|| format!("{}.{}.dict", dictionaries.name(), id));
+    fs::write(&output, &dictionary)?;
+    let raw_size: usize = training_samples.iter().map(|s| s.len()).sum();
+    let current = dictionaries
+        .dictionary(current_id)
+        .expect("The current dictionary is always loaded");
+    println!(
+        "The samples take {} bytes, {} compressed with the current dictionary {} and {} with the new one.",
+        raw_size,
+        compressed_size(current, &training_samples)?,
+        current_id,
+        compressed_size(&dictionary, &training_samples)?
+    );
+    println!("The dictionary {} was written to {}.", id, output);
+    if kind == "changes" {
+        println!("To use it, copy it to osm_db/changes_dictionaries in the repository and rebuild the server and the clients.");
+    } else {
+        println!("It will compress the newly cached objects once the area tools are restarted in the directory with it, keep the older dictionaries there for the objects compressed with them.");
+    }
+    Ok(())
+}
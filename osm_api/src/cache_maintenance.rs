@@ -0,0 +1,268 @@
+//! Keeps the entities cache from growing forever: statistics, garbage collection of the objects no area needs, and compaction.
+//!
+//! Everything here needs the cache for itself, so it can't run while the server or the area tools have it opened.
+use crate::node_locations::NodeLocations;
+use crate::object_manager::{deserialize_compressed, open_cache, CACHE_DICTIONARIES, CACHE_PATH};
+use crate::object_metadata::{ObjectMetadata, ObjectMetadataTree};
+use crate::Result;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use sled::Db;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+const COUNTERS_TREE_NAME: &str = "counters";
+const QUERIES_KEY: &str = "cache_queries";
+const HITS_KEY: &str = "cache_hits";
+const COMPACTED_PATH: &str = "entities_cache.compacted";
+const REPLACED_PATH: &str = "entities_cache.old";
+
+fn add_to_counter(counters: &sled::Tree, key: &str, value: u64) -> Result<()> {
+    counters.fetch_and_update(key, |old| {
+        let old = old.map_or(0, read_counter);
+        Some((old + value).to_be_bytes().to_vec())
+    })?;
+    Ok(())
+}
+
+fn read_counter(value: &[u8]) -> u64 {
+    u64::from_be_bytes(value.try_into().unwrap_or_default())
+}
+
+/// Adds the cache queries of an object manager to the totals kept in the cache.
+pub(crate) fn add_to_counters(cache: &Db, queries: u64, hits: u64) -> Result<()> {
+    let counters = cache.open_tree(COUNTERS_TREE_NAME)?;
+    add_to_counter(&counters, QUERIES_KEY, queries)?;
+    add_to_counter(&counters, HITS_KEY, hits)?;
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    pub nodes: usize,
+    pub ways: usize,
+    pub relations: usize,
+    pub node_locations: usize,
+    /// The objects cached before their metadata were tracked.
+    pub untracked: usize,
+    /// The objects belonging to no area, e.g. the dependencies of the area objects.
+    pub without_area: usize,
+    pub objects_per_area: BTreeMap<i64, usize>,
+    pub oldest_seen: Option<DateTime<Utc>>,
+    pub size_on_disk: u64,
+    /// The totals of all the object managers which used the cache.
+    pub queries: u64,
+    pub hits: u64,
+}
+
+impl CacheStats {
+    pub fn objects(&self) -> usize {
+        self.nodes + self.ways + self.relations
+    }
+}
+
+pub fn cache_stats(cache: &Db) -> Result<CacheStats> {
+    let metadata = ObjectMetadataTree::open(cache)?;
+    let mut stats = CacheStats {
+        node_locations: NodeLocations::open(cache)?.len(),
+        size_on_disk: cache.size_on_disk()?,
+        ..Default::default()
+    };
+    for key in cache.iter().keys() {
+        let key = key?;
+        match key.first() {
+            Some(b'n') => stats.nodes += 1,
+            Some(b'w') => stats.ways += 1,
+            Some(b'r') => stats.relations += 1,
+            _ => continue,
+        }
+        match metadata.get(&String::from_utf8_lossy(&key))? {
+            None => stats.untracked += 1,
+            Some(object) => {
+                if object.areas.is_empty() {
+                    stats.without_area += 1;
+                }
+                for area in &object.areas {
+                    *stats.objects_per_area.entry(*area).or_insert(0) += 1;
+                }
+                if let Some(seen) = object.last_seen_at() {
+                    if stats.oldest_seen.is_none_or(|oldest| seen < oldest) {
+                        stats.oldest_seen = Some(seen);
+                    }
+                }
+            }
+        }
+    }
+    let counters = cache.open_tree(COUNTERS_TREE_NAME)?;
+    stats.queries = counters.get(QUERIES_KEY)?.map_or(0, |v| read_counter(&v));
+    stats.hits = counters.get(HITS_KEY)?.map_or(0, |v| read_counter(&v));
+    Ok(stats)
+}
+
+/// Makes the cached objects with the given ids, and the objects they consist of, members of the area.
+///
+/// The objects of the areas created before the metadata were tracked have no memberships, so this has to run for every live area before `collect_garbage`. Returns the number of the memberships which were missing, with `dry_run` they are only counted.
+pub fn add_area_memberships(
+    cache: &Db,
+    area: i64,
+    ids: impl IntoIterator<Item = String>,
+    dry_run: bool,
+) -> Result<usize> {
+    let metadata = ObjectMetadataTree::open(cache)?;
+    let now = Utc::now().timestamp();
+    let mut pending: Vec<String> = ids.into_iter().collect();
+    let mut visited = HashSet::new();
+    let mut added = 0;
+    while let Some(id) = pending.pop() {
+        if !visited.insert(id.clone()) {
+            continue;
+        }
+        let object = match cache.get(&id)? {
+            Some(value) => deserialize_compressed(&value)?,
+            None => continue,
+        };
+        pending.extend(object.related_ids().map(|(id, _)| id));
+        let mut object_metadata = metadata.get(&id)?.unwrap_or(ObjectMetadata {
+            last_seen: now,
+            areas: vec![],
+        });
+        if object_metadata.areas.contains(&area) {
+            continue;
+        }
+        added += 1;
+        if !dry_run {
+            object_metadata.areas.push(area);
+            metadata.insert(&id, &object_metadata)?;
+        }
+    }
+    Ok(added)
+}
+
+#[derive(Debug, Default)]
+pub struct GarbageCollectionReport {
+    pub examined: usize,
+    pub removed: usize,
+    /// The untracked objects which got their metadata, they are collectable after the maximum age from now on.
+    pub newly_tracked: usize,
+    pub removed_metadata: usize,
+    pub removed_node_locations: usize,
+}
+
+/// Removes the objects which belong to none of the live areas and were not seen for longer than `max_age`.
+///
+/// The memberships of the areas which no longer exist are forgotten, the untracked objects get tracked as belonging to no area. With `dry_run`, nothing is changed, only the report is computed.
+pub fn collect_garbage(
+    cache: &Db,
+    live_areas: &HashSet<i64>,
+    max_age: Duration,
+    dry_run: bool,
+) -> Result<GarbageCollectionReport> {
+    let metadata = ObjectMetadataTree::open(cache)?;
+    let node_locations = NodeLocations::open(cache)?;
+    let now = Utc::now();
+    let oldest_kept = (now - max_age).timestamp();
+    let mut report = GarbageCollectionReport::default();
+    for key in cache.iter().keys() {
+        let key = key?;
+        if !matches!(key.first(), Some(b'n') | Some(b'w') | Some(b'r')) {
+            continue;
+        }
+        let id = String::from_utf8_lossy(&key).into_owned();
+        report.examined += 1;
+        let mut object = match metadata.get(&id)? {
+            Some(object) => object,
+            None => {
+                report.newly_tracked += 1;
+                if !dry_run {
+                    metadata.insert(
+                        &id,
+                        &ObjectMetadata {
+                            last_seen: now.timestamp(),
+                            areas: vec![],
+                        },
+                    )?;
+                }
+                continue;
+            }
+        };
+        let area_count = object.areas.len();
+        object.areas.retain(|area| live_areas.contains(area));
+        if object.areas.is_empty() && object.last_seen < oldest_kept {
+            report.removed += 1;
+            if !dry_run {
+                cache.remove(&key)?;
+                metadata.remove(&id)?;
+                if let Some(node_id) = id.strip_prefix('n').and_then(|i| i.parse().ok()) {
+                    node_locations.remove(node_id)?;
+                }
+            }
+        } else if object.areas.len() != area_count && !dry_run {
+            metadata.insert(&id, &object)?;
+        }
+    }
+    // The metadata and locations of the objects removed some other way, e.g. by hand.
+    for entry in metadata.iter() {
+        let (id, _) = entry?;
+        if !cache.contains_key(&id)? {
+            report.removed_metadata += 1;
+            if !dry_run {
+                metadata.remove(&id)?;
+            }
+        }
+    }
+    for id in node_locations.ids() {
+        let id = id?;
+        if !cache.contains_key(format!("n{}", id))? {
+            report.removed_node_locations += 1;
+            if !dry_run {
+                node_locations.remove(id)?;
+            }
+        }
+    }
+    if !dry_run {
+        cache.flush()?;
+    }
+    info!(
+        "Garbage collection of the entities cache finished: {:?}",
+        report
+    );
+    Ok(report)
+}
+
+/// The serialized forms of the cached objects spread evenly over the cache, the samples for training a new dictionary.
+pub fn sample_objects(cache: &Db, count: usize) -> Result<Vec<Vec<u8>>> {
+    let step = (cache.len() / count.max(1)).max(1);
+    let mut samples = Vec::with_capacity(count);
+    for entry in cache.iter().step_by(step).take(count) {
+        let (_, value) = entry?;
+        samples.push(CACHE_DICTIONARIES.decompress(&value)?);
+    }
+    Ok(samples)
+}
+
+/// Rewrites the cache into a new directory and replaces the old one, which gives the space of the removed objects back to the filesystem.
+///
+/// Returns the sizes on disk before and after the compaction. It needs about as much free space as the cache takes.
+pub fn compact_cache() -> Result<(u64, u64)> {
+    let cache = open_cache()?;
+    let before = cache.size_on_disk()?;
+    if Path::new(COMPACTED_PATH).exists() {
+        fs::remove_dir_all(COMPACTED_PATH)?;
+    }
+    let compacted = sled::open(COMPACTED_PATH)?;
+    compacted.import(cache.export());
+    compacted.flush()?;
+    let after = compacted.size_on_disk()?;
+    drop(compacted);
+    drop(cache);
+    fs::rename(CACHE_PATH, REPLACED_PATH)?;
+    fs::rename(COMPACTED_PATH, CACHE_PATH)?;
+    fs::remove_dir_all(REPLACED_PATH)?;
+    info!(
+        "Compacted the entities cache from {} to {} bytes.",
+        before, after
+    );
+    Ok((before, after))
+}
@@ -1,5 +1,6 @@
 use crate::change::{OSMObjectChange, OSMObjectChangeType};
 use crate::object::*;
+use crate::overpass_api::remark_error;
 use crate::Error;
 use crate::Result;
 use hashbrown::HashMap;
@@ -156,6 +157,10 @@ impl<T: Read> OSMObjectChangeIterator<T> {
             Ok(XmlEvent::Characters(chars)) => {
                 trace!("Characters: {}", chars);
                 if self.in_remark {
+                    if let Some(e) = remark_error(&chars) {
+                        self.finished = true;
+                        return Err(e);
+                    }
                     warn!("Received a remark from the server: {}", chars);
                 }
             }
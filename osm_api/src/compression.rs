@@ -0,0 +1,202 @@
+//! Zstd compression with versioned dictionaries.
+//!
+//! Every payload starts with a header carrying the id of the dictionary it was compressed with, so a new dictionary can be introduced while the payloads compressed with the older ones are still around. The payloads written before the ids existed have no header and use the legacy dictionary.
+use crate::{Error, Result};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use zstd_util::ZstdContext;
+
+// Can't be the first byte of a zstd frame, so the legacy payloads are recognizable.
+const HEADER_MAGIC: u8 = 0xD1;
+const HEADER_LEN: usize = 5;
+/// The id of the dictionaries used before the payloads got their headers.
+pub const LEGACY_DICTIONARY_ID: u32 = 1;
+
+static NEXT_SET_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // One context per thread and dictionary, so the threads do not wait for each other.
+    static CONTEXTS: RefCell<HashMap<(usize, u32), ZstdContext<'static>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// All the known versions of a dictionary, the one with the highest id compresses the new payloads.
+pub struct Dictionaries {
+    set_id: usize,
+    name: String,
+    level: i32,
+    dictionaries: BTreeMap<u32, Vec<u8>>,
+}
+
+fn parse_file_name(file_name: &str, name: &str) -> Option<u32> {
+    let rest = file_name.strip_prefix(name)?.strip_suffix(".dict")?;
+    if rest.is_empty() {
+        Some(LEGACY_DICTIONARY_ID)
+    } else {
+        rest.strip_prefix('.')?.parse().ok()
+    }
+}
+
+impl Dictionaries {
+    pub fn new(name: &str, level: i32, dictionaries: BTreeMap<u32, Vec<u8>>) -> Self {
+        assert!(!dictionaries.is_empty(), "No {} dictionaries given.", name);
+        Self {
+            set_id: NEXT_SET_ID.fetch_add(1, Ordering::Relaxed),
+            name: name.to_string(),
+            level,
+            dictionaries,
+        }
+    }
+
+    /// Loads the `<name>.dict` file of the directory as the legacy dictionary and the `<name>.<id>.dict` files as the newer ones.
+    pub fn from_dir(dir: &Path, name: &str, level: i32) -> Result<Self> {
+        let mut dictionaries = BTreeMap::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let id = match path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| parse_file_name(n, name))
+            {
+                Some(id) => id,
+                None => continue,
+            };
+            dictionaries.insert(id, fs::read(&path)?);
+        }
+        if dictionaries.is_empty() {
+            return Err(Error::NoDictionaries(name.to_string()));
+        }
+        Ok(Self::new(name, level, dictionaries))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.dictionaries.keys().copied()
+    }
+
+    pub fn current_id(&self) -> u32 {
+        *self
+            .dictionaries
+            .keys()
+            .next_back()
+            .expect("The dictionaries are never empty")
+    }
+
+    pub fn dictionary(&self, id: u32) -> Option<&[u8]> {
+        self.dictionaries.get(&id).map(|d| d.as_slice())
+    }
+
+    fn with_context<T>(&self, id: u32, f: impl FnOnce(&mut ZstdContext) -> T) -> Result<T> {
+        let dictionary = self
+            .dictionary(id)
+            .ok_or_else(|| Error::UnknownDictionary(self.name.clone(), id))?;
+        Ok(CONTEXTS.with(|contexts| {
+            let mut contexts = contexts.borrow_mut();
+            let context = contexts
+                .entry((self.set_id, id))
+                .or_insert_with(|| ZstdContext::new(self.level, Some(dictionary)));
+            f(context)
+        }))
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let id = self.current_id();
+        let compressed = self.with_context(id, |context| context.compress(data))??;
+        let mut payload = Vec::with_capacity(HEADER_LEN + compressed.len());
+        payload.push(HEADER_MAGIC);
+        payload.extend_from_slice(&id.to_le_bytes());
+        payload.extend_from_slice(&compressed);
+        Ok(payload)
+    }
+
+    /// Compresses with the legacy dictionary and without the header, for the readers which predate the headers.
+    pub fn compress_legacy(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.with_context(LEGACY_DICTIONARY_ID, |context| context.compress(data))??)
+    }
+
+    pub fn decompress(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let (id, compressed) = match payload.first() {
+            Some(&HEADER_MAGIC) if payload.len() >= HEADER_LEN => (
+                u32::from_le_bytes(
+                    payload[1..HEADER_LEN]
+                        .try_into()
+                        .expect("Incorrect slice length"),
+                ),
+                &payload[HEADER_LEN..],
+            ),
+            Some(&HEADER_MAGIC) => return Err(Error::TruncatedPayload),
+            _ => (LEGACY_DICTIONARY_ID, payload),
+        };
+        Ok(self.with_context(id, |context| context.decompress(compressed))??)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] =
+        br#"{"Create": {"id": "n1", "discriminator": "Bench", "data": "{\"backrest\": true}"}}"#;
+
+    fn dictionaries() -> Dictionaries {
+        let mut dictionaries = BTreeMap::new();
+        // Raw content dictionaries, zstd accepts any bytes as one.
+        dictionaries.insert(
+            LEGACY_DICTIONARY_ID,
+            br#"{"Create": {"id": "discriminator": "data": "#.repeat(8),
+        );
+        dictionaries.insert(3, br#""Bench" "backrest" true false "#.repeat(8));
+        Dictionaries::new("test", 3, dictionaries)
+    }
+
+    #[test]
+    fn compresses_with_the_current_dictionary() {
+        let dictionaries = dictionaries();
+        let payload = dictionaries.compress(DATA).unwrap();
+        assert_eq!(payload[0], HEADER_MAGIC);
+        assert_eq!(payload[1..HEADER_LEN], 3u32.to_le_bytes());
+        assert_eq!(dictionaries.decompress(&payload).unwrap(), DATA);
+    }
+
+    #[test]
+    fn reads_the_legacy_payloads() {
+        let dictionaries = dictionaries();
+        let payload = dictionaries.compress_legacy(DATA).unwrap();
+        assert_ne!(payload[0], HEADER_MAGIC);
+        assert_eq!(dictionaries.decompress(&payload).unwrap(), DATA);
+        // A payload from before the headers existed, compressed by a set knowing only the legacy dictionary.
+        let mut legacy_only = BTreeMap::new();
+        legacy_only.insert(
+            LEGACY_DICTIONARY_ID,
+            dictionaries
+                .dictionary(LEGACY_DICTIONARY_ID)
+                .unwrap()
+                .to_vec(),
+        );
+        let legacy_only = Dictionaries::new("test", 3, legacy_only);
+        let payload = legacy_only.compress_legacy(DATA).unwrap();
+        assert_eq!(dictionaries.decompress(&payload).unwrap(), DATA);
+    }
+
+    #[test]
+    fn rejects_unknown_and_truncated_headers() {
+        let dictionaries = dictionaries();
+        let mut payload = dictionaries.compress(DATA).unwrap();
+        payload[1..HEADER_LEN].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            dictionaries.decompress(&payload),
+            Err(Error::UnknownDictionary(_, 2))
+        ));
+        assert!(matches!(
+            dictionaries.decompress(&payload[..3]),
+            Err(Error::TruncatedPayload)
+        ));
+    }
+}
@@ -18,4 +18,28 @@ pub enum Error {
     ZstdError(#[from] zstd_util::Error),
     #[error("The retry limit was exceeded")]
     RetryLimitExceeded,
+    #[error("Overpass servers configuration error: {0}")]
+    ServersConfigError(#[from] toml::de::Error),
+    #[error("No Overpass API servers are configured")]
+    NoOverpassServers,
+    #[error("The request dispatcher of an Overpass API server is gone")]
+    ServerDispatcherGone,
+    #[error("The Overpass API response exceeded {0} bytes")]
+    ResponseTooLarge(u64),
+    #[error("The Overpass API response did not finish in {0:?}")]
+    ResponseTimedOut(std::time::Duration),
+    #[error("Malformed Overpass API response: {0}")]
+    MalformedResponse(#[from] serde_json::Error),
+    #[error("The Overpass query timed out: {0}")]
+    OverpassQueryTimeout(String),
+    #[error("The Overpass query ran out of memory: {0}")]
+    OverpassOutOfMemory(String),
+    #[error("Overpass runtime error: {0}")]
+    OverpassRuntimeError(String),
+    #[error("No zstd dictionaries named {0} found")]
+    NoDictionaries(String),
+    #[error("The {0} zstd dictionary with id {1} is not loaded")]
+    UnknownDictionary(String, u32),
+    #[error("The compressed payload is shorter than its header")]
+    TruncatedPayload,
 }
@@ -0,0 +1,133 @@
+use crate::multipolygon::GeometryFix;
+use geo_types::Geometry;
+use hashbrown::HashMap;
+use smol_str::SmolStr;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+struct Entry {
+    geometry: Option<Geometry<f64>>,
+    dependencies: Vec<SmolStr>,
+    fixes: Vec<GeometryFix>,
+}
+
+struct Entries {
+    geometries: HashMap<SmolStr, Entry>,
+    /// For every object, the cached geometries constructed from it, e.g. the ways of a node.
+    dependents: HashMap<SmolStr, Vec<SmolStr>>,
+    insertion_order: VecDeque<SmolStr>,
+}
+
+impl Entries {
+    fn remove(&mut self, id: &str) -> bool {
+        let entry = match self.geometries.remove(id) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        for dependency in entry.dependencies {
+            if let Some(dependents) = self.dependents.get_mut(&dependency) {
+                dependents.retain(|d| d != id);
+                if dependents.is_empty() {
+                    self.dependents.remove(&dependency);
+                }
+            }
+        }
+        true
+    }
+}
+
+/// The already constructed geometries, shared by the threads using an object manager.
+///
+/// It holds at most `capacity` geometries, the oldest ones get evicted first. The ways are usually needed again only by the relations translated shortly after them, so that's enough to avoid most of the repeated constructions.
+pub(crate) struct GeometryCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl GeometryCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries {
+                geometries: HashMap::new(),
+                dependents: HashMap::new(),
+                insertion_order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// The outer None means that the geometry is not cached, the inner one that the object has no geometry.
+    pub(crate) fn get(&self, id: &str) -> Option<Option<Geometry<f64>>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .geometries
+            .get(id)
+            .map(|e| e.geometry.clone())
+    }
+
+    /// The problems of the source data fixed when constructing the cached geometry.
+    pub(crate) fn fixes(&self, id: &str) -> Vec<GeometryFix> {
+        self.entries
+            .lock()
+            .unwrap()
+            .geometries
+            .get(id)
+            .map(|e| e.fixes.clone())
+            .unwrap_or_default()
+    }
+
+    /// Caches the geometry of an object, the dependencies are the ids of the objects it was constructed from.
+    pub(crate) fn insert(
+        &self,
+        id: SmolStr,
+        geometry: Option<Geometry<f64>>,
+        dependencies: Vec<SmolStr>,
+        fixes: Vec<GeometryFix>,
+    ) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.geometries.contains_key(&id) {
+            // Another thread constructed the same geometry meanwhile.
+            return;
+        }
+        for dependency in &dependencies {
+            entries
+                .dependents
+                .entry(dependency.clone())
+                .or_default()
+                .push(id.clone());
+        }
+        entries.geometries.insert(
+            id.clone(),
+            Entry {
+                geometry,
+                dependencies,
+                fixes,
+            },
+        );
+        entries.insertion_order.push_back(id);
+        while entries.insertion_order.len() > self.capacity {
+            if let Some(oldest) = entries.insertion_order.pop_front() {
+                // An invalidated and constructed again geometry gets evicted sooner, that's cheaper than searching the order on every invalidation.
+                entries.remove(&oldest);
+            }
+        }
+    }
+
+    /// Forgets the geometry of a changed object and of everything constructed from it, e.g. the ways and relations containing a moved node.
+    pub(crate) fn invalidate(&self, id: &str) {
+        self.invalidate_all(std::iter::once(SmolStr::from(id)));
+    }
+
+    /// Like `invalidate`, but locks the cache only once for all the objects.
+    pub(crate) fn invalidate_all(&self, ids: impl IntoIterator<Item = SmolStr>) {
+        let mut entries = self.entries.lock().unwrap();
+        let mut pending: Vec<SmolStr> = ids.into_iter().collect();
+        while let Some(id) = pending.pop() {
+            entries.remove(&id);
+            if let Some(dependents) = entries.dependents.remove(&id) {
+                pending.extend(dependents);
+            }
+        }
+    }
+}
@@ -1,12 +1,19 @@
 pub type Result<T> = core::result::Result<T, Error>;
 
+pub mod cache_maintenance;
 pub mod change;
 mod change_iterator;
+pub mod compression;
 mod error;
+mod geometry_cache;
+pub mod multipolygon;
+pub mod node_locations;
 pub mod object;
+pub mod object_metadata;
 pub mod object_manager;
 // mod overpass_api_server;
 pub mod overpass_api;
+pub mod route;
 mod utils;
 pub use error::Error;
 pub use smol_str::SmolStr;
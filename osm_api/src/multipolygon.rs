@@ -0,0 +1,645 @@
+//! Assembly of multipolygon relations from their member ways.
+//!
+//! The ways are joined into rings regardless of their order and direction, and whether a ring is an outer or an inner one is decided by how deeply it is nested, the roles are only checked against that.
+use geo_types::{Geometry, LineString, MultiPolygon, Polygon};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+type Point = (f64, f64);
+type PointKey = (u64, u64);
+
+/// A problem of the source data which was corrected, or at least detected, during the assembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GeometryFix {
+    /// A chain of ways did not end where it started, so it was closed by a straight line.
+    ClosedRing,
+    /// A chain of ways was too short to be closed into a ring, so it was left out.
+    DroppedOpenChain,
+    /// A ring contained repeated points or spikes going there and back.
+    RemovedDuplicatePoints,
+    /// A ring had less than three distinct points or no area at all.
+    DroppedDegenerateRing,
+    /// The role of a ring did not correspond to its nesting.
+    ChangedRole,
+    /// A member which is not a way was ignored.
+    IgnoredNonWayMember,
+    /// A ring crossed or touched itself, so it was split into simple rings at those points.
+    SelfIntersection,
+    /// No polygon could be assembled, so the members were put into a geometry collection.
+    FellBackToGeometryCollection,
+}
+
+impl GeometryFix {
+    pub fn name(&self) -> &'static str {
+        use GeometryFix::*;
+        match self {
+            ClosedRing => "closed_ring",
+            DroppedOpenChain => "dropped_open_chain",
+            RemovedDuplicatePoints => "removed_duplicate_points",
+            DroppedDegenerateRing => "dropped_degenerate_ring",
+            ChangedRole => "changed_role",
+            IgnoredNonWayMember => "ignored_non_way_member",
+            SelfIntersection => "self_intersection",
+            FellBackToGeometryCollection => "fell_back_to_geometry_collection",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Outer,
+    Inner,
+}
+
+impl Role {
+    /// Members with an empty or unknown role are treated as outer ones, the nesting decides anyway.
+    pub fn from_member_role(role: &str) -> Self {
+        match role {
+            "inner" => Role::Inner,
+            _ => Role::Outer,
+        }
+    }
+}
+
+pub struct Assembly {
+    /// A polygon, a multipolygon, or nothing when not a single valid ring could be built.
+    pub geometry: Option<Geometry<f64>>,
+    pub fixes: Vec<GeometryFix>,
+}
+
+struct Ring {
+    role: Role,
+    points: Vec<Point>,
+    area: f64,
+    bbox: (f64, f64, f64, f64),
+}
+
+fn key(point: Point) -> PointKey {
+    (point.0.to_bits(), point.1.to_bits())
+}
+
+fn is_closed(points: &[Point]) -> bool {
+    points.len() > 1 && key(points[0]) == key(points[points.len() - 1])
+}
+
+/// Twice the signed area, positive for counter-clockwise rings.
+fn signed_area(points: &[Point]) -> f64 {
+    points
+        .windows(2)
+        .map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1)
+        .sum()
+}
+
+fn bbox_of(points: &[Point]) -> (f64, f64, f64, f64) {
+    points.iter().fold(
+        (
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        ),
+        |(min_x, min_y, max_x, max_y), &(x, y)| {
+            (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+        },
+    )
+}
+
+fn distinct_point_count(points: &[Point]) -> usize {
+    let mut keys: Vec<_> = points.iter().map(|p| key(*p)).collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys.len()
+}
+
+/// Joins the segments into rings, an endpoint which closes the ring is preferred when more than two segments meet.
+fn build_rings(segments: Vec<(Role, Vec<Point>)>, fixes: &mut Vec<GeometryFix>) -> Vec<Ring> {
+    let mut by_endpoint: HashMap<PointKey, Vec<usize>> = HashMap::new();
+    for (idx, (_, points)) in segments.iter().enumerate() {
+        if points.len() > 1 {
+            by_endpoint.entry(key(points[0])).or_default().push(idx);
+            by_endpoint
+                .entry(key(points[points.len() - 1]))
+                .or_default()
+                .push(idx);
+        }
+    }
+    let mut used = vec![false; segments.len()];
+    let mut rings = vec![];
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let (role, first_points) = &segments[start];
+        if first_points.len() < 2 {
+            fixes.push(GeometryFix::DroppedOpenChain);
+            continue;
+        }
+        let mut points = first_points.clone();
+        let mut reversed = false;
+        while !is_closed(&points) {
+            let end = key(points[points.len() - 1]);
+            let ring_start = key(points[0]);
+            let candidates: Vec<usize> = by_endpoint[&end]
+                .iter()
+                .copied()
+                .filter(|idx| !used[*idx])
+                .collect();
+            let other_end = |idx: usize| {
+                let segment = &segments[idx].1;
+                if key(segment[0]) == end {
+                    key(segment[segment.len() - 1])
+                } else {
+                    key(segment[0])
+                }
+            };
+            let next = candidates
+                .iter()
+                .copied()
+                .find(|idx| other_end(*idx) == ring_start)
+                .or_else(|| candidates.first().copied());
+            match next {
+                Some(idx) => {
+                    used[idx] = true;
+                    let segment = &segments[idx].1;
+                    if key(segment[0]) == end {
+                        points.extend_from_slice(&segment[1..]);
+                    } else {
+                        points.extend(segment.iter().rev().skip(1).copied());
+                    }
+                }
+                // The chain might have started in its middle, so try to extend it on the other side too.
+                None if !reversed => {
+                    reversed = true;
+                    points.reverse();
+                }
+                None => break,
+            }
+        }
+        if !is_closed(&points) {
+            if distinct_point_count(&points) >= 3 {
+                points.push(points[0]);
+                fixes.push(GeometryFix::ClosedRing);
+            } else {
+                fixes.push(GeometryFix::DroppedOpenChain);
+                continue;
+            }
+        }
+        rings.push(Ring {
+            role: *role,
+            points,
+            area: 0.0,
+            bbox: (0.0, 0.0, 0.0, 0.0),
+        });
+    }
+    rings
+}
+
+/// Removes repeated points and spikes, returns whether anything was removed.
+fn remove_duplicate_points(points: &mut Vec<Point>) -> bool {
+    let original_len = points.len();
+    // Work on the open form of the ring, so the spikes around the closing point are found too.
+    points.pop();
+    let mut cleaned: Vec<Point> = Vec::with_capacity(points.len());
+    for &point in points.iter() {
+        if cleaned.last().map(|p| key(*p)) == Some(key(point)) {
+            continue;
+        }
+        if cleaned.len() >= 2 && key(cleaned[cleaned.len() - 2]) == key(point) {
+            cleaned.pop();
+            continue;
+        }
+        cleaned.push(point);
+    }
+    loop {
+        let len = cleaned.len();
+        // Either the closing point is repeated, or the last point is a spike.
+        let last_is_redundant = (len >= 2 && key(cleaned[0]) == key(cleaned[len - 1]))
+            || (len >= 3 && key(cleaned[len - 2]) == key(cleaned[0]));
+        if last_is_redundant {
+            cleaned.pop();
+        } else if len >= 3 && key(cleaned[len - 1]) == key(cleaned[1]) {
+            cleaned.remove(0);
+        } else {
+            break;
+        }
+    }
+    if let Some(first) = cleaned.first().copied() {
+        cleaned.push(first);
+    }
+    *points = cleaned;
+    points.len() != original_len
+}
+
+fn orientation(a: Point, b: Point, c: Point) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+fn within_bbox(a: Point, b: Point, p: Point) -> bool {
+    p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0) && p.1 >= a.1.min(b.1) && p.1 <= a.1.max(b.1)
+}
+
+fn segments_intersect(p1: Point, p2: Point, q1: Point, q2: Point) -> bool {
+    let d1 = orientation(q1, q2, p1);
+    let d2 = orientation(q1, q2, p2);
+    let d3 = orientation(p1, p2, q1);
+    let d4 = orientation(p1, p2, q2);
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+    (d1 == 0.0 && within_bbox(q1, q2, p1))
+        || (d2 == 0.0 && within_bbox(q1, q2, p2))
+        || (d3 == 0.0 && within_bbox(p1, p2, q1))
+        || (d4 == 0.0 && within_bbox(p1, p2, q2))
+}
+
+/// The pairs of non-adjacent edges of a closed ring which meet, found by a sweep over the edges sorted by their smallest x.
+fn intersecting_edges(points: &[Point]) -> Vec<(usize, usize)> {
+    let edge_count = points.len() - 1;
+    let mut edges: Vec<usize> = (0..edge_count).collect();
+    let min_x = |idx: usize| points[idx].0.min(points[idx + 1].0);
+    let max_x = |idx: usize| points[idx].0.max(points[idx + 1].0);
+    edges.sort_unstable_by(|a, b| min_x(*a).partial_cmp(&min_x(*b)).unwrap_or(Ordering::Equal));
+    let mut pairs = vec![];
+    for (pos, &a) in edges.iter().enumerate() {
+        for &b in &edges[pos + 1..] {
+            if min_x(b) > max_x(a) {
+                break;
+            }
+            let (lower, higher) = (a.min(b), a.max(b));
+            let adjacent = higher - lower == 1 || (lower == 0 && higher == edge_count - 1);
+            if !adjacent && segments_intersect(points[a], points[a + 1], points[b], points[b + 1]) {
+                pairs.push((lower, higher));
+            }
+        }
+    }
+    pairs
+}
+
+/// The points where two intersecting segments meet, either their crossing or the endpoints lying on the other segment.
+fn intersection_points(p1: Point, p2: Point, q1: Point, q2: Point) -> Vec<Point> {
+    let d1 = orientation(q1, q2, p1);
+    let d2 = orientation(q1, q2, p2);
+    let d3 = orientation(p1, p2, q1);
+    let d4 = orientation(p1, p2, q2);
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        let t = d1 / (d1 - d2);
+        return vec![(p1.0 + t * (p2.0 - p1.0), p1.1 + t * (p2.1 - p1.1))];
+    }
+    let mut points = vec![];
+    for (orientation, point, a, b) in [
+        (d1, p1, q1, q2),
+        (d2, p2, q1, q2),
+        (d3, q1, p1, p2),
+        (d4, q2, p1, p2),
+    ] {
+        if orientation == 0.0 && within_bbox(a, b, point) {
+            points.push(point);
+        }
+    }
+    points
+}
+
+/// The position of a point lying on a segment, from 0 at its start to 1 at its end.
+fn position_on_segment(start: Point, end: Point, point: Point) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    ((point.0 - start.0) * dx + (point.1 - start.1) * dy) / (dx * dx + dy * dy)
+}
+
+/// Adds the points where the edges of the ring meet to both of the edges, so every intersection becomes a repeated point.
+fn node_ring(points: &[Point], pairs: &[(usize, usize)]) -> Vec<Point> {
+    let mut splits: Vec<Vec<Point>> = vec![vec![]; points.len() - 1];
+    for &(a, b) in pairs {
+        for point in intersection_points(points[a], points[a + 1], points[b], points[b + 1]) {
+            for edge in [a, b] {
+                if key(point) != key(points[edge]) && key(point) != key(points[edge + 1]) {
+                    splits[edge].push(point);
+                }
+            }
+        }
+    }
+    let mut noded = Vec::with_capacity(points.len());
+    for (edge, mut edge_splits) in splits.into_iter().enumerate() {
+        let (start, end) = (points[edge], points[edge + 1]);
+        edge_splits.sort_by(|a, b| {
+            position_on_segment(start, end, *a)
+                .partial_cmp(&position_on_segment(start, end, *b))
+                .unwrap_or(Ordering::Equal)
+        });
+        edge_splits.dedup_by_key(|p| key(*p));
+        noded.push(start);
+        noded.extend(edge_splits);
+    }
+    noded.push(points[points.len() - 1]);
+    noded
+}
+
+/// Splits a closed ring into closed rings at its repeated points, each loop between two visits of a point becomes a ring.
+fn split_at_repeated_points(points: &[Point]) -> Vec<Vec<Point>> {
+    let mut rings = vec![];
+    let mut current: Vec<Point> = vec![];
+    let mut positions: HashMap<PointKey, usize> = HashMap::new();
+    for &point in &points[..points.len() - 1] {
+        match positions.get(&key(point)) {
+            Some(&start) => {
+                let mut ring: Vec<Point> = current.drain(start..).collect();
+                for removed in &ring[1..] {
+                    positions.remove(&key(*removed));
+                }
+                ring.push(point);
+                rings.push(ring);
+                current.push(point);
+            }
+            None => {
+                positions.insert(key(point), current.len());
+                current.push(point);
+            }
+        }
+    }
+    if let Some(first) = current.first().copied() {
+        current.push(first);
+        rings.push(current);
+    }
+    rings
+}
+
+/// Repairs a self intersecting ring by splitting it at the points where it crosses or touches itself, the nesting then decides which of the parts are holes.
+fn split_self_intersecting(points: &[Point], pairs: &[(usize, usize)]) -> Vec<Vec<Point>> {
+    split_at_repeated_points(&node_ring(points, pairs))
+        .into_iter()
+        .map(|mut part| {
+            remove_duplicate_points(&mut part);
+            part
+        })
+        .collect()
+}
+
+fn ring_contains_point(points: &[Point], point: Point) -> bool {
+    let mut inside = false;
+    for edge in points.windows(2) {
+        let (a, b) = (edge[0], edge[1]);
+        if (a.1 > point.1) != (b.1 > point.1)
+            && point.0 < (b.0 - a.0) * (point.1 - a.1) / (b.1 - a.1) + a.0
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Whether the inner ring lies in the outer one, the rings may touch in their vertices.
+fn ring_contains_ring(outer: &Ring, inner: &Ring) -> bool {
+    let (o_min_x, o_min_y, o_max_x, o_max_y) = outer.bbox;
+    let (i_min_x, i_min_y, i_max_x, i_max_y) = inner.bbox;
+    if i_min_x < o_min_x || i_min_y < o_min_y || i_max_x > o_max_x || i_max_y > o_max_y {
+        return false;
+    }
+    let shared: HashSet<PointKey> = outer.points.iter().map(|p| key(*p)).collect();
+    match inner.points.iter().find(|p| !shared.contains(&key(**p))) {
+        Some(point) => ring_contains_point(&outer.points, *point),
+        None => false,
+    }
+}
+
+fn oriented(mut points: Vec<Point>, counter_clockwise: bool) -> LineString<f64> {
+    if (signed_area(&points) > 0.0) != counter_clockwise {
+        points.reverse();
+    }
+    points.into()
+}
+
+/// Assembles the ways of a multipolygon relation together with the roles they have there.
+pub fn assemble(members: Vec<(Role, LineString<f64>)>) -> Assembly {
+    let mut fixes = vec![];
+    let segments = members
+        .into_iter()
+        .map(|(role, line)| (role, line.0.iter().map(|c| (c.x, c.y)).collect()))
+        .collect();
+    let mut rings = vec![];
+    for mut ring in build_rings(segments, &mut fixes) {
+        if remove_duplicate_points(&mut ring.points) {
+            fixes.push(GeometryFix::RemovedDuplicatePoints);
+        }
+        let pairs = if ring.points.len() < 4 {
+            vec![]
+        } else {
+            intersecting_edges(&ring.points)
+        };
+        let parts = if pairs.is_empty() {
+            vec![ring.points]
+        } else {
+            fixes.push(GeometryFix::SelfIntersection);
+            split_self_intersecting(&ring.points, &pairs)
+        };
+        for points in parts {
+            let area = signed_area(&points).abs();
+            if points.len() < 4 || area == 0.0 {
+                fixes.push(GeometryFix::DroppedDegenerateRing);
+                continue;
+            }
+            rings.push(Ring {
+                role: ring.role,
+                bbox: bbox_of(&points),
+                points,
+                area,
+            });
+        }
+    }
+    // The larger rings go first, so a ring can only be contained in one which was already processed.
+    rings.sort_by(|a, b| b.area.partial_cmp(&a.area).unwrap_or(Ordering::Equal));
+    let mut parents: Vec<Option<usize>> = Vec::with_capacity(rings.len());
+    let mut depths: Vec<usize> = Vec::with_capacity(rings.len());
+    for (idx, ring) in rings.iter().enumerate() {
+        // The smallest containing ring is the last one which contains it.
+        let parent = (0..idx)
+            .rev()
+            .find(|candidate| ring_contains_ring(&rings[*candidate], ring));
+        depths.push(parent.map_or(0, |p| depths[p] + 1));
+        parents.push(parent);
+    }
+    let mut polygons: Vec<(LineString<f64>, Vec<LineString<f64>>)> = vec![];
+    let mut polygon_of_ring = HashMap::new();
+    for (idx, ring) in rings.into_iter().enumerate() {
+        let role = if depths[idx] % 2 == 0 {
+            Role::Outer
+        } else {
+            Role::Inner
+        };
+        if role != ring.role {
+            fixes.push(GeometryFix::ChangedRole);
+        }
+        match role {
+            Role::Outer => {
+                polygon_of_ring.insert(idx, polygons.len());
+                polygons.push((oriented(ring.points, true), vec![]));
+            }
+            Role::Inner => {
+                let parent = parents[idx].expect("An inner ring always has a parent");
+                polygons[polygon_of_ring[&parent]]
+                    .1
+                    .push(oriented(ring.points, false));
+            }
+        }
+    }
+    let mut polygons: Vec<Polygon<f64>> = polygons
+        .into_iter()
+        .map(|(exterior, interiors)| Polygon::new(exterior, interiors))
+        .collect();
+    let geometry = match polygons.len() {
+        0 => None,
+        1 => polygons.pop().map(Geometry::Polygon),
+        _ => Some(Geometry::MultiPolygon(MultiPolygon(polygons))),
+    };
+    Assembly { geometry, fixes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn way(points: &[(f64, f64)]) -> LineString<f64> {
+        points.to_vec().into()
+    }
+
+    fn polygons_of(assembly: &Assembly) -> Vec<Polygon<f64>> {
+        match &assembly.geometry {
+            Some(Geometry::Polygon(polygon)) => vec![polygon.clone()],
+            Some(Geometry::MultiPolygon(multi)) => multi.0.clone(),
+            other => panic!("Expected a polygon, got {:?}", other),
+        }
+    }
+
+    fn ring_points(ring: &LineString<f64>) -> Vec<Point> {
+        ring.0.iter().map(|c| (c.x, c.y)).collect()
+    }
+
+    #[test]
+    fn joins_unordered_and_reversed_ways() {
+        let assembly = assemble(vec![
+            (Role::Outer, way(&[(2.0, 2.0), (0.0, 2.0), (0.0, 0.0)])),
+            (Role::Outer, way(&[(0.0, 0.0), (2.0, 0.0)])),
+            (Role::Outer, way(&[(2.0, 2.0), (2.0, 0.0)])),
+        ]);
+        assert!(assembly.fixes.is_empty());
+        let polygons = polygons_of(&assembly);
+        assert_eq!(polygons.len(), 1);
+        let exterior = ring_points(polygons[0].exterior());
+        assert_eq!(exterior.len(), 5);
+        // The exteriors are counter-clockwise.
+        assert_eq!(signed_area(&exterior), 8.0);
+    }
+
+    #[test]
+    fn nesting_decides_the_roles() {
+        let assembly = assemble(vec![
+            (
+                Role::Outer,
+                way(&[(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 2.0), (1.0, 1.0)]),
+            ),
+            (
+                Role::Outer,
+                way(&[(0.0, 0.0), (3.0, 0.0), (3.0, 3.0), (0.0, 3.0), (0.0, 0.0)]),
+            ),
+            (
+                Role::Inner,
+                way(&[(5.0, 5.0), (6.0, 5.0), (6.0, 6.0), (5.0, 5.0)]),
+            ),
+        ]);
+        assert_eq!(
+            assembly.fixes,
+            vec![GeometryFix::ChangedRole, GeometryFix::ChangedRole]
+        );
+        let polygons = polygons_of(&assembly);
+        assert_eq!(polygons.len(), 2);
+        assert_eq!(polygons[0].interiors().len(), 1);
+        // The holes are clockwise.
+        assert_eq!(signed_area(&ring_points(&polygons[0].interiors()[0])), -2.0);
+        assert!(polygons[1].interiors().is_empty());
+    }
+
+    #[test]
+    fn closes_open_chains_and_drops_the_short_ones() {
+        let assembly = assemble(vec![
+            (Role::Outer, way(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)])),
+            (Role::Outer, way(&[(5.0, 5.0), (6.0, 5.0)])),
+        ]);
+        assert_eq!(
+            assembly.fixes,
+            vec![GeometryFix::ClosedRing, GeometryFix::DroppedOpenChain]
+        );
+        assert_eq!(ring_points(polygons_of(&assembly)[0].exterior()).len(), 4);
+    }
+
+    #[test]
+    fn removes_duplicate_points_and_spikes() {
+        let assembly = assemble(vec![(
+            Role::Outer,
+            way(&[
+                (0.0, 0.0),
+                (1.0, 0.0),
+                (1.0, 0.0),
+                (1.0, 1.0),
+                (1.0, 2.0),
+                (1.0, 1.0),
+                (0.0, 1.0),
+                (0.0, 0.0),
+            ]),
+        )]);
+        assert_eq!(assembly.fixes, vec![GeometryFix::RemovedDuplicatePoints]);
+        assert_eq!(ring_points(polygons_of(&assembly)[0].exterior()).len(), 5);
+    }
+
+    #[test]
+    fn drops_degenerate_rings() {
+        let assembly = assemble(vec![(
+            Role::Outer,
+            way(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (0.0, 0.0)]),
+        )]);
+        assert_eq!(assembly.fixes, vec![GeometryFix::DroppedDegenerateRing]);
+        assert!(assembly.geometry.is_none());
+    }
+
+    #[test]
+    fn splits_a_bowtie_into_two_polygons() {
+        let assembly = assemble(vec![(
+            Role::Outer,
+            way(&[(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0), (0.0, 0.0)]),
+        )]);
+        assert_eq!(assembly.fixes, vec![GeometryFix::SelfIntersection]);
+        let polygons = polygons_of(&assembly);
+        assert_eq!(polygons.len(), 2);
+        for polygon in &polygons {
+            let exterior = ring_points(polygon.exterior());
+            assert_eq!(exterior.len(), 4);
+            assert!(exterior.contains(&(1.0, 1.0)));
+            assert!(intersecting_edges(&exterior).is_empty());
+            assert_eq!(signed_area(&exterior), 2.0);
+        }
+    }
+
+    #[test]
+    fn a_ring_touching_itself_gets_a_hole() {
+        // The ring goes around the square and then around a smaller one inside, touching the outer one at (0, 0).
+        let assembly = assemble(vec![(
+            Role::Outer,
+            way(&[
+                (0.0, 0.0),
+                (4.0, 0.0),
+                (4.0, 4.0),
+                (0.0, 4.0),
+                (0.0, 0.0),
+                (1.0, 2.0),
+                (2.0, 2.0),
+                (2.0, 1.0),
+                (0.0, 0.0),
+            ]),
+        )]);
+        assert_eq!(
+            assembly.fixes,
+            vec![GeometryFix::SelfIntersection, GeometryFix::ChangedRole]
+        );
+        let polygons = polygons_of(&assembly);
+        assert_eq!(polygons.len(), 1);
+        assert_eq!(polygons[0].interiors().len(), 1);
+    }
+}
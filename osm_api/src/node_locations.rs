@@ -0,0 +1,85 @@
+use crate::Result;
+use sled::transaction::{TransactionalTree, UnabortableTransactionError};
+use sled::{Db, Tree};
+use std::convert::TryInto;
+
+const TREE_NAME: &str = "node_locations";
+// The OSM coordinates have seven decimal places, so the fixed point values round trip exactly.
+const SCALE: f64 = 10_000_000.0;
+
+fn encode(lon: f64, lat: f64) -> [u8; 8] {
+    let mut value = [0; 8];
+    value[..4].copy_from_slice(&((lon * SCALE).round() as i32).to_le_bytes());
+    value[4..].copy_from_slice(&((lat * SCALE).round() as i32).to_le_bytes());
+    value
+}
+
+fn decode(value: &[u8]) -> (f64, f64) {
+    let lon = i32::from_le_bytes(value[..4].try_into().expect("Incorrect slice length"));
+    let lat = i32::from_le_bytes(value[4..8].try_into().expect("Incorrect slice length"));
+    (f64::from(lon) / SCALE, f64::from(lat) / SCALE)
+}
+
+/// The locations of the cached nodes, so the way geometries can be built without deserializing every node from the entities cache.
+///
+/// It lives in its own tree of the cache database, the handles are cheap to clone and share between the object managers.
+#[derive(Clone)]
+pub struct NodeLocations {
+    tree: Tree,
+}
+
+impl NodeLocations {
+    pub fn open(cache: &Db) -> Result<Self> {
+        Ok(Self {
+            tree: cache.open_tree(TREE_NAME)?,
+        })
+    }
+
+    pub fn insert(&self, id: u64, lon: f64, lat: f64) -> Result<()> {
+        self.tree.insert(id.to_be_bytes(), &encode(lon, lat))?;
+        Ok(())
+    }
+
+    pub(crate) fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /// Like `insert`, but within a transaction over the tree returned by `tree`.
+    pub(crate) fn insert_in(
+        tree: &TransactionalTree,
+        id: u64,
+        lon: f64,
+        lat: f64,
+    ) -> std::result::Result<(), UnabortableTransactionError> {
+        tree.insert(&id.to_be_bytes(), &encode(lon, lat))?;
+        Ok(())
+    }
+
+    /// Returns the longitude and latitude of the node.
+    pub fn get(&self, id: u64) -> Result<Option<(f64, f64)>> {
+        Ok(self.tree.get(id.to_be_bytes())?.map(|v| decode(&v)))
+    }
+
+    pub fn remove(&self, id: u64) -> Result<()> {
+        self.tree.remove(id.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Iterates the ids of the nodes with a known location.
+    pub fn ids(&self) -> impl Iterator<Item = Result<u64>> {
+        self.tree.iter().keys().map(|key| {
+            let key = key?;
+            Ok(u64::from_be_bytes(
+                key.as_ref().try_into().expect("Incorrect key length"),
+            ))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+}
@@ -1,46 +1,57 @@
+use crate::cache_maintenance;
 use crate::change::OSMObjectChange;
 use crate::change_iterator::OSMObjectChangeIterator;
-use crate::object::{OSMObject, OSMObjectFromNetwork, OSMObjectSpecifics, OSMObjectType};
-use crate::overpass_api::Servers;
+use crate::compression::Dictionaries;
+use crate::geometry_cache::GeometryCache;
+use crate::multipolygon::{self, GeometryFix, Role};
+use crate::node_locations::NodeLocations;
+use crate::object::{OSMObject, OSMObjectSpecifics, OSMObjectType};
+use crate::object_metadata::ObjectMetadataTree;
+use crate::overpass_api::{self, Servers};
+use crate::route::{self, MemberKind};
 use crate::utils;
 use crate::{Error, Result};
 use chrono::{DateTime, Utc};
 use geo_types::{Geometry, LineString, Point, Polygon};
-use hashbrown::HashMap;
 use itertools::Itertools;
 use log::{debug, error, info, warn};
 use once_cell::sync::Lazy;
+use sled::transaction::{TransactionResult, Transactional};
 use sled::Db;
-use serde::Deserialize;
-use serde_json::{self, Deserializer};
 use smol_str::SmolStr;
-use std::cell::{Ref, RefCell};
-use std::cmp;
 use std::collections::HashSet;
-use std::fs;
 use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
-use zstd_util::ZstdContext;
 
 const COMPRESSION_LEVEL: i32 = 10;
-
-static ZSTD_CONTEXT: Lazy<Mutex<ZstdContext>> = Lazy::new(|| {
-    let dict = fs::read("fts.dict").expect("Could not read ZSTD dictionary.");
-    Mutex::new(ZstdContext::new(COMPRESSION_LEVEL, Some(&dict)))
+pub const CACHE_PATH: &str = "entities_cache";
+// Enough for the members of the relations translated shortly after them, the whole area would not fit into memory.
+const GEOMETRIES_CACHE_CAPACITY: usize = 200_000;
+// The received objects are stored in batches, a transaction per object would slow the area retrieval down.
+const STORE_BATCH_SIZE: usize = 1000;
+// How many times a failing area data query gets sent, possibly to different servers.
+const AREA_QUERY_ATTEMPTS: usize = 3;
+
+/// The `fts.dict` and `fts.<id>.dict` dictionaries from the working directory.
+pub static CACHE_DICTIONARIES: Lazy<Dictionaries> = Lazy::new(|| {
+    Dictionaries::from_dir(Path::new("."), "fts", COMPRESSION_LEVEL)
+        .expect("Could not read ZSTD dictionary.")
 });
 
 fn serialize_and_compress(object: &OSMObject) -> Result<Vec<u8>> {
     let serialized = bincode::serialize(&object)?;
-    Ok(ZSTD_CONTEXT.lock().unwrap().compress(&serialized)?)
+    CACHE_DICTIONARIES.compress(&serialized)
 }
 
 pub fn open_cache() -> Result<Db> {
-    Ok(sled::open("entities_cache")?)
+    Ok(sled::open(CACHE_PATH)?)
 }
 
-fn deserialize_compressed(compressed: &[u8]) -> Result<OSMObject> {
-    let serialized = ZSTD_CONTEXT.lock().unwrap().decompress(compressed)?;
+pub(crate) fn deserialize_compressed(compressed: &[u8]) -> Result<OSMObject> {
+    let serialized = CACHE_DICTIONARIES.decompress(compressed)?;
     Ok(bincode::deserialize(&serialized)?)
 }
 
@@ -69,47 +80,119 @@ fn format_data_retrieval(area: i64) -> String {
     )
 }
 
+/// The manager is `Sync`, so the threads working with the same cache should share one instead of creating more of them.
 pub struct OSMObjectManager {
-    geometries_cache: RefCell<HashMap<SmolStr, Option<Geometry<f64>>>>,
+    geometries_cache: GeometryCache,
     api_servers: Arc<Servers>,
     cache: Arc<Db>,
-    retrieved_from_network: RefCell<HashSet<SmolStr>>,
-    cache_queries: RefCell<u32>,
-    cache_hits: RefCell<u32>,
+    node_locations: NodeLocations,
+    metadata: ObjectMetadataTree,
+    retrieved_from_network: Mutex<HashSet<SmolStr>>,
+    cache_queries: AtomicU32,
+    cache_hits: AtomicU32,
 }
 impl OSMObjectManager {
-    /// Use this constructor only if there's only one OSMObjectManager at a time.
+    /// Opens the cache, so use this constructor only once per process and share the manager between the threads.
     pub fn new() -> Result<Self> {
-        Self::new_multithread(Arc::new(Servers::default()), Arc::new(open_cache()?))
+        Self::new_multithread(Arc::new(Servers::from_env()?), Arc::new(open_cache()?))
     }
 
-    /// Creates an OsmObjectManager in a scenario where each thread has its own instance and there are at least two of these.
+    /// Creates an OsmObjectManager using an already opened cache and the servers of another manager.
     pub fn new_multithread(servers: Arc<Servers>, cache: Arc<Db>) -> Result<Self> {
+        let node_locations = NodeLocations::open(&cache)?;
+        Self::with_node_locations(servers, cache, node_locations)
+    }
+
+    /// Like `new_multithread`, but reusing the node locations handle of another manager.
+    pub fn with_node_locations(
+        servers: Arc<Servers>,
+        cache: Arc<Db>,
+        node_locations: NodeLocations,
+    ) -> Result<Self> {
+        let metadata = ObjectMetadataTree::open(&cache)?;
         Ok(OSMObjectManager {
             api_servers: servers,            cache,
-            geometries_cache: RefCell::new(HashMap::new()),
-            retrieved_from_network: RefCell::new(HashSet::new()),
-            cache_queries: RefCell::new(0),
-            cache_hits: RefCell::new(0),
+            node_locations,
+            metadata,
+            geometries_cache: GeometryCache::new(GEOMETRIES_CACHE_CAPACITY),
+            retrieved_from_network: Mutex::new(HashSet::new()),
+            cache_queries: AtomicU32::new(0),
+            cache_hits: AtomicU32::new(0),
         })
     }
 
-    pub fn get_ids_retrieved_from_network(&self) -> Ref<HashSet<SmolStr>> {
-        self.retrieved_from_network.borrow()
+    pub fn node_locations(&self) -> &NodeLocations {
+        &self.node_locations
+    }
+
+    /// Returns a snapshot, the objects retrieved later, e.g. the missing dependencies found during a translation, are not included.
+    pub fn get_ids_retrieved_from_network(&self) -> HashSet<SmolStr> {
+        self.retrieved_from_network.lock().unwrap().clone()
     }
 
     pub fn cache_object(&self, object: &OSMObject) {
-        let compressed = serialize_and_compress(object).expect("Could not serialize object");
-        self.cache
-            .insert(object.unique_id().as_bytes(), compressed)
-            .expect("Could not cache object.");
+        self.store_object(object, None);
+    }
+
+    /// Caches an object received as a part of the data of the area, the object stays cached while the area exists.
+    pub fn cache_area_object(&self, area: i64, object: &OSMObject) {
+        self.store_object(object, Some(area));
+    }
+
+    fn store_object(&self, object: &OSMObject, area: Option<i64>) {
+        self.store_objects(std::slice::from_ref(object), area);
+    }
+
+    fn store_objects(&self, objects: &[OSMObject], area: Option<i64>) {
+        let compressed: Vec<_> = objects
+            .iter()
+            .map(|o| serialize_and_compress(o).expect("Could not serialize object"))
+            .collect();
+        // One transaction, so the garbage collection never finds an object without its metadata.
+        let stored: TransactionResult<()> = (
+            &**self.cache,
+            self.node_locations.tree(),
+            self.metadata.tree(),
+        )
+            .transaction(|(cache, locations, metadata)| {
+                for (object, compressed) in objects.iter().zip(&compressed) {
+                    let id = object.unique_id();
+                    cache.insert(id.as_bytes(), compressed.as_slice())?;
+                    if let OSMObjectSpecifics::Node { lon, lat } = object.specifics {
+                        NodeLocations::insert_in(locations, object.id, lon, lat)?;
+                    }
+                    ObjectMetadataTree::touch_in(metadata, &id, area)?;
+                }
+                Ok(())
+            });
+        stored.expect("Could not cache object.");
+        self.geometries_cache
+            .invalidate_all(objects.iter().map(|o| o.unique_id()));
+    }
+
+    /// Stores a batch of the objects received from the network, and moves them to the returned objects when they are wanted.
+    fn store_received(
+        &self,
+        batch: &mut Vec<OSMObject>,
+        area: Option<i64>,
+        returned: Option<&mut Vec<OSMObject>>,
+    ) {
+        self.retrieved_from_network
+            .lock()
+            .unwrap()
+            .extend(batch.iter().map(|o| o.unique_id()));
+        self.store_objects(batch, area);
+        match returned {
+            Some(returned) => returned.append(batch),
+            None => batch.clear(),
+        }
     }
 
     fn has_object(&self, id: &str) -> bool {
-        *self.cache_queries.borrow_mut() += 1;
+        self.cache_queries.fetch_add(1, Ordering::Relaxed);
         let exists = self.cache.contains_key(&id).expect("Cache query failed.");
         if exists {
-            *self.cache_hits.borrow_mut() += 1;
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
         }
         exists
     }
@@ -131,39 +214,41 @@ impl OSMObjectManager {
         &self,
         readable: Box<dyn Read>,
         return_objects: bool,
+        area: Option<i64>,
     ) -> Result<Vec<OSMObject>> {
         let start = Instant::now();
         let mut objects = Vec::new();
-        let mut cached_readable = BufReader::with_capacity(65536, readable);
-        let mut buf = [0; 1];
-        loop {
-            let _bytes = cached_readable.read(&mut buf)?;
-            if String::from_utf8_lossy(&buf[..]) == "[" {
-                break;
-            }
-        }
-        // We're past the data header, only objects follow.
-        loop {
-            {
-                let mut de = Deserializer::from_reader(&mut cached_readable);
-                match OSMObjectFromNetwork::deserialize(&mut de) {
-                    Ok(obj) => {
-                        let internal_object = obj.into_osm_object();
-                        self.retrieved_from_network
-                            .borrow_mut()
-                            .insert(internal_object.unique_id());
-                        self.cache_object(&internal_object);
-                        if return_objects {
-                            objects.push(internal_object);
-                        }
-                    }
-                    Err(_e) => break,
+        let mut batch = Vec::with_capacity(STORE_BATCH_SIZE);
+        let result = overpass_api::for_each_element(
+            BufReader::with_capacity(65536, readable),
+            |obj| {
+                batch.push(obj.into_osm_object());
+                if batch.len() == STORE_BATCH_SIZE {
+                    self.store_received(
+                        &mut batch,
+                        area,
+                        return_objects.then_some(&mut objects),
+                    );
                 }
-            }
-            cached_readable.read_exact(&mut buf)?; // Skip a comma
-        }
-        debug!("Caching finished after {:?}", start.elapsed());
+                Ok(())
+            },
+            |progress| {
+                info!(
+                    "Received {} MiB of the query result, {} objects so far.",
+                    progress.bytes / (1024 * 1024),
+                    progress.elements
+                )
+            },
+        );
+        // Even an incomplete result is worth keeping, the objects are valid.
+        self.store_received(&mut batch, area, return_objects.then_some(&mut objects));
         self.flush_cache();
+        let progress = result?;
+        debug!(
+            "Caching of {} objects finished after {:?}",
+            progress.elements,
+            start.elapsed()
+        );
         Ok(objects)
     }
 
@@ -171,9 +256,25 @@ impl OSMObjectManager {
         info!("Looking up all objects in area {}.", area);
         // Area retrieval queries are costly, so tell the server about it upfront.
         let query = format_query(900, 1073741824, &format_data_retrieval(area));
-        let readable = self.run_query(&query, false)?;
-        self.cache_objects_from(readable, false)?;
-        Ok(())
+        let mut attempt = 1;
+        loop {
+            let readable = self.run_query(&query, false)?;
+            // The server failure got recorded, so the next attempt likely goes to another server.
+            match self.cache_objects_from(readable, false, Some(area)) {
+                Err(
+                    e @ (Error::OverpassQueryTimeout(_)
+                    | Error::OverpassRuntimeError(_)
+                    | Error::OverpassOutOfMemory(_)),
+                ) if attempt < AREA_QUERY_ATTEMPTS => {
+                    warn!(
+                        "Looking up the objects in area {} failed in attempt {}: {}",
+                        area, attempt, e
+                    );
+                    attempt += 1;
+                }
+                result => return result.map(|_| ()),
+            }
+        }
     }
 
     fn lookup_objects<S: AsRef<str>>(&self, ids: &mut [S]) -> Result<()> {
@@ -203,7 +304,7 @@ impl OSMObjectManager {
                     &format!("{}(id:{})", translate_type_shortcut(entity_type), ids_str),
                 );
                 let readable = self.run_query(&query, false)?;
-                objects.extend(self.cache_objects_from(readable, true)?);
+                objects.extend(self.cache_objects_from(readable, true, None)?);
             }
         }
         self.ensure_has_cached_dependencies_for(&objects)?;
@@ -287,19 +388,34 @@ impl OSMObjectManager {
 
     fn get_way_coords(&self, way: &OSMObject) -> Result<LineString<f64>> {
         use self::OSMObjectSpecifics::{Node, Way};
-        let node_count = match &way.specifics {
-            Way { nodes } => nodes.len(),
+        let nodes = match &way.specifics {
+            Way { nodes } => nodes,
             _ => unreachable!(),
         };
-        let mut coords = Vec::with_capacity(node_count);
-        for obj in self.related_objects_of(way)? {
-            match obj.specifics {
-                Node { lon, lat } => {
-                    coords.push((lon, lat));
+        let mut locations = Vec::with_capacity(nodes.len());
+        let mut any_missing = false;
+        for node in nodes {
+            let location = self.node_locations.get(*node)?;
+            any_missing |= location.is_none();
+            locations.push(location);
+        }
+        if any_missing {
+            // The nodes cached before the locations index existed, or not cached at all.
+            self.ensure_has_cached_dependencies_for(&[way])?;
+            for (node, location) in nodes.iter().zip(locations.iter_mut()) {
+                if location.is_some() {
+                    continue;
+                }
+                match self.get_cached_object(&format!("n{}", node)) {
+                    Ok(Some(OSMObject { specifics: Node { lon, lat }, .. })) => {
+                        self.node_locations.insert(*node, lon, lat)?;
+                        *location = Some((lon, lat));
+                    }
+                    _ => error!("The OSM API did not return the node {} of way {}, or its retrieval failed.", node, way.id),
                 }
-                _ => unreachable!(),
             }
         }
+        let coords: Vec<(f64, f64)> = locations.into_iter().flatten().collect();
         Ok(coords.into())
     }
 
@@ -313,22 +429,28 @@ impl OSMObjectManager {
     }
 
     fn get_geometry_of(&self, object: &OSMObject) -> Result<Option<Geometry<f64>>> {
-        let exists = self
-            .geometries_cache
-            .borrow()
-            .contains_key(&object.unique_id());
-        if exists {
-            Ok(self.geometries_cache.borrow()[&object.unique_id()].clone())
+        let id = object.unique_id();
+        if let Some(cached) = self.geometries_cache.get(&id) {
+            Ok(cached)
         } else {
-            let res = self.get_geometry_of_uncached(object)?;
-            self.geometries_cache
-                .borrow_mut()
-                .insert(object.unique_id(), res.clone());
+            // Not holding the cache locked, the geometries of the members are constructed through it.
+            let mut fixes = vec![];
+            let res = self.get_geometry_of_uncached(object, &mut fixes)?;
+            if !fixes.is_empty() {
+                debug!("The geometry of object {} needed fixes: {:?}", id, fixes);
+            }
+            let dependencies = object.related_ids().map(|(id, _)| id.into()).collect();
+            // The fixes are kept with the geometry, so they are known for as long as it is reused.
+            self.geometries_cache.insert(id, res.clone(), dependencies, fixes);
             Ok(res)
         }
     }
 
-    fn get_geometry_of_uncached(&self, object: &OSMObject) -> Result<Option<Geometry<f64>>> {
+    fn get_geometry_of_uncached(
+        &self,
+        object: &OSMObject,
+        fixes: &mut Vec<GeometryFix>,
+    ) -> Result<Option<Geometry<f64>>> {
         use self::OSMObjectSpecifics::*;
         match object.specifics {
             Node { lon, lat } => Ok(Some(Geometry::Point(Point::new(lon, lat)))),
@@ -355,7 +477,7 @@ impl OSMObjectManager {
                         .unwrap_or("")
                     {
                         "inner" | "outer" => {
-                            self.construct_multipolygon_from_complex_polygons(object)?
+                            self.construct_multipolygon_from_complex_polygons(object, fixes)?
                         }
                         _ => {
                             self.construct_multipolygon_from_polygons(object)?
@@ -364,8 +486,14 @@ impl OSMObjectManager {
                     if let Some(geom) = multi {
                         Ok(Some(geom))
                     } else {
+                        fixes.push(GeometryFix::FellBackToGeometryCollection);
                         self.create_geometry_collection(object)
                     }
+                } else if geom_type == "route" || geom_type == "route_master" {
+                    match self.construct_route_line(object)? {
+                        Some(line) => Ok(Some(line)),
+                        None => self.create_geometry_collection(object),
+                    }
                 } else {
                     self.create_geometry_collection(object)
                 }
@@ -383,6 +511,30 @@ impl OSMObjectManager {
         )))
     }
 
+    fn construct_route_line(&self, object: &OSMObject) -> Result<Option<Geometry<f64>>> {
+        if object.tags.get("type").map(|t| t.as_str()) == Some("route_master") {
+            let mut geometries = vec![];
+            for related in self.related_objects_of(object)? {
+                if related.object_type() == OSMObjectType::Relation {
+                    if let Some(geometry) = self.get_geometry_of(&related)? {
+                        geometries.push(geometry);
+                    }
+                }
+            }
+            return Ok(route::merge_routes(geometries));
+        }
+        let mut ways = vec![];
+        for related in self.related_objects_of(object)? {
+            let role = related.tags.get("role").map(|r| r.as_str()).unwrap_or("");
+            if related.object_type() == OSMObjectType::Way
+                && MemberKind::from_role(role) == MemberKind::Way
+            {
+                ways.push(self.get_way_coords(&related)?);
+            }
+        }
+        Ok(route::merge_ways(ways))
+    }
+
     fn construct_multipolygon_from_polygons(
         &self,
         object: &OSMObject,
@@ -406,65 +558,33 @@ impl OSMObjectManager {
     fn construct_multipolygon_from_complex_polygons(
         &self,
         object: &OSMObject,
+        fixes: &mut Vec<GeometryFix>,
     ) -> Result<Option<Geometry<f64>>> {
-        let mut inners = vec![];
-        let mut outers = vec![];
+        let mut members = vec![];
         for related in self.related_objects_of(object)? {
-            if !related.tags.contains_key("role") {
-                warn!(
-                    "Missing role specifier for object {} as part of geometry of object {}",
+            if related.object_type() != OSMObjectType::Way {
+                debug!(
+                    "Ignoring member {} of multipolygon {}, it is not a way.",
                     related.unique_id(),
                     object.unique_id()
                 );
-                return Ok(None);
-            }
-            if !(related.object_type() == OSMObjectType::Way) {
-                warn!(
-                    "Creation of a point sequence for object {} not supported/impossible.",
-                    related.unique_id()
-                );
-                return Ok(None);
-            }
-            let points = self.get_way_coords(&related)?;
-            match related.tags["role"].as_ref() {
-                "inner" => inners.push(points),
-                "outer" => outers.push(points),
-                _ => {
-                    warn!("Unknown multipolygon part tole {}.", related.tags["role"]);
-                    return Ok(None);
-                }
-            }
-        }
-        utils::connect_polygon_segments(&mut inners);
-        utils::connect_polygon_segments(&mut outers);
-        if outers.len() != 1 && !inners.is_empty() {
-            warn!("multiple outer ring(s) and some inner ring(s), geometry for object {} is ambiguous.", object.unique_id());
-            return Ok(None);
-        }
-        let mut polys = Vec::with_capacity(cmp::max(inners.len(), outers.len()));
-        // Because i could not manage to convince the loop that references are enough, the check must be done there
-        let inners_is_empty = inners.is_empty();
-        for inner in &inners {
-            if inner.0.len() < 4 {
-                warn!("One of the inner polygons for object {} did not have enough points, falling back to a geometry collection.", object.unique_id());
-                return Ok(None);
+                fixes.push(GeometryFix::IgnoredNonWayMember);
+                continue;
             }
-            polys.push(Polygon::new(outers[0].clone(), inners.clone()))
-        }
-        if inners_is_empty {
-            for outer in outers {
-                if outer.0.len() < 4 {
-                    warn!("One of the outer rings of object {} did not have enough points, falling back to a geometry collection.", object.unique_id());
-                    return Ok(None);
-                }
-                polys.push(Polygon::new(outer, vec![]));
-            }
-        }
-        if polys.len() == 1 {
-            Ok(Some(Geometry::Polygon(polys[0].clone())))
-        } else {
-            Ok(Some(Geometry::MultiPolygon(polys.into())))
+            let role = related.tags.get("role").map(|r| r.as_str()).unwrap_or("");
+            members.push((
+                Role::from_member_role(role),
+                self.get_way_coords(&related)?,
+            ));
         }
+        let assembly = multipolygon::assemble(members);
+        fixes.extend(assembly.fixes);
+        Ok(assembly.geometry)
+    }
+
+    /// Returns the problems which were fixed when constructing the geometry of the given object, the geometry must have been constructed before.
+    pub fn geometry_fixes_of(&self, id: &str) -> Vec<GeometryFix> {
+        self.geometries_cache.fixes(id)
     }
 
     fn flush_cache(&self) {
@@ -499,7 +619,7 @@ impl OSMObjectManager {
     pub fn get_area_parents(&self, area_id: i64) -> Result<Vec<OSMObject>> {
         let query = format!("[out:json];rel({});<<;out meta;", area_id - 3_600_000_000);
         let readable = self.run_query(&query, false)?;
-        self.cache_objects_from(readable, true)
+        self.cache_objects_from(readable, true, None)
     }
 
     pub fn cached_objects(
@@ -515,17 +635,39 @@ impl OSMObjectManager {
 
     pub fn remove_cached_object(&self, id: &str) -> Result<()> {
         self.cache.remove(id)?;
+        self.metadata.remove(id)?;
+        self.geometries_cache.invalidate(id);
+        if let Some(node_id) = id.strip_prefix('n') {
+            if let Ok(node_id) = node_id.parse() {
+                self.node_locations.remove(node_id)?;
+            }
+        }
         Ok(())
     }
+
+    /// Removes the object from the area, the object itself gets removed from the cache only if no other area contains it.
+    pub fn remove_area_object(&self, area: i64, id: &str) -> Result<()> {
+        if self.metadata.remove_area(id, area)? {
+            debug!("Keeping object {}, another area contains it.", id);
+            Ok(())
+        } else {
+            self.remove_cached_object(id)
+        }
+    }
 }
 
 impl Drop for OSMObjectManager {
     fn drop(&mut self) {
+        let queries = self.cache_queries.load(Ordering::Relaxed);
+        let hits = self.cache_hits.load(Ordering::Relaxed);
         info!(
             "Out of {} entity cache queries {} were cache hits.",
-            self.cache_queries.borrow(),
-            self.cache_hits.borrow()
+            queries,
+            hits
         );
+        if let Err(e) = cache_maintenance::add_to_counters(&self.cache, queries.into(), hits.into()) {
+            error!("Failed to store the entity cache counters: {}", e);
+        }
     }
 }
 
@@ -0,0 +1,123 @@
+use crate::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sled::transaction::{TransactionalTree, UnabortableTransactionError};
+use sled::{Db, Tree};
+
+const TREE_NAME: &str = "object_metadata";
+
+/// When an object was last received from the OSM data, and the areas it was received for.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ObjectMetadata {
+    /// Unix timestamp in seconds.
+    pub last_seen: i64,
+    pub areas: Vec<i64>,
+}
+
+impl ObjectMetadata {
+    /// None for a timestamp out of the range of the dates.
+    pub fn last_seen_at(&self) -> Option<DateTime<Utc>> {
+        Utc.timestamp_opt(self.last_seen, 0).single()
+    }
+}
+
+fn decode(value: &[u8]) -> Option<ObjectMetadata> {
+    bincode::deserialize(value).ok()
+}
+
+fn encode(metadata: &ObjectMetadata) -> Vec<u8> {
+    bincode::serialize(metadata).expect("Could not serialize object metadata")
+}
+
+fn touched(old: Option<&[u8]>, now: i64, area: Option<i64>) -> Vec<u8> {
+    let mut metadata = old.and_then(decode).unwrap_or_default();
+    metadata.last_seen = now;
+    if let Some(area) = area {
+        if !metadata.areas.contains(&area) {
+            metadata.areas.push(area);
+        }
+    }
+    encode(&metadata)
+}
+
+/// The metadata of the cached objects, keyed by their unique ids, they decide which objects the garbage collection removes.
+///
+/// The objects retrieved as dependencies of other objects belong to no area, they are kept only while they were seen recently enough.
+#[derive(Clone)]
+pub struct ObjectMetadataTree {
+    tree: Tree,
+}
+
+impl ObjectMetadataTree {
+    pub fn open(cache: &Db) -> Result<Self> {
+        Ok(Self {
+            tree: cache.open_tree(TREE_NAME)?,
+        })
+    }
+
+    pub(crate) fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /// Marks the object as seen now, and as a member of the area, if any.
+    pub fn touch(&self, id: &str, area: Option<i64>) -> Result<()> {
+        let now = Utc::now().timestamp();
+        self.tree
+            .fetch_and_update(id, |old| Some(touched(old, now, area)))?;
+        Ok(())
+    }
+
+    /// Like `touch`, but within a transaction over the tree returned by `tree`.
+    pub(crate) fn touch_in(
+        tree: &TransactionalTree,
+        id: &str,
+        area: Option<i64>,
+    ) -> std::result::Result<(), UnabortableTransactionError> {
+        let old = tree.get(id)?;
+        tree.insert(id, touched(old.as_deref(), Utc::now().timestamp(), area))?;
+        Ok(())
+    }
+
+    /// Removes the area from the areas of the object, returns whether the object still belongs to some.
+    pub fn remove_area(&self, id: &str, area: i64) -> Result<bool> {
+        let updated = self.tree.update_and_fetch(id, |old| {
+            let mut metadata = old.and_then(decode).unwrap_or_default();
+            metadata.areas.retain(|a| *a != area);
+            Some(encode(&metadata))
+        })?;
+        Ok(updated
+            .and_then(|v| decode(&v))
+            .is_some_and(|m| !m.areas.is_empty()))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<ObjectMetadata>> {
+        Ok(self.tree.get(id)?.and_then(|v| decode(&v)))
+    }
+
+    pub fn insert(&self, id: &str, metadata: &ObjectMetadata) -> Result<()> {
+        self.tree.insert(id, encode(metadata))?;
+        Ok(())
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        self.tree.remove(id)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Iterates the unique ids together with the metadata, the undecodable entries are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = Result<(String, ObjectMetadata)>> {
+        self.tree.iter().filter_map(|entry| match entry {
+            Ok((key, value)) => decode(&value)
+                .map(|metadata| Ok((String::from_utf8_lossy(&key).into_owned(), metadata))),
+            Err(e) => Some(Err(e.into())),
+        })
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{Error, Result};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::time::Duration;
+
+const DEFAULT_SERVER_URLS: [&str; 4] = [
+    "https://z.overpass-api.de",
+    "https://lz4.overpass-api.de",
+    "https://overpass.kumi.systems",
+    "https://maps.mail.ru/osm/tools/overpass",
+];
+
+/// When the servers misbehave and for how long they're left alone then.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HealthPolicy {
+    /// The number of consecutive failures after which the circuit of the server opens and it gets no queries.
+    pub failure_threshold: u32,
+    /// After this time, one trial query is let through an open circuit, its success closes it again.
+    pub open_duration_secs: u64,
+    pub connect_timeout_secs: u64,
+    /// The Overpass queries of bigger areas take minutes, so this should be rather generous.
+    pub query_timeout_secs: u64,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            open_duration_secs: 120,
+            connect_timeout_secs: 30,
+            query_timeout_secs: 900,
+        }
+    }
+}
+
+impl HealthPolicy {
+    pub fn open_duration(&self) -> Duration {
+        Duration::from_secs(self.open_duration_secs)
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    pub fn query_timeout(&self) -> Duration {
+        Duration::from_secs(self.query_timeout_secs)
+    }
+}
+
+/// Guards against the responses which would never end, or fill the disk when spooled.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ResponseLimits {
+    pub max_bytes: u64,
+    /// The time limit of downloading a response into a temporary file. A streamed response is consumed as slowly as its reader processes it, so only the query timeout of the individual reads guards it.
+    pub total_timeout_secs: u64,
+}
+
+impl Default for ResponseLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 4 * 1024 * 1024 * 1024,
+            total_timeout_secs: 3600,
+        }
+    }
+}
+
+impl ResponseLimits {
+    pub fn total_timeout(&self) -> Duration {
+        Duration::from_secs(self.total_timeout_secs)
+    }
+}
+
+/// The Overpass API servers used for the queries.
+///
+/// The TOML form looks like the following, the sections are optional:
+/// ```toml
+/// urls = ["https://overpass.example.org"]
+///
+/// [health]
+/// failure_threshold = 3
+/// open_duration_secs = 120
+///
+/// [response]
+/// max_bytes = 4294967296
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServersConfig {
+    pub urls: Vec<String>,
+    pub health: HealthPolicy,
+    pub response: ResponseLimits,
+}
+
+impl Default for ServersConfig {
+    fn default() -> Self {
+        Self {
+            urls: DEFAULT_SERVER_URLS.iter().map(|u| u.to_string()).collect(),
+            health: HealthPolicy::default(),
+            response: ResponseLimits::default(),
+        }
+    }
+}
+
+impl ServersConfig {
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        config.validated()
+    }
+
+    /// Reads the configuration from the TOML file named by the OVERPASS_CONFIG environment variable, or takes the comma separated server urls from the OVERPASS_SERVERS one.
+    ///
+    /// Without either of them, the public Overpass API instances are used.
+    pub fn from_env() -> Result<Self> {
+        if let Ok(path) = env::var("OVERPASS_CONFIG") {
+            return Self::from_toml(&fs::read_to_string(path)?);
+        }
+        match env::var("OVERPASS_SERVERS") {
+            Ok(urls) => Self {
+                urls: urls
+                    .split(',')
+                    .map(|u| u.trim().to_string())
+                    .filter(|u| !u.is_empty())
+                    .collect(),
+                ..Self::default()
+            }
+            .validated(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    fn validated(mut self) -> Result<Self> {
+        // The endpoint paths get appended to the urls.
+        for url in &mut self.urls {
+            while url.ends_with('/') {
+                url.pop();
+            }
+        }
+        if self.urls.is_empty() {
+            Err(Error::NoOverpassServers)
+        } else {
+            Ok(self)
+        }
+    }
+}
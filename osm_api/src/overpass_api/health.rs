@@ -0,0 +1,102 @@
+use super::config::HealthPolicy;
+use std::time::{Duration, Instant};
+
+// The weight of the newest observation in the moving averages.
+const SMOOTHING: f64 = 0.2;
+// Assumed for a server which did not answer any query yet, so it gets tried.
+const UNKNOWN_LATENCY_SECS: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CircuitState {
+    Closed,
+    /// No queries go to the server until the given instant.
+    Open(Instant),
+    /// A trial query was let through, its result decides whether the circuit closes again.
+    HalfOpen,
+}
+
+/// What we observed about a server, used to pick the one which gets the next query.
+#[derive(Debug, Clone)]
+pub struct ServerHealth {
+    pub url: String,
+    latency_secs: Option<f64>,
+    error_rate: f64,
+    pub successes: u64,
+    pub failures: u64,
+    pub timeouts: u64,
+    consecutive_failures: u32,
+    pub circuit: CircuitState,
+    /// The queries given to the server which did not finish yet.
+    pub pending: usize,
+}
+
+impl ServerHealth {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            latency_secs: None,
+            error_rate: 0.0,
+            successes: 0,
+            failures: 0,
+            timeouts: 0,
+            consecutive_failures: 0,
+            circuit: CircuitState::Closed,
+            pending: 0,
+        }
+    }
+
+    /// The moving average of the durations of the successful queries.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency_secs.map(Duration::from_secs_f64)
+    }
+
+    /// The moving average of the failed query fraction.
+    pub fn error_rate(&self) -> f64 {
+        self.error_rate
+    }
+
+    /// Whether the server can take a query now, an expired open circuit becomes half open for a single trial query.
+    pub fn accepts_queries(&mut self, now: Instant) -> bool {
+        match self.circuit {
+            CircuitState::Closed => true,
+            CircuitState::Open(until) if until <= now => {
+                self.circuit = CircuitState::HalfOpen;
+                true
+            }
+            CircuitState::Open(_) => false,
+            CircuitState::HalfOpen => self.pending == 0,
+        }
+    }
+
+    /// Lower is better, a slow, busy or unreliable server gets a high one.
+    pub fn score(&self) -> f64 {
+        let latency = self.latency_secs.unwrap_or(UNKNOWN_LATENCY_SECS);
+        latency * (1 + self.pending) as f64 / (1.0 - self.error_rate).max(0.05)
+    }
+
+    pub fn record_success(&mut self, latency: Duration) {
+        let secs = latency.as_secs_f64();
+        self.latency_secs = Some(match self.latency_secs {
+            Some(avg) => avg + SMOOTHING * (secs - avg),
+            None => secs,
+        });
+        self.error_rate -= SMOOTHING * self.error_rate;
+        self.successes += 1;
+        self.consecutive_failures = 0;
+        self.circuit = CircuitState::Closed;
+    }
+
+    pub fn record_failure(&mut self, timed_out: bool, policy: &HealthPolicy) {
+        self.error_rate += SMOOTHING * (1.0 - self.error_rate);
+        self.failures += 1;
+        if timed_out {
+            self.timeouts += 1;
+        }
+        self.consecutive_failures += 1;
+        if self.circuit == CircuitState::HalfOpen
+            || self.consecutive_failures >= policy.failure_threshold
+        {
+            self.circuit = CircuitState::Open(Instant::now() + policy.open_duration());
+        }
+    }
+}
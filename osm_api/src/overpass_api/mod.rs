@@ -1,4 +1,10 @@
+mod config;
+mod health;
+mod response;
 mod server;
 mod servers;
 
+pub use config::{HealthPolicy, ResponseLimits, ServersConfig};
+pub use response::{for_each_element, remark_error, ResponseProgress};
+pub use health::{CircuitState, ServerHealth};
 pub use servers::Servers;
\ No newline at end of file
@@ -0,0 +1,266 @@
+use crate::object::OSMObjectFromNetwork;
+use crate::{Error, Result};
+use log::{debug, warn};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer as _};
+use serde_json::Value;
+use std::cell::Cell;
+use std::fmt;
+use std::io::{self, Read};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// How often the progress of a long response is reported.
+const PROGRESS_INTERVAL_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Why a `LimitedReader` refused to read further, carried inside an `io::Error` through the parsers.
+#[derive(Debug, Clone)]
+enum LimitExceeded {
+    Size(u64),
+    Time(Duration),
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitExceeded::Size(max) => write!(f, "more than {} bytes", max),
+            LimitExceeded::Time(timeout) => write!(f, "longer than {:?}", timeout),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Fails the reads once the response is too big, or took too long when it has a deadline.
+pub struct LimitedReader<R: Read> {
+    inner: R,
+    read: u64,
+    max_bytes: u64,
+    deadline: Option<(Instant, Duration)>,
+}
+
+impl<R: Read> LimitedReader<R> {
+    pub fn new(inner: R, max_bytes: u64) -> Self {
+        Self {
+            inner,
+            read: 0,
+            max_bytes,
+            deadline: None,
+        }
+    }
+
+    /// Limits the time the whole response may take, counted from the given start.
+    pub fn with_deadline(mut self, started: Instant, timeout: Duration) -> Self {
+        self.deadline = Some((started + timeout, timeout));
+        self
+    }
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some((deadline, timeout)) = self.deadline {
+            if Instant::now() > deadline {
+                return Err(io::Error::other(LimitExceeded::Time(timeout)));
+            }
+        }
+        let count = self.inner.read(buf)?;
+        self.read += count as u64;
+        if self.read > self.max_bytes {
+            return Err(io::Error::other(LimitExceeded::Size(self.max_bytes)));
+        }
+        Ok(count)
+    }
+}
+
+/// Converts a failed read of a response, turning the exceeded limits into their error variants.
+pub fn read_error(error: io::Error) -> Error {
+    match error.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()) {
+        Some(LimitExceeded::Size(max)) => Error::ResponseTooLarge(*max),
+        Some(LimitExceeded::Time(timeout)) => Error::ResponseTimedOut(*timeout),
+        None => Error::IoError(error),
+    }
+}
+
+fn parse_error(error: serde_json::Error) -> Error {
+    if error.is_io() {
+        read_error(error.into())
+    } else {
+        Error::MalformedResponse(error)
+    }
+}
+
+/// Classifies a remark of an Overpass response, the ones which do not describe an error give None.
+pub fn remark_error(remark: &str) -> Option<Error> {
+    let lowercase = remark.to_lowercase();
+    if !lowercase.contains("runtime error") {
+        return None;
+    }
+    let remark = remark.trim().to_string();
+    Some(if lowercase.contains("timed out") || lowercase.contains("timeout") {
+        Error::OverpassQueryTimeout(remark)
+    } else if lowercase.contains("out of memory") {
+        Error::OverpassOutOfMemory(remark)
+    } else {
+        Error::OverpassRuntimeError(remark)
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResponseProgress {
+    pub bytes: u64,
+    pub elements: usize,
+}
+
+struct CountingReader<R: Read> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.count.set(self.count.get() + count as u64);
+        Ok(count)
+    }
+}
+
+struct ParseState<F, P> {
+    on_element: F,
+    on_progress: P,
+    bytes: Rc<Cell<u64>>,
+    next_report: u64,
+    elements: usize,
+    remark: Option<String>,
+    // The serde errors can't carry ours, so the error of the element handler waits here.
+    handler_error: Option<Error>,
+}
+
+impl<F, P> ParseState<F, P>
+where
+    F: FnMut(OSMObjectFromNetwork) -> Result<()>,
+    P: FnMut(&ResponseProgress),
+{
+    fn progress(&self) -> ResponseProgress {
+        ResponseProgress {
+            bytes: self.bytes.get(),
+            elements: self.elements,
+        }
+    }
+
+    fn handle_element<E: de::Error>(&mut self, element: Value) -> std::result::Result<(), E> {
+        // The area queries output the area itself as well, and the count statements have their own element type.
+        match element.get("type").and_then(|t| t.as_str()) {
+            Some("node") | Some("way") | Some("relation") => {}
+            other => {
+                debug!("Skipping an element of type {:?}.", other);
+                return Ok(());
+            }
+        }
+        let object = OSMObjectFromNetwork::deserialize(element).map_err(E::custom)?;
+        self.elements += 1;
+        if let Err(e) = (self.on_element)(object) {
+            self.handler_error = Some(e);
+            return Err(E::custom("the element handler failed"));
+        }
+        if self.bytes.get() >= self.next_report {
+            let progress = self.progress();
+            (self.on_progress)(&progress);
+            self.next_report = self.bytes.get() + PROGRESS_INTERVAL_BYTES;
+        }
+        Ok(())
+    }
+}
+
+struct ResponseVisitor<'a, F, P>(&'a mut ParseState<F, P>);
+
+impl<'de, 'a, F, P> Visitor<'de> for ResponseVisitor<'a, F, P>
+where
+    F: FnMut(OSMObjectFromNetwork) -> Result<()>,
+    P: FnMut(&ResponseProgress),
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an Overpass API JSON response")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "elements" => map.next_value_seed(ElementsSeed(&mut *self.0))?,
+                "remark" => self.0.remark = map.next_value()?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct ElementsSeed<'a, F, P>(&'a mut ParseState<F, P>);
+
+impl<'de, 'a, F, P> DeserializeSeed<'de> for ElementsSeed<'a, F, P>
+where
+    F: FnMut(OSMObjectFromNetwork) -> Result<()>,
+    P: FnMut(&ResponseProgress),
+{
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a, F, P> Visitor<'de> for ElementsSeed<'a, F, P>
+where
+    F: FnMut(OSMObjectFromNetwork) -> Result<()>,
+    P: FnMut(&ResponseProgress),
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of OSM elements")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        while let Some(element) = seq.next_element::<Value>()? {
+            self.0.handle_element(element)?;
+        }
+        Ok(())
+    }
+}
+
+/// Streams the elements of an Overpass API JSON response into the handler, without holding more than one of them in memory.
+///
+/// The progress callback gets called every few megabytes of the response. A remark describing a runtime error of the query, e.g. a timeout, fails the parsing even though some elements were handled already, because the response is incomplete.
+pub fn for_each_element<R, F, P>(readable: R, on_element: F, on_progress: P) -> Result<ResponseProgress>
+where
+    R: Read,
+    F: FnMut(OSMObjectFromNetwork) -> Result<()>,
+    P: FnMut(&ResponseProgress),
+{
+    let bytes = Rc::new(Cell::new(0));
+    let mut state = ParseState {
+        on_element,
+        on_progress,
+        bytes: bytes.clone(),
+        next_report: PROGRESS_INTERVAL_BYTES,
+        elements: 0,
+        remark: None,
+        handler_error: None,
+    };
+    let mut de = serde_json::Deserializer::from_reader(CountingReader { inner: readable, count: bytes });
+    let parsed = de.deserialize_map(ResponseVisitor(&mut state)).and_then(|_| de.end());
+    if let Some(e) = state.handler_error.take() {
+        return Err(e);
+    }
+    parsed.map_err(parse_error)?;
+    if let Some(remark) = &state.remark {
+        match remark_error(remark) {
+            Some(e) => return Err(e),
+            None => warn!("Received a remark from the server: {}", remark),
+        }
+    }
+    Ok(state.progress())
+}
@@ -3,15 +3,18 @@ use crossbeam_channel::{Sender, Receiver};
 use log::{warn, debug, info};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use super::servers::ServerQuery;
-use ureq::{Agent, Request};
+use super::config::{HealthPolicy, ResponseLimits};
+use super::response::{self, LimitedReader};
+use super::servers::{HealthTable, ServerQuery};
+use ureq::{Agent, AgentBuilder, Request};
 use crate::{Result, Error};
 use std::sync::Arc;
 use std::thread;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::io::{self, Read, Seek, SeekFrom};
 use tempfile::tempfile;
-use std::time::Instant;
+use std::error::Error as _;
+use std::time::{Duration as StdDuration, Instant};
 
 static AVAILABLE_SLOTS_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^(\d+) slots available now.").unwrap());
@@ -20,46 +23,165 @@ static SLOT_AVAILABLE_AFTER_RE: Lazy<Regex> = Lazy::new(|| {
 });
 static RATE_LIMIT_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^Rate limit: (\d+)").unwrap());
+// Enough for the remark of a failed query, which follows the last element.
+const REMARK_TAIL_LEN: usize = 4096;
 
-    const QUERY_RETRY_COUNT: u8 = 6;
+/// The identity of a server within the pool and the shared record of its health.
+pub struct ServerHandle {
+    pub index: usize,
+    pub url: String,
+    pub health: HealthTable,
+    pub policy: HealthPolicy,
+    pub limits: ResponseLimits,
+}
 
-fn query_executor(req: Request, query: ServerQuery, wake_sender: Sender<()>) {
-    let mut retry = 0;
-    let ret = loop {
-        retry += 1;
-        if retry > QUERY_RETRY_COUNT {
-            break Err(Error::RetryLimitExceeded)
-        }
-        match run_query(req.clone(), &query.query, query.result_to_tempfile, &wake_sender) {
-            Ok(r) => break Ok(r),
-            Err(e) => {
-                warn!("Query failed during retry {}, error: {:?}.", retry, e);
+impl ServerHandle {
+    fn record_success(&self, latency: StdDuration) {
+        let mut health = self.health.lock().unwrap();
+        health[self.index].record_success(latency);
+    }
+
+    fn record_failure(&self, error: &Error) {
+        let mut health = self.health.lock().unwrap();
+        health[self.index].record_failure(is_timeout(error), &self.policy);
+    }
+
+    fn finish_query(&self) {
+        self.health.lock().unwrap()[self.index].pending -= 1;
+    }
+}
+
+fn is_timeout_kind(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock)
+}
+
+fn is_timeout(error: &Error) -> bool {
+    match error {
+        Error::IoError(e) => is_timeout_kind(e),
+        Error::ResponseTimedOut(_) | Error::OverpassQueryTimeout(_) => true,
+        // The gateway timeout is what Overpass answers when the query took too long.
+        Error::HttpError(ureq::Error::Status(504, _)) => true,
+        Error::HttpError(ureq::Error::Transport(t)) => t
+            .source()
+            .and_then(|e| e.downcast_ref::<io::Error>())
+            .is_some_and(is_timeout_kind),
+        _ => false,
+    }
+}
+
+/// The remark at the end of a JSON or XML response, a query which failed while running ends with one.
+fn trailing_remark(tail: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(tail);
+    if let Some(start) = text.rfind("<remark>") {
+        let rest = &text[start + "<remark>".len()..];
+        return Some(rest.split("</remark>").next().unwrap_or(rest).to_string());
+    }
+    let start = text.rfind("\"remark\"")?;
+    let value = text[start + "\"remark\"".len()..]
+        .trim_start()
+        .strip_prefix(':')?;
+    serde_json::Deserializer::from_str(value)
+        .into_iter::<String>()
+        .next()?
+        .ok()
+}
+
+/// Records the health of the server once the streamed response was read completely, or failed.
+///
+/// A complete response whose remark describes a runtime error counts as a failure, the same one the parsing of the response reports.
+struct HealthRecordingReader<R: Read> {
+    inner: R,
+    handle: Arc<ServerHandle>,
+    start: Instant,
+    recorded: bool,
+    tail: Vec<u8>,
+}
+
+impl<R: Read> HealthRecordingReader<R> {
+    fn new(inner: R, handle: Arc<ServerHandle>, start: Instant) -> Self {
+        Self { inner, handle, start, recorded: false, tail: Vec::with_capacity(REMARK_TAIL_LEN) }
+    }
+
+    fn keep_tail(&mut self, read: &[u8]) {
+        self.tail.extend_from_slice(&read[read.len().saturating_sub(REMARK_TAIL_LEN)..]);
+        let excess = self.tail.len().saturating_sub(REMARK_TAIL_LEN);
+        self.tail.drain(..excess);
+    }
+}
+
+impl<R: Read> Read for HealthRecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self.inner.read(buf);
+        if !self.recorded {
+            match &result {
+                Ok(0) if !buf.is_empty() => {
+                    self.recorded = true;
+                    match trailing_remark(&self.tail).and_then(|r| response::remark_error(&r)) {
+                        Some(e) => self.handle.record_failure(&e),
+                        None => self.handle.record_success(self.start.elapsed()),
+                    }
+                }
+                Ok(count) => self.keep_tail(&buf[..*count]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.recorded = true;
+                    let mut health = self.handle.health.lock().unwrap();
+                    health[self.handle.index].record_failure(is_timeout_kind(e), &self.handle.policy);
+                }
             }
         }
-    };
-    query.result_sender.send(ret).unwrap();
+        result
+    }
+}
+
+/// Runs a single attempt of the query, the retries on other servers are up to the caller.
+fn query_executor(req: Request, query: ServerQuery, wake_sender: Sender<()>, handle: Arc<ServerHandle>) {
+    let start = Instant::now();
+    let ret = run_query(req, &query.query, query.result_to_tempfile, &wake_sender, &handle);
+    match &ret {
+        // The reader of a streamed response records the outcome when the body ends.
+        Ok(_) if !query.result_to_tempfile => {}
+        Ok(_) => handle.record_success(start.elapsed()),
+        Err(e) => handle.record_failure(e),
+    }
+    handle.finish_query();
+    // The caller might have given up already.
+    let _ = query.result_sender.send(ret);
 }
 
-fn run_query(req: Request, query: &str, result_to_tempfile: bool, wake_sender: &Sender<()>) -> Result<Box<dyn Read + Send>> {
+fn run_query(req: Request, query: &str, result_to_tempfile: bool, wake_sender: &Sender<()>, handle: &Arc<ServerHandle>) -> Result<Box<dyn Read + Send>> {
     let start = Instant::now();
     debug!("Calling interpreter endpoint {} with query {}", req.url(), query);
     let resp = req
-        .send_form(&[("data", query)])?;
-    debug!("Request successfully finished after {:?}.", start.elapsed());
-    // Maybe wake the dispatcher
+        .send_form(&[("data", query)]);
+    // Maybe wake the dispatcher, a failed request frees its slot as well.
     wake_sender.send(()).unwrap();
+    let resp = resp?;
+    debug!("Request successfully finished after {:?}.", start.elapsed());
+    let limits = &handle.limits;
+    let reader = LimitedReader::new(resp.into_reader(), limits.max_bytes);
     if !result_to_tempfile {
-        Ok(Box::new(resp.into_reader()))
+        // The reads time out individually through the agent, a slow consumer must not fail the transfer.
+        Ok(Box::new(HealthRecordingReader::new(reader, handle.clone(), start)))
     } else {
+        // The limits count from the start of the request, so the total timeout covers the waiting for the server as well.
+        let mut reader = reader.with_deadline(start, limits.total_timeout());
         let mut file = tempfile()?;
-        io::copy(&mut resp.into_reader(), &mut file)?;
+        io::copy(&mut reader, &mut file).map_err(response::read_error)?;
         file.seek(SeekFrom::Start(0))?;
         Ok(Box::new(file))
     }
 }
 
-pub fn requests_dispatcher(url: &'static str, queries_receiver: Receiver<ServerQuery>, should_exit: Arc<AtomicBool>) {
-    let mut server = Server::new(url).expect("Could not create server");
+pub fn requests_dispatcher(handle: ServerHandle, queries_receiver: Receiver<ServerQuery>, should_exit: Arc<AtomicBool>) {
+    let handle = Arc::new(handle);
+    let url = handle.url.clone();
+    let mut server = Server::new(url.clone(), &handle.policy);
+    if let Err(e) = server.update_status() {
+        // An unreachable server gets its circuit opened and the queries go elsewhere until it recovers.
+        warn!("Could not get the status of the Overpass API endpoint at {}, error: {:?}", url, e);
+        handle.record_failure(&e);
+    }
     let mut in_flight_requests = 0;
     let (wake_tx, wake_rx) = crossbeam_channel::unbounded();
     while !should_exit.load(Ordering::SeqCst) {
@@ -74,7 +196,8 @@ pub fn requests_dispatcher(url: &'static str, queries_receiver: Receiver<ServerQ
             let req = server.prepare_run_query();
             in_flight_requests += 1;
             let wake_clone = wake_tx.clone();
-            thread::spawn(move || query_executor(req, query, wake_clone));
+            let handle_clone = handle.clone();
+            thread::spawn(move || query_executor(req, query, wake_clone, handle_clone));
             // Did we reach the maximum nuber of in-flight queries? If yes, wait for at leas one to finish.
             if server.rate_limit > 0 && server.rate_limit == in_flight_requests {
                 let _ = wake_rx.recv().unwrap();
@@ -84,7 +207,10 @@ pub fn requests_dispatcher(url: &'static str, queries_receiver: Receiver<ServerQ
                     in_flight_requests -= 1;
                 }
             }
-            server.update_status().expect("Could not update status");
+            if let Err(e) = server.update_status() {
+                warn!("Could not update the status of the Overpass API endpoint at {}, error: {:?}", url, e);
+                handle.record_failure(&e);
+            }
         }
         else {
             // The Servers instance got dropped, so just exit gracefully.
@@ -94,7 +220,7 @@ pub fn requests_dispatcher(url: &'static str, queries_receiver: Receiver<ServerQ
 }
 
 struct Server {
-    url: &'static str,
+    url: String,
     agent: Agent,
     pub rate_limit: usize,
     available_slots: usize,
@@ -102,10 +228,13 @@ struct Server {
 }
 
 impl Server {
-    fn new(url: &'static str) -> Result<Self> {
-        let mut this = Self { agent: Agent::new(), available_slots: 0, rate_limit: 0, slots_available_after: vec![], url };
-        this.update_status()?;
-        Ok(this)
+    /// The status is not known until the first `update_status`, so the server looks unlimited until then.
+    fn new(url: String, policy: &HealthPolicy) -> Self {
+        let agent = AgentBuilder::new()
+            .timeout_connect(policy.connect_timeout())
+            .timeout_read(policy.query_timeout())
+            .build();
+        Self { agent, available_slots: 0, rate_limit: 0, slots_available_after: vec![], url }
     }
     fn get_api_status(&self) -> Result<String> {
         Ok(self
@@ -176,4 +305,23 @@ impl Server {
         self.agent
             .post(&final_url)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_remark_of_xml_and_json() {
+        let xml = b"<node id=\"1\"/>\n<remark> runtime error: Query timed out in \"query\" at line 3 after 901 seconds. </remark>\n\n</osm>\n";
+        let remark = trailing_remark(xml).unwrap();
+        assert!(matches!(response::remark_error(&remark), Some(Error::OverpassQueryTimeout(_))));
+        let json = br#"{"type": "node", "id": 1}
+  ],
+  "remark": "runtime error: Query ran out of memory in \"recurse\" at line 2."
+}
+"#;
+        let remark = trailing_remark(json).unwrap();
+        assert!(matches!(response::remark_error(&remark), Some(Error::OverpassOutOfMemory(_))));
+        assert_eq!(trailing_remark(b"{\"elements\": []}"), None);
+    }
+}
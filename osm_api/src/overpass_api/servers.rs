@@ -1,43 +1,120 @@
-use std::{io::Read, sync::{atomic::{AtomicBool, Ordering}, Arc}};
-use crate::Result;
+use std::{io::Read, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}};
+use crate::{Error, Result};
+use super::config::ServersConfig;
+use super::health::{CircuitState, ServerHealth};
 use super::server;
 use crossbeam_channel::Sender;
+use log::warn;
 use std::thread;
+use std::time::{Duration, Instant};
+
+// The number of attempts of a query, across all the servers.
+const QUERY_RETRY_COUNT: u8 = 6;
+// How long to wait at most when every server has its circuit open, before looking again.
+const MAX_ALL_OPEN_WAIT: Duration = Duration::from_secs(30);
 
 pub struct ServerQuery { pub query: String, pub result_to_tempfile: bool, pub result_sender:  Sender<Result<Box<dyn Read + Send>>>}
 
+pub type HealthTable = Arc<Mutex<Vec<ServerHealth>>>;
+
 pub struct Servers {
-    commands_sender: Sender<ServerQuery>,
+    query_senders: Vec<Sender<ServerQuery>>,
+    health: HealthTable,
     should_exit: Arc<AtomicBool>
 }
 
 impl Default for Servers {
     fn default() -> Self {
-        Self::with_server_urls(vec![
-            "https://z.overpass-api.de",
-            "https://lz4.overpass-api.de",
-            "https://overpass.kumi.systems",
-            "https://maps.mail.ru/osm/tools/overpass"
-        ])
+        Self::with_config(ServersConfig::default())
     }
 }
 
 impl Servers {
-    pub fn with_server_urls(urls: Vec<&'static str>) -> Self {
-        let (tx, rx) = crossbeam_channel::unbounded();
+    /// Uses the servers configured in the environment, see `ServersConfig::from_env`.
+    pub fn from_env() -> Result<Self> {
+        Ok(Self::with_config(ServersConfig::from_env()?))
+    }
+
+    pub fn with_server_urls(urls: Vec<String>) -> Self {
+        Self::with_config(ServersConfig { urls, ..ServersConfig::default() })
+    }
+
+    pub fn with_config(config: ServersConfig) -> Self {
         let should_exit = Arc::new(AtomicBool::new(false));
-        for url in urls {
-            let rx_clone = rx.clone();
+        let health = Arc::new(Mutex::new(config.urls.iter().map(|u| ServerHealth::new(u)).collect()));
+        let mut query_senders = vec![];
+        for (index, url) in config.urls.into_iter().enumerate() {
+            let (tx, rx) = crossbeam_channel::unbounded();
+            query_senders.push(tx);
             let exit_clone = should_exit.clone();
-            thread::spawn(move || server::requests_dispatcher(url, rx_clone, exit_clone));
+            let handle = server::ServerHandle { index, url, health: health.clone(), policy: config.health.clone(), limits: config.response.clone() };
+            thread::spawn(move || server::requests_dispatcher(handle, rx, exit_clone));
         }
-        Self { commands_sender: tx, should_exit }
+        Self { query_senders, health, should_exit }
+    }
+
+    /// A snapshot of the health of the servers, e.g. for logging.
+    pub fn health(&self) -> Vec<ServerHealth> {
+        self.health.lock().unwrap().clone()
     }
 
+    /// Picks the best scoring server which accepts queries, preferring the ones which were not tried for the query yet.
+    fn pick_server(&self, tried: &[bool]) -> Option<usize> {
+        let mut health = self.health.lock().unwrap();
+        let now = Instant::now();
+        let accepting: Vec<usize> = (0..health.len()).filter(|i| health[*i].accepts_queries(now)).collect();
+        let best = |candidates: Vec<usize>| candidates.into_iter().min_by(|a, b| health[*a].score().partial_cmp(&health[*b].score()).unwrap());
+        let untried = accepting.iter().copied().filter(|i| !tried[*i]).collect();
+        let picked = best(untried).or_else(|| best(accepting));
+        if let Some(idx) = picked {
+            health[idx].pending += 1;
+        }
+        picked
+    }
+
+    fn wait_for_open_circuit(&self) {
+        let now = Instant::now();
+        let reopening = self.health.lock().unwrap().iter().filter_map(|h| match h.circuit {
+            CircuitState::Open(until) => Some(until.saturating_duration_since(now)),
+            _ => None,
+        }).min();
+        let wait = reopening.unwrap_or(MAX_ALL_OPEN_WAIT).min(MAX_ALL_OPEN_WAIT).max(Duration::from_millis(100));
+        warn!("No Overpass server accepts queries, waiting {:?}.", wait);
+        thread::sleep(wait);
+    }
+
+    /// Runs the query on the healthiest server, a failed query moves to a different one if there is any.
     pub fn run_query(&self, query: &str, result_to_tempfile: bool) -> Result<Box<dyn Read + Send>> {
-        let (tx, rx) = crossbeam_channel::bounded(1);
-        self.commands_sender.send(ServerQuery { query: query.to_string(), result_sender: tx, result_to_tempfile }).unwrap();
-        rx.recv().unwrap()
+        let mut tried = vec![false; self.query_senders.len()];
+        let mut attempt = 0;
+        while attempt < QUERY_RETRY_COUNT {
+            let idx = match self.pick_server(&tried) {
+                Some(idx) => idx,
+                None => {
+                    self.wait_for_open_circuit();
+                    continue;
+                }
+            };
+            attempt += 1;
+            tried[idx] = true;
+            let (tx, rx) = crossbeam_channel::bounded(1);
+            let sent = self.query_senders[idx].send(ServerQuery { query: query.to_string(), result_sender: tx, result_to_tempfile });
+            let result = match sent {
+                Ok(()) => rx.recv().unwrap_or(Err(Error::ServerDispatcherGone)),
+                Err(_) => {
+                    // The dispatcher is gone, so nobody else will settle the query.
+                    self.health.lock().unwrap()[idx].pending -= 1;
+                    Err(Error::ServerDispatcherGone)
+                }
+            };
+            match result {
+                Ok(r) => return Ok(r),
+                Err(e) => {
+                    warn!("Query failed during attempt {}, error: {:?}.", attempt, e);
+                }
+            }
+        }
+        Err(Error::RetryLimitExceeded)
     }
 }
 
@@ -45,4 +122,4 @@ impl Drop for Servers {
     fn drop(&mut self) {
         self.should_exit.store(true, Ordering::SeqCst);
     }
-}
\ No newline at end of file
+}
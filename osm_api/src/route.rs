@@ -0,0 +1,147 @@
+//! Route relations, e.g. bus lines or hiking trails, with their ordered stops and the line they follow.
+use crate::object::{OSMObject, OSMObjectSpecifics};
+use geo_types::{Geometry, LineString, MultiLineString};
+
+type Point = (f64, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberKind {
+    /// Where the vehicle stops, including the public transport v1 roles like `forward:stop`.
+    Stop,
+    /// Where the passengers wait.
+    Platform,
+    /// A part of the way the route follows.
+    Way,
+}
+
+impl MemberKind {
+    pub fn from_role(role: &str) -> Self {
+        // The v2 roles can be suffixed by `_entry_only` or `_exit_only`, the older ones prefixed by a direction.
+        let base = role.split(&[':', '_'][..]).find(|part| {
+            !matches!(
+                *part,
+                "forward" | "backward" | "entry" | "exit" | "only" | ""
+            )
+        });
+        match base {
+            Some("stop") => MemberKind::Stop,
+            Some("platform") => MemberKind::Platform,
+            _ => MemberKind::Way,
+        }
+    }
+}
+
+pub fn is_route(object: &OSMObject) -> bool {
+    matches!(
+        object.tags.get("type").map(|t| t.as_str()),
+        Some("route") | Some("route_master")
+    )
+}
+
+/// The ids of the members of a route, or of a route master, in the order in which they're served.
+#[derive(Debug, Default)]
+pub struct RouteMembers {
+    pub stops: Vec<String>,
+    pub platforms: Vec<String>,
+    /// The routes of a route master, e.g. both directions of a bus line.
+    pub routes: Vec<String>,
+}
+
+impl RouteMembers {
+    pub fn of(object: &OSMObject) -> Option<Self> {
+        if !is_route(object) {
+            return None;
+        }
+        let members = match &object.specifics {
+            OSMObjectSpecifics::Relation { members } => members,
+            _ => return None,
+        };
+        let is_master = object.tags.get("type").map(|t| t.as_str()) == Some("route_master");
+        let mut route_members = Self::default();
+        for member in members {
+            let id = member.unique_reference();
+            if is_master {
+                if id.starts_with('r') {
+                    route_members.routes.push(id);
+                }
+                continue;
+            }
+            match MemberKind::from_role(&member.role) {
+                MemberKind::Stop => route_members.stops.push(id),
+                MemberKind::Platform => route_members.platforms.push(id),
+                MemberKind::Way => {}
+            }
+        }
+        Some(route_members)
+    }
+}
+
+fn key(point: Point) -> (u64, u64) {
+    (point.0.to_bits(), point.1.to_bits())
+}
+
+/// Merges the ways of a route, in their relation order, into a line, reversing them where needed.
+///
+/// The result is a multi line string when the ways don't connect.
+pub fn merge_ways(ways: Vec<LineString<f64>>) -> Option<Geometry<f64>> {
+    let mut lines: Vec<Vec<Point>> = vec![];
+    let mut current: Vec<Point> = vec![];
+    let mut ways_in_current = 0;
+    for way in ways {
+        let mut points: Vec<Point> = way.0.iter().map(|c| (c.x, c.y)).collect();
+        if points.len() < 2 {
+            continue;
+        }
+        if current.is_empty() {
+            current = points;
+            ways_in_current = 1;
+            continue;
+        }
+        let end = key(current[current.len() - 1]);
+        let start = key(current[0]);
+        let (first, last) = (key(points[0]), key(points[points.len() - 1]));
+        // Only the direction of the first way is unknown, the following ones say where the line continues.
+        if ways_in_current == 1 && end != first && end != last && (start == first || start == last)
+        {
+            current.reverse();
+        }
+        let end = key(current[current.len() - 1]);
+        if end == last && end != first {
+            points.reverse();
+        }
+        if key(points[0]) == end {
+            current.extend_from_slice(&points[1..]);
+            ways_in_current += 1;
+        } else {
+            lines.push(std::mem::replace(&mut current, points));
+            ways_in_current = 1;
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    match lines.len() {
+        0 => None,
+        1 => lines.pop().map(|l| Geometry::LineString(l.into())),
+        _ => Some(Geometry::MultiLineString(MultiLineString(
+            lines.into_iter().map(LineString::from).collect(),
+        ))),
+    }
+}
+
+/// Collects the lines of the routes of a route master.
+pub fn merge_routes(geometries: Vec<Geometry<f64>>) -> Option<Geometry<f64>> {
+    let mut lines = vec![];
+    for geometry in geometries {
+        match geometry {
+            Geometry::LineString(line) => lines.push(line),
+            Geometry::MultiLineString(multi) => lines.extend(multi.0),
+            _ => {}
+        }
+    }
+    if lines.is_empty() {
+        None
+    } else {
+        Some(Geometry::MultiLineString(MultiLineString(lines)))
+    }
+}
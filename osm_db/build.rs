@@ -0,0 +1,38 @@
+//! Compiles in the newer versions of the changes dictionary, so adding one needs no code changes.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DICTIONARIES_DIR: &str = "changes_dictionaries";
+
+fn dictionary_id(path: &Path) -> Option<u32> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("changes.")?
+        .strip_suffix(".dict")?
+        .parse()
+        .ok()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", DICTIONARIES_DIR);
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(DICTIONARIES_DIR);
+    let mut dictionaries: Vec<(u32, PathBuf)> = fs::read_dir(&dir)
+        .expect("Could not list the changes dictionaries")
+        .filter_map(|entry| {
+            let path = entry
+                .expect("Could not list the changes dictionaries")
+                .path();
+            dictionary_id(&path).map(|id| (id, path))
+        })
+        .collect();
+    dictionaries.sort();
+    let mut generated = String::from("&[\n");
+    for (id, path) in dictionaries {
+        println!("cargo:rerun-if-changed={}", path.display());
+        generated.push_str(&format!("    ({}, include_bytes!({:?})),\n", id, path));
+    }
+    generated.push(']');
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("changes_dictionaries.rs");
+    fs::write(out, generated).expect("Could not write the list of the changes dictionaries");
+}
@@ -0,0 +1,103 @@
+use crate::conditional::ConditionContext;
+use crate::entity::Entity;
+use crate::entity_metadata::{EntityMetadata, Enum};
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TravelMode {
+    Foot,
+    Wheelchair,
+    Bicycle,
+    Horse,
+    Motorcycle,
+    Motorcar,
+    Hgv,
+    Psv,
+}
+
+impl TravelMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        use TravelMode::*;
+        match name {
+            "foot" => Some(Foot),
+            "wheelchair" => Some(Wheelchair),
+            "bicycle" => Some(Bicycle),
+            "horse" => Some(Horse),
+            "motorcycle" => Some(Motorcycle),
+            "motorcar" => Some(Motorcar),
+            "hgv" => Some(Hgv),
+            "psv" => Some(Psv),
+            _ => None,
+        }
+    }
+
+    /// The access tags relevant for this mode, from the most specific to the most generic one.
+    fn access_tags(&self) -> &'static [&'static str] {
+        use TravelMode::*;
+        match self {
+            Foot => &["foot", "access"],
+            Wheelchair => &["wheelchair"],
+            Bicycle => &["bicycle", "vehicle", "access"],
+            Horse => &["horse", "access"],
+            Motorcycle => &["motorcycle", "motor_vehicle", "vehicle", "access"],
+            Motorcar => &["motorcar", "motor_vehicle", "vehicle", "access"],
+            Hgv => &["hgv", "motor_vehicle", "vehicle", "access"],
+            Psv => &["psv", "motor_vehicle", "vehicle", "access"],
+        }
+    }
+
+    fn usability_of(&self, access_value: &str) -> Option<bool> {
+        match access_value {
+            "yes" | "permissive" | "designated" | "destination" | "customers" | "delivery"
+            | "public" | "official" | "limited" => Some(true),
+            "no" | "private" | "agricultural" | "forestry" | "restricted" => Some(false),
+            "dismount" => Some(!matches!(self, TravelMode::Bicycle | TravelMode::Horse)),
+            "hgv" => Some(*self == TravelMode::Hgv),
+            "psv" => Some(*self == TravelMode::Psv),
+            _ => None,
+        }
+    }
+}
+
+/// Decides whether the entity can be used by the given travel mode in the given context, taking the conditional restrictions into account.
+///
+/// Returns `None` when the data does not say anything conclusive.
+pub fn usability_for(
+    entity: &mut Entity,
+    mode: TravelMode,
+    context: &ConditionContext,
+) -> Option<bool> {
+    let metadata = EntityMetadata::for_discriminator(&entity.discriminator);
+    let fields = metadata.map(|m| m.all_fields()).unwrap_or_default();
+    for tag in mode.access_tags() {
+        let restriction = entity.conditional_restriction_of(&format!("{}_conditional", tag));
+        if let Some(value) = restriction.as_ref().and_then(|r| r.value_in(context)) {
+            if let Some(usable) = mode.usability_of(value) {
+                return Some(usable);
+            }
+        }
+        // Some entities use the plain tag name, some have the field renamed to avoid clashes.
+        for field in &[tag.to_string(), format!("{}_allowed", tag)] {
+            let type_name = fields.get(field).map(|f| f.type_name.as_str());
+            let value = access_value_of(entity.value_of_field(field), type_name);
+            if let Some(usable) = value.and_then(|v| mode.usability_of(&v)) {
+                return Some(usable);
+            }
+        }
+    }
+    None
+}
+
+fn access_value_of(value: &Value, type_name: Option<&str>) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(if *b { "yes" } else { "no" }.to_string()),
+        Value::Number(n) => {
+            let enum_spec = Enum::with_name(type_name?)?;
+            enum_spec
+                .name_for_value(n.as_i64()? as i32)
+                .map(|name| name.to_string())
+        }
+        _ => None,
+    }
+}
@@ -2,7 +2,9 @@ use crate::entities_query_executor::EntitiesQueryExecutor;
 use crate::entity::Entity;
 use crate::entity_relationship::EntityRelationship;
 use crate::entity_relationship_kind::EntityRelationshipKind;
+use crate::geometry_metrics::{self, GeometryMetrics};
 use crate::semantic_change::{RelationshipChange, SemanticChange};
+use crate::simplification::{self, SimplificationLevel};
 use crate::{entities_query::EntitiesQuery, entity_relationship::RootedEntityRelationship};
 use crate::{Error, Result};
 use log::{debug, error, info, trace, warn};
@@ -10,14 +12,31 @@ use osm_api::SmolStr;
 use rusqlite::types::ToSql;
 use rusqlite::{named_params, params, Connection, OpenFlags, Row};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 const INIT_AREA_DB_SQL: &str = include_str!("init_area_db.sql");
+// Stored as the user_version of the database, the databases older than it get migrated when opened.
+const SCHEMA_VERSION: i32 = 1;
 const INSERT_ENTITY_SQL: &str = "insert into entities (id, discriminator, geometry, effective_width, data) values (?, ?, geomFromWKB(?, 4326), ?, ?)";
 const INSERT_ENTITY_SQL_BUFFERED: &str = "insert into entities (id, discriminator, geometry, effective_width, data) values (?, ?, Buffer(geomFromWKB(?, 4326), 0), ?, ?)";
 const INSERT_ENTITY_RELATIONSHIP_SQL: &str =
-    "INSERT INTO entity_relationships (parent_id, child_id, kind) VALUES (?, ?, ?) ON CONFLICT DO NOTHING";
+    "INSERT INTO entity_relationships (parent_id, child_id, kind, role, sequence_index) VALUES (?, ?, ?, ?, ?) ON CONFLICT (parent_id, child_id, kind, coalesce(sequence_index, -1)) DO UPDATE SET role = excluded.role";
+// Area databases created before the relationships knew the member roles lack these columns.
+const ADD_RELATIONSHIP_MEMBER_COLUMNS_SQL: &str = "ALTER TABLE entity_relationships ADD COLUMN role TEXT; ALTER TABLE entity_relationships ADD COLUMN sequence_index INTEGER;";
+// Their relationships were keyed without the position, so a repeated member was kept only once. The primary key can't be altered, so the table is rebuilt.
+const KEY_RELATIONSHIPS_BY_POSITION_SQL: &str = "BEGIN;
+CREATE TABLE entity_relationships_by_position (parent_id VARCHAR(16) REFERENCES entities(id) ON DELETE CASCADE, child_id VARCHAR(16) REFERENCES entities(id) ON DELETE CASCADE, kind INTEGER CHECK (KIND in (0, 1, 2)), role TEXT, sequence_index INTEGER);
+INSERT INTO entity_relationships_by_position SELECT parent_id, child_id, kind, role, sequence_index FROM entity_relationships;
+DROP TABLE entity_relationships;
+ALTER TABLE entity_relationships_by_position RENAME TO entity_relationships;
+CREATE UNIQUE INDEX idx_entity_relationship_key ON entity_relationships (parent_id, child_id, kind, coalesce(sequence_index, -1));
+COMMIT;";
+
+/// The columns which `row_to_entity` expects, in the order it reads them, with the geometry at the given level of detail.
+pub(crate) fn entity_columns_sql(level: SimplificationLevel) -> String {
+    format!("id, discriminator, AsBinary({}) as geometry, data, effective_width, geodesic_length, geodesic_area, centroid_lon, centroid_lat, start_bearing, end_bearing", level.geometry_expr())
+}
 
 #[derive(PartialEq)]
 enum ForeignKeyViolationClassification {
@@ -46,18 +65,54 @@ pub(crate) fn row_to_entity(row: &Row) -> core::result::Result<Entity, rusqlite:
         data: row.get_unwrap(3),
         effective_width: row.get_unwrap(4),
         parsed_data: None,
+        metrics: GeometryMetrics::from_row(row, 5)?,
     })
 }
 
-fn init_extensions(conn: &Connection) -> Result<()> {
+pub(crate) fn init_extensions(conn: &Connection) -> Result<()> {
     conn.load_extension_enable()?;
     conn.load_extension("mod_spatialite", None)?;
     Ok(())
 }
 
+/// Brings a database created before the schema got versioned to the current schema, each step checks whether it is still needed.
+fn migrate(conn: &Connection) -> Result<()> {
+    let has_member_columns = conn
+        .prepare("SELECT 1 FROM pragma_table_info('entity_relationships') WHERE name = 'sequence_index'")?
+        .exists([])?;
+    if !has_member_columns {
+        conn.execute_batch(ADD_RELATIONSHIP_MEMBER_COLUMNS_SQL)?;
+    }
+    let has_relationship_key = conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = 'idx_entity_relationship_key'")?
+        .exists([])?;
+    if !has_relationship_key {
+        info!("Keying the entity relationships by the member positions.");
+        conn.execute_batch(KEY_RELATIONSHIPS_BY_POSITION_SQL)?;
+    }
+    let has_metric_columns = conn
+        .prepare("SELECT 1 FROM pragma_table_info('entities') WHERE name = 'geodesic_length'")?
+        .exists([])?;
+    if !has_metric_columns {
+        info!("Computing the geometry metrics of the existing entities.");
+        conn.execute_batch(geometry_metrics::ADD_METRIC_COLUMNS_SQL)?;
+        conn.execute(&geometry_metrics::update_missing_metrics_sql(), [])?;
+    }
+    let has_simplified_geometry_columns = conn
+        .prepare("SELECT 1 FROM pragma_table_info('entities') WHERE name = 'geometry_fine'")?
+        .exists([])?;
+    if !has_simplified_geometry_columns {
+        info!("Simplifying the geometries of the existing entities.");
+        conn.execute_batch(simplification::ADD_SIMPLIFIED_GEOMETRY_COLUMNS_SQL)?;
+        conn.execute(&simplification::update_simplified_geometries_sql("1"), [])?;
+    }
+    conn.pragma_update(None, "user_version", &SCHEMA_VERSION)?;
+    Ok(())
+}
+
 pub struct AreaDatabase {
     pub(crate) conn: Connection,
-    deferred_relationship_additions: HashMap<String, RootedEntityRelationship>,
+    deferred_relationship_additions: Vec<EntityRelationship>,
 }
 
 impl AreaDatabase {
@@ -65,7 +120,7 @@ impl AreaDatabase {
         conn.execute("PRAGMA foreign_keys=on", params![])?;
         Ok(Self {
             conn,
-            deferred_relationship_additions: HashMap::new(),
+            deferred_relationship_additions: Vec::new(),
         })
     }
     pub fn path_for(area: i64, server_side: bool) -> PathBuf {
@@ -80,6 +135,11 @@ impl AreaDatabase {
         root
     }
 
+    /// Where the server keeps the copy of the area database with the geometries simplified to the given level.
+    pub fn simplified_path_for(area: i64, level: SimplificationLevel) -> PathBuf {
+        PathBuf::from(format!("{}_{}.db", area, level.name()))
+    }
+
     pub fn create(area: i64) -> Result<Self> {
         // We're not, and will not, be using anything which a relatively new sqlite would not support, so, if it can help Fedora in not crashing...
         unsafe {
@@ -88,8 +148,19 @@ impl AreaDatabase {
         let conn = Connection::open(&AreaDatabase::path_for(area, true))?;
         init_extensions(&conn)?;
         conn.execute_batch(INIT_AREA_DB_SQL)?;
+        conn.pragma_update(None, "user_version", &SCHEMA_VERSION)?;
+        AreaDatabase::common_construct(conn)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn create_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()?;
+        init_extensions(&conn)?;
+        conn.execute_batch(INIT_AREA_DB_SQL)?;
+        conn.pragma_update(None, "user_version", &SCHEMA_VERSION)?;
         AreaDatabase::common_construct(conn)
     }
+
     pub fn open_existing(area: i64, server_side: bool) -> Result<Self> {
         unsafe {
             rusqlite::bypass_sqlite_version_check();
@@ -99,17 +170,21 @@ impl AreaDatabase {
             OpenFlags::SQLITE_OPEN_READ_WRITE,
         )?;
         init_extensions(&conn)?;
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version < SCHEMA_VERSION {
+            migrate(&conn)?;
+        }
         AreaDatabase::common_construct(conn)
     }
 
     pub fn insert_entities<T>(&mut self, entities: T) -> Result<()>
     where
-        T: Iterator<Item = (Entity, Box<dyn Iterator<Item = String>>)>,
+        T: Iterator<Item = (Entity, Box<dyn Iterator<Item = RootedEntityRelationship>>)>,
     {
         let mut count = 0;
         self.begin()?;
-        let mut deferred_relationship_insertions = HashMap::new();
-        for (entity, related_ids) in entities {
+        let mut deferred_relationship_insertions = vec![];
+        for (entity, relationships) in entities {
             let mut insert_related_stmt =
                 self.conn.prepare_cached(INSERT_ENTITY_RELATIONSHIP_SQL)?;
             if entity.geometry.len() < 1_000_000 {
@@ -128,16 +203,18 @@ impl AreaDatabase {
                 ]) {
                     Ok(_) => {
                         count += 1;
-                        for related_id in related_ids {
+                        for relationship in relationships {
                             if let Err(e) = insert_related_stmt.execute(params![
                                 entity.id.as_str(),
-                                related_id,
-                                EntityRelationshipKind::OSMChild
+                                relationship.child_id,
+                                relationship.kind,
+                                relationship.role,
+                                relationship.sequence_index,
                             ]) {
-                                match classify_db_error(&e, &related_id) {
+                                match classify_db_error(&e, &relationship.child_id) {
                                     ForeignKeyViolationClassification::Retryable => {
                                         deferred_relationship_insertions
-                                            .insert(entity.id.clone(), related_id);
+                                            .push(relationship.with_parent(&entity.id));
                                     }
                                     ForeignKeyViolationClassification::Fatal => {
                                         continue;
@@ -162,13 +239,17 @@ impl AreaDatabase {
             }
         }
         // Handle deferred relationship insertions.
-        for (parent, child) in deferred_relationship_insertions.iter() {
-            self.insert_entity_relationship(&EntityRelationship::new(
-                parent,
-                child,
-                EntityRelationshipKind::OSMChild,
-            ))?;
+        for relationship in deferred_relationship_insertions.iter() {
+            self.insert_entity_relationship(relationship)?;
         }
+        // Computing them in one statement is a lot cheaper than doing it after every insertion.
+        // The entities without the metrics are the ones inserted now, so the simplification must go first.
+        self.conn.execute(
+            &simplification::update_simplified_geometries_sql("centroid_lon IS NULL"),
+            [],
+        )?;
+        self.conn
+            .execute(&geometry_metrics::update_missing_metrics_sql(), [])?;
 
         self.commit()?;
         info!("Successfully inserted {} entities.", count);
@@ -183,7 +264,10 @@ impl AreaDatabase {
     }
 
     pub fn get_entity(&self, osm_id: &str) -> Result<Option<Entity>> {
-        let mut stmt = self.conn.prepare_cached("select id, discriminator, AsBinary(geometry) as geometry, data, effective_width from entities where id = ?")?;
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "select {} from entities where id = ?",
+            entity_columns_sql(SimplificationLevel::Full)
+        ))?;
         match stmt.query_row(&[&osm_id], row_to_entity) {
             Ok(e) => Ok(Some(e)),
             Err(e) => match e {
@@ -210,18 +294,25 @@ impl AreaDatabase {
         x: f64,
         y: f64,
         fast: bool,
+        level: SimplificationLevel,
     ) -> Result<Vec<Entity>> {
         let mut candidate_params = vec![];
         for i in 0..candidate_ids.len() {
             candidate_params.push(format!(":candidate{}", i));
         }
-        let mut query = format!("SELECT id, discriminator, AsBinary(geometry) as geometry, data, effective_width FROM entities WHERE id in ({})", candidate_params.join(","));
+        let geometry = level.geometry_expr();
+        let mut query = format!(
+            "SELECT {} FROM entities WHERE id in ({})",
+            entity_columns_sql(level),
+            candidate_params.join(",")
+        );
         if fast {
-            query += " AND length(geometry) < 100000";
+            // A simplified variant can get e.g. a coastline under the limit.
+            query += &format!(" AND length({}) < 100000", geometry);
         }
         let fragment = format!(
-            " AND St_Disjoint(geometry, GeomFromText('POINT({} {})', 4326)) = 0",
-            x, y
+            " AND St_Disjoint({}, GeomFromText('POINT({} {})', 4326)) = 0",
+            geometry, x, y
         );
         query += &fragment;
         let mut params: Vec<(&str, &dyn ToSql)> = vec![];
@@ -254,18 +345,21 @@ impl AreaDatabase {
             self.conn.prepare_cached(INSERT_ENTITY_SQL_BUFFERED)?
         };
         stmt.execute(params![id, discriminator, geometry, effective_width, data])?;
+        self.update_derived_geometry_columns(id)?;
         let mut insert_relationship_stmt =
             self.conn.prepare_cached(INSERT_ENTITY_RELATIONSHIP_SQL)?;
         for relationship in entity_relationships {
             if let Err(e) = insert_relationship_stmt.execute(params![
                 id,
                 relationship.child_id,
-                relationship.kind
+                relationship.kind,
+                relationship.role,
+                relationship.sequence_index,
             ]) {
                 match classify_db_error(&e, &relationship.child_id) {
                     ForeignKeyViolationClassification::Retryable => {
                         self.deferred_relationship_additions
-                            .insert(id.to_string(), relationship.clone());
+                            .push(relationship.with_parent(id));
                     }
                     ForeignKeyViolationClassification::Fatal => {
                         continue;
@@ -300,6 +394,38 @@ impl AreaDatabase {
             entity.data,
             entity.id.as_str(),
         ])?;
+        self.update_derived_geometry_columns(&entity.id)
+    }
+
+    fn update_derived_geometry_columns(&self, osm_id: &str) -> Result<()> {
+        self.conn
+            .prepare_cached(&simplification::update_simplified_geometries_sql("id = ?"))?
+            .execute(&[&osm_id])?;
+        self.conn
+            .prepare_cached(&geometry_metrics::update_metrics_of_entity_sql())?
+            .execute(&[&osm_id])?;
+        Ok(())
+    }
+
+    /// Writes a copy of this database, whose geometries are replaced by their variants at the given level of detail, to the target path.
+    ///
+    /// The copy is written to a temporary file next to the target and then renamed over it, so nobody reading the target sees a partial copy.
+    pub fn write_simplified_copy(&self, level: SimplificationLevel, target: &Path) -> Result<()> {
+        let dir = target
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let temp = tempfile::Builder::new()
+            .suffix(".db.tmp")
+            .tempfile_in(dir)?;
+        // VACUUM INTO accepts an empty target file.
+        self.conn
+            .execute("VACUUM INTO ?", params![temp.path().to_string_lossy()])?;
+        let copy = Connection::open(temp.path())?;
+        init_extensions(&copy)?;
+        copy.execute_batch(&simplification::replace_geometries_sql(level))?;
+        drop(copy);
+        temp.persist(target).map_err(|e| e.error)?;
         Ok(())
     }
 
@@ -341,6 +467,25 @@ impl AreaDatabase {
         }
     }
 
+    /// Converts a GeoJSON geometry to WKB, None means that SpatiaLite could not parse it.
+    pub(crate) fn geojson_to_wkb(&self, geojson: &str) -> Result<Option<Vec<u8>>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("select AsBinary(GeomFromGeoJSON(?))")?;
+        stmt.query_row(&[&geojson], |row| row.get(0))
+            .map_err(Error::from)
+    }
+
+    pub fn get_entity_ids_with_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT id FROM entities WHERE substr(id, 1, length(:prefix)) = :prefix",
+        )?;
+        let ids = stmt
+            .query_map(named_params! {":prefix": prefix}, |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(ids)
+    }
+
     fn geometry_is_valid(&self, geometry: &[u8]) -> Result<bool> {
         let mut stmt = self
             .conn
@@ -371,12 +516,18 @@ impl AreaDatabase {
                     if let Err(e) = self
                         .conn
                         .prepare_cached(INSERT_ENTITY_RELATIONSHIP_SQL)?
-                        .execute(params![parent_id, value.child_id, value.kind])
+                        .execute(params![
+                            parent_id,
+                            value.child_id,
+                            value.kind,
+                            value.role,
+                            value.sequence_index
+                        ])
                     {
                         match classify_db_error(&e, &value.child_id) {
                             ForeignKeyViolationClassification::Retryable => {
                                 self.deferred_relationship_additions
-                                    .insert(parent_id.to_string(), value.clone());
+                                    .push(value.with_parent(parent_id));
                             }
                             ForeignKeyViolationClassification::Fatal => {
                                 continue;
@@ -388,11 +539,18 @@ impl AreaDatabase {
                     }
                 }
                 RelationshipChange::Remove { value } => {
+                    // A member whose role changed was already updated by the corresponding addition.
                     self.conn
                         .prepare_cached(
-                            "DELETE FROM entity_relationships where parent_id = ? and child_id = ? AND kind = ?",
+                            "DELETE FROM entity_relationships where parent_id = ? and child_id = ? AND kind = ? AND role IS ? AND sequence_index IS ?",
                         )?
-                        .execute(params![parent_id, value.child_id, value.kind])?;
+                        .execute(params![
+                            parent_id,
+                            value.child_id,
+                            value.kind,
+                            value.role,
+                            value.sequence_index
+                        ])?;
                 }
             }
         }
@@ -403,7 +561,7 @@ impl AreaDatabase {
         Ok(self
             .conn
             .prepare_cached(
-                "SELECT child_id from entity_relationships WHERE parent_id = ? AND kind = ?",
+                "SELECT child_id from entity_relationships WHERE parent_id = ? AND kind = ? ORDER BY sequence_index",
             )?
             .query_and_then(
                 params![parent_id, EntityRelationshipKind::OSMChild],
@@ -413,6 +571,32 @@ impl AreaDatabase {
             .collect())
     }
 
+    /// Returns the members of the given entity in their order, together with their roles.
+    pub fn get_entity_child_relationships(
+        &self,
+        parent_id: &str,
+    ) -> Result<Vec<RootedEntityRelationship>> {
+        Ok(self
+            .conn
+            .prepare_cached(
+                "SELECT child_id, kind, role, sequence_index from entity_relationships WHERE parent_id = ? AND kind = ? ORDER BY sequence_index",
+            )?
+            .query_and_then(
+                params![parent_id, EntityRelationshipKind::OSMChild],
+                |row| -> Result<RootedEntityRelationship> {
+                    let mut relationship = RootedEntityRelationship::new(
+                        &row.get_unwrap::<_, String>(0),
+                        row.get_unwrap(1),
+                    );
+                    relationship.role = row.get_unwrap(2);
+                    relationship.sequence_index = row.get_unwrap(3);
+                    Ok(relationship)
+                },
+            )?
+            .filter_map(Result::ok)
+            .collect())
+    }
+
     pub fn get_parent_count(&self, child_id: &str) -> Result<u32> {
         Ok(self
             .conn
@@ -445,7 +629,9 @@ impl AreaDatabase {
             .execute(params![
                 relationship.parent_id.as_str(),
                 relationship.child_id.as_str(),
-                relationship.kind
+                relationship.kind,
+                relationship.role.as_deref(),
+                relationship.sequence_index,
             ]); // Whatever error there is fatal - the relationships should all be there and nothing else should be inserted to the relationships table at this point.
         if let Err(e) = res {
             match classify_db_error(&e, &relationship.child_id) {
@@ -459,12 +645,8 @@ impl AreaDatabase {
     }
 
     pub fn apply_deferred_relationship_additions(&mut self) -> Result<()> {
-        for (parent, child) in self.deferred_relationship_additions.iter() {
-            self.insert_entity_relationship(&EntityRelationship::new(
-                parent,
-                &child.child_id,
-                child.kind,
-            ))?;
+        for relationship in self.deferred_relationship_additions.iter() {
+            self.insert_entity_relationship(relationship)?;
         }
         self.deferred_relationship_additions.clear();
         Ok(())
@@ -486,6 +668,16 @@ impl AreaDatabase {
         Ok(results)
     }
 
+    /// Returns the routes, e.g. bus lines, which stop at the given stop or platform.
+    pub fn get_lines_stopping_at(&self, stop_id: &str) -> Result<Vec<Entity>> {
+        let mut stmt = self.conn.prepare_cached(&format!("SELECT {} FROM entities WHERE discriminator = 'Route' AND (EXISTS (SELECT 1 FROM json_each(entities.data, '$.stops') WHERE json_each.value = :stop_id) OR EXISTS (SELECT 1 FROM json_each(entities.data, '$.platforms') WHERE json_each.value = :stop_id))", entity_columns_sql(SimplificationLevel::Full)))?;
+        let results = stmt
+            .query_map(named_params! {":stop_id": stop_id}, row_to_entity)?
+            .map(|e| e.expect("Failed to retrieve entity"))
+            .collect();
+        Ok(results)
+    }
+
     pub fn get_contained_entity_ids(&self, entity_id: &str) -> Result<Vec<String>> {
         let mut stmt = self.conn.prepare_cached("SELECT id FROM entities, (SELECT geometry FROM entities WHERE id = ?) AS outer WHERE entities.id not like 'r%' AND entities.rowid IN (SELECT rowid from SpatialIndex WHERE f_table_name = 'entities' AND search_frame = outer.geometry) AND contains(outer.geometry, entities.geometry)")?;
         let results = stmt
@@ -528,16 +720,21 @@ impl AreaDatabase {
     }
 
     pub fn get_relationships_related_to(&self, entity_id: &str) -> Result<Vec<EntityRelationship>> {
-        let mut stmt = self.conn.prepare_cached("SELECT parent_id, child_id, kind FROM entity_relationships WHERE parent_id = :entity_id OR child_id = :entity_id")?;
+        let mut stmt = self.conn.prepare_cached("SELECT parent_id, child_id, kind, role, sequence_index FROM entity_relationships WHERE parent_id = :entity_id OR child_id = :entity_id")?;
         let results = stmt
             .query_map(
                 named_params! {":entity_id": entity_id},
                 |row| -> rusqlite::Result<EntityRelationship> {
-                    Ok(EntityRelationship::new(
+                    let mut relationship = EntityRelationship::new(
                         &row.get_unwrap::<_, String>(0),
                         &row.get_unwrap::<_, String>(1),
                         row.get_unwrap(2),
-                    ))
+                    );
+                    relationship.role = row
+                        .get_unwrap::<_, Option<String>>(3)
+                        .map(SmolStr::new);
+                    relationship.sequence_index = row.get_unwrap(4);
+                    Ok(relationship)
                 },
             )?
             .map(|e| e.expect("Should not happen"))
@@ -550,6 +747,49 @@ impl AreaDatabase {
         Ok(stmt.query_row([], |row| Ok(row.get_unwrap(0)))?)
     }
 
+    /// Returns the entities matching the query, with their geometries clipped to the rectangle, e.g. for the map tiles.
+    pub fn get_entities_clipped_to(
+        &self,
+        query: &EntitiesQuery,
+        min_x: f64,
+        max_x: f64,
+        min_y: f64,
+        max_y: f64,
+    ) -> Result<Vec<Entity>> {
+        // The empty intersections, or the failed ones of invalid geometries, are null.
+        let sql = format!("SELECT * FROM (SELECT q.id, q.discriminator, AsBinary(ST_Intersection(GeomFromWKB(q.geometry, 4326), BuildMbr(:clip_min_x, :clip_min_y, :clip_max_x, :clip_max_y, 4326))) AS geometry, q.data, q.effective_width, q.geodesic_length, q.geodesic_area, q.centroid_lon, q.centroid_lat, q.start_bearing, q.end_bearing FROM ({}) AS q) WHERE geometry IS NOT NULL", query.to_query_sql());
+        let orig_params = query.to_query_params();
+        let mut params: Vec<(&str, &dyn ToSql)> = orig_params
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect();
+        params.push((":clip_min_x", &min_x));
+        params.push((":clip_max_x", &max_x));
+        params.push((":clip_min_y", &min_y));
+        params.push((":clip_max_y", &max_y));
+        let mut stmt = self.conn.prepare_cached(&sql)?;
+        let res = stmt
+            .query_map(params.as_slice(), row_to_entity)?
+            .map(|e| e.expect("Failed to retrieve entity"))
+            .collect();
+        Ok(res)
+    }
+
+    /// The bounding box of all the entities, as min_x, max_x, min_y and max_y, None for an empty area.
+    pub fn get_bounds(&self) -> Result<Option<(f64, f64, f64, f64)>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT min(xmin), max(xmax), min(ymin), max(ymax) FROM idx_entities_geometry",
+        )?;
+        let bounds: (Option<f64>, Option<f64>, Option<f64>, Option<f64>) =
+            stmt.query_row([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+        Ok(match bounds {
+            (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) => {
+                Some((min_x, max_x, min_y, max_y))
+            }
+            _ => None,
+        })
+    }
+
     pub fn num_entities(&self) -> Result<usize> {
         self.num_rows_in_table("entities")
     }
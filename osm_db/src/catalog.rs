@@ -0,0 +1,185 @@
+use crate::file_finder;
+use crate::{Error, Result};
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const MO_MAGIC_LE: u32 = 0x950412de;
+const MO_MAGIC_BE: u32 = 0xde120495;
+const CATALOG_DOMAIN: &str = "messages";
+
+static CATALOGS: Lazy<Mutex<HashMap<String, Option<Arc<Catalog>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A compiled gettext message catalog, the same one the Python client installs through `gettext.install`.
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    pub fn from_mo_bytes(data: &[u8]) -> Result<Self> {
+        let magic = read_u32(data, 0, true)?;
+        let little_endian = match magic {
+            MO_MAGIC_LE => true,
+            MO_MAGIC_BE => false,
+            _ => {
+                return Err(Error::MalformedCatalog(format!(
+                    "Invalid magic number {:#x}",
+                    magic
+                )))
+            }
+        };
+        let count = read_u32(data, 8, little_endian)? as usize;
+        let originals_offset = read_u32(data, 12, little_endian)? as usize;
+        let translations_offset = read_u32(data, 16, little_endian)? as usize;
+        // Both tables have an eight byte descriptor per message, so a corrupt count can't make us reserve more than the file holds.
+        for table_offset in [originals_offset, translations_offset] {
+            let table_end = count
+                .checked_mul(8)
+                .and_then(|len| len.checked_add(table_offset));
+            if !matches!(table_end, Some(end) if end <= data.len()) {
+                return Err(Error::MalformedCatalog(format!(
+                    "Table of {} messages at {} out of bounds",
+                    count, table_offset
+                )));
+            }
+        }
+        let mut messages = HashMap::with_capacity(count);
+        for idx in 0..count {
+            let original = read_string(data, originals_offset + idx * 8, little_endian)?;
+            let translation = read_string(data, translations_offset + idx * 8, little_endian)?;
+            // The header entry has an empty msgid, and plural forms are separated by NULs, we care only about the singular.
+            if original.is_empty() {
+                continue;
+            }
+            let singular = original.split('\0').next().unwrap_or_default();
+            let translated = translation.split('\0').next().unwrap_or_default();
+            if !translated.is_empty() {
+                messages.insert(singular.to_string(), translated.to_string());
+            }
+        }
+        Ok(Self { messages })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_mo_bytes(&fs::read(path)?)
+    }
+
+    /// Returns the catalog for the given language (e.g. `cs` or `cs_CZ`), loading it on first use.
+    pub fn for_language(language: &str) -> Option<Arc<Catalog>> {
+        let mut catalogs = CATALOGS.lock().unwrap();
+        if let Some(catalog) = catalogs.get(language) {
+            return catalog.clone();
+        }
+        let catalog = find_catalog_file(language).and_then(|path| match Catalog::load(&path) {
+            Ok(catalog) => {
+                debug!(
+                    "Loaded {} messages for language {} from {}.",
+                    catalog.messages.len(),
+                    language,
+                    path.display()
+                );
+                Some(Arc::new(catalog))
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to load the message catalog {}, error: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        });
+        catalogs.insert(language.to_string(), catalog.clone());
+        catalog
+    }
+
+    pub fn gettext<'a>(&'a self, msgid: &'a str) -> &'a str {
+        self.messages
+            .get(msgid)
+            .map(|m| m.as_str())
+            .unwrap_or(msgid)
+    }
+}
+
+/// Translates the message for the given language, falling back to the message itself when there's no catalog or translation.
+pub fn gettext(msgid: &str, language: &str) -> String {
+    match Catalog::for_language(language) {
+        Some(catalog) => catalog.gettext(msgid).to_string(),
+        None => msgid.to_string(),
+    }
+}
+
+fn find_catalog_file(language: &str) -> Option<PathBuf> {
+    let locale_dir = file_finder::find_file_in_current_or_exe_dir("locale")?;
+    // Try the full locale name first, then only the language part, the same as gettext does.
+    let mut candidates = vec![language];
+    if let Some((lang, _territory)) = language.split_once('_') {
+        candidates.push(lang);
+    }
+    for candidate in candidates {
+        let mut path = locale_dir.clone();
+        path.push(candidate);
+        path.push("LC_MESSAGES");
+        path.push(format!("{}.mo", CATALOG_DOMAIN));
+        if path.exists() {
+            return Some(path);
+        }
+    }
+    None
+}
+
+fn read_u32(data: &[u8], offset: usize, little_endian: bool) -> Result<u32> {
+    let bytes: [u8; 4] = data
+        .get(offset..offset + 4)
+        .ok_or_else(|| Error::MalformedCatalog(format!("Offset {} out of bounds", offset)))?
+        .try_into()
+        .expect("Incorrect slice length");
+    Ok(if little_endian {
+        u32::from_le_bytes(bytes)
+    } else {
+        u32::from_be_bytes(bytes)
+    })
+}
+
+fn read_string(data: &[u8], descriptor_offset: usize, little_endian: bool) -> Result<&str> {
+    let len = read_u32(data, descriptor_offset, little_endian)? as usize;
+    let offset = read_u32(data, descriptor_offset + 4, little_endian)? as usize;
+    let bytes = data.get(offset..offset + len).ok_or_else(|| {
+        Error::MalformedCatalog(format!("String at {} out of bounds", offset))
+    })?;
+    std::str::from_utf8(bytes)
+        .map_err(|e| Error::MalformedCatalog(format!("String at {} is not UTF-8: {}", offset, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mo_bytes(count: u32) -> Vec<u8> {
+        // The tables of one message, the header entry, followed by its empty strings.
+        let mut data = vec![];
+        for value in [MO_MAGIC_LE, 0, count, 28, 36, 0, 0, 0, 44, 0, 44] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.push(0);
+        data
+    }
+
+    #[test]
+    fn reads_a_catalog() {
+        assert!(Catalog::from_mo_bytes(&mo_bytes(1)).unwrap().messages.is_empty());
+    }
+
+    #[test]
+    fn rejects_a_count_larger_than_the_file() {
+        assert!(matches!(
+            Catalog::from_mo_bytes(&mo_bytes(u32::MAX)),
+            Err(Error::MalformedCatalog(_))
+        ));
+    }
+}
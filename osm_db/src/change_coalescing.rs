@@ -0,0 +1,569 @@
+//! Merges the semantic changes of an update run, so the clients receive at most one change per entity in most cases.
+//!
+//! The coalesced changes leave an area database in the same state as the original ones. A merged change takes the place of the first change of its entity, the same place the inferred relationships are added to, unless it would add a relationship before the creation of its child.
+use crate::entity::Entity;
+use crate::entity_relationship::RootedEntityRelationship;
+use crate::entity_relationship_kind::EntityRelationshipKind;
+use crate::semantic_change::{EntryChange, RelationshipChange, SemanticChange};
+use hashbrown::{HashMap, HashSet};
+
+enum Merged {
+    One(SemanticChange),
+    Both(SemanticChange, SemanticChange),
+    /// The entity was created and removed again.
+    Nothing,
+}
+
+fn entry_key(change: &EntryChange) -> &str {
+    match change {
+        EntryChange::Create { key, .. }
+        | EntryChange::Update { key, .. }
+        | EntryChange::Remove { key } => key,
+    }
+}
+
+/// Whether the changes of the keys can affect each other, the data keys are paths separated by slashes.
+fn keys_overlap(first: &str, second: &str) -> bool {
+    let (shorter, longer) = if first.len() <= second.len() {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    longer
+        .strip_prefix(shorter)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Merges every change with the last preceding change of the same key, when nothing in between touched that key.
+fn coalesce_entry_changes(changes: Vec<EntryChange>) -> Vec<EntryChange> {
+    use EntryChange::*;
+    let mut coalesced: Vec<EntryChange> = Vec::with_capacity(changes.len());
+    for change in changes {
+        let previous = coalesced
+            .iter()
+            .rposition(|c| keys_overlap(entry_key(c), entry_key(&change)))
+            .filter(|&pos| entry_key(&coalesced[pos]) == entry_key(&change));
+        let pos = match previous {
+            Some(pos) => pos,
+            None => {
+                coalesced.push(change);
+                continue;
+            }
+        };
+        let merged = match (&coalesced[pos], change) {
+            (Update { old_value, .. }, Update { key, new_value, .. }) => Update {
+                key,
+                old_value: old_value.clone(),
+                new_value,
+            },
+            (Create { .. }, Update { key, new_value, .. }) => Create {
+                key,
+                value: new_value,
+            },
+            (Update { .. }, Remove { key }) => Remove { key },
+            // Creating a nested key creates the missing maps on the way, so a removal does not undo it completely.
+            (_, change) => {
+                coalesced.push(change);
+                continue;
+            }
+        };
+        coalesced[pos] = merged;
+    }
+    coalesced
+}
+
+fn relationship_key(
+    relationship: &RootedEntityRelationship,
+) -> (&str, EntityRelationshipKind, Option<u32>) {
+    (
+        &relationship.child_id,
+        relationship.kind,
+        relationship.sequence_index,
+    )
+}
+
+/// An addition replaces the relationship with the same child, kind and position, a removal removes only an exact match.
+fn coalesce_relationship_changes(changes: Vec<RelationshipChange>) -> Vec<RelationshipChange> {
+    use RelationshipChange::*;
+    let mut coalesced: Vec<RelationshipChange> = Vec::with_capacity(changes.len());
+    for change in changes {
+        match &change {
+            Add { value } => {
+                // Whatever happened to the relationship before, the addition decides its state.
+                coalesced.retain(|c| match c {
+                    Add { value: v } | Remove { value: v } => {
+                        relationship_key(v) != relationship_key(value)
+                    }
+                });
+            }
+            Remove { value } => {
+                let previous = coalesced.iter().rev().find(|c| match c {
+                    Add { value: v } | Remove { value: v } => {
+                        relationship_key(v) == relationship_key(value)
+                    }
+                });
+                match previous {
+                    // Already removed, or the relationship is now a different one.
+                    Some(Remove { value: v }) if v == value => continue,
+                    Some(Add { value: v }) if v != value => continue,
+                    _ => {}
+                }
+            }
+        }
+        coalesced.push(change);
+    }
+    coalesced
+}
+
+/// The later relationship with the same child, kind and position replaces the earlier one when inserted.
+fn dedup_relationships(
+    relationships: Vec<RootedEntityRelationship>,
+) -> Vec<RootedEntityRelationship> {
+    let mut deduplicated: Vec<RootedEntityRelationship> = Vec::with_capacity(relationships.len());
+    for relationship in relationships {
+        deduplicated.retain(|r| relationship_key(r) != relationship_key(&relationship));
+        deduplicated.push(relationship);
+    }
+    deduplicated
+}
+
+fn fold_update_into_create(create: SemanticChange, update: SemanticChange) -> SemanticChange {
+    let (id, geometry, discriminator, data, effective_width, entity_relationships) = match create {
+        SemanticChange::Create {
+            id,
+            geometry,
+            discriminator,
+            data,
+            effective_width,
+            entity_relationships,
+        } => (
+            id,
+            geometry,
+            discriminator,
+            data,
+            effective_width,
+            entity_relationships,
+        ),
+        _ => unreachable!("Only creates can be folded into"),
+    };
+    let (property_changes, data_changes, relationship_changes) = match update {
+        SemanticChange::Update {
+            property_changes,
+            data_changes,
+            relationship_changes,
+            ..
+        } => (property_changes, data_changes, relationship_changes),
+        _ => unreachable!("Only updates can be folded"),
+    };
+    let mut entity_relationships = dedup_relationships(entity_relationships);
+    let mut entity = Entity {
+        id: id.as_str().into(),
+        geometry: base64::decode(&geometry).expect("Geometry should be base64 encoded"),
+        discriminator: discriminator.as_str().into(),
+        data,
+        parsed_data: None,
+        effective_width,
+        metrics: Default::default(),
+    };
+    entity.apply_property_changes(&property_changes);
+    entity.apply_data_changes(&data_changes);
+    for change in relationship_changes {
+        match change {
+            RelationshipChange::Add { value } => {
+                entity_relationships.retain(|r| relationship_key(r) != relationship_key(&value));
+                entity_relationships.push(value);
+            }
+            RelationshipChange::Remove { value } => entity_relationships.retain(|r| *r != value),
+        }
+    }
+    SemanticChange::creating(
+        id,
+        entity.geometry,
+        entity.discriminator.to_string(),
+        entity.data,
+        entity.effective_width,
+        entity_relationships,
+    )
+}
+
+/// The property changes are applied before the data changes, so a replaced data map can't be moved before the older data changes.
+fn can_merge_updates(previous: &SemanticChange, next: &SemanticChange) -> bool {
+    let previous_changes_data =
+        matches!(previous, SemanticChange::Update { data_changes, .. } if !data_changes.is_empty());
+    let next_replaces_data = matches!(next, SemanticChange::Update { property_changes, .. } if property_changes.iter().any(|c| entry_key(c) == "data"));
+    !(previous_changes_data && next_replaces_data)
+}
+
+fn merge(previous: SemanticChange, next: SemanticChange) -> Merged {
+    use SemanticChange::*;
+    if !can_merge_updates(&previous, &next) {
+        return Merged::Both(previous, next);
+    }
+    match (previous, next) {
+        (previous @ Create { .. }, next @ Update { .. }) => {
+            Merged::One(fold_update_into_create(previous, next))
+        }
+        (Create { .. }, Remove { .. }) => Merged::Nothing,
+        (Update { .. }, next @ Remove { .. }) => Merged::One(next),
+        (
+            Update {
+                osm_id,
+                mut property_changes,
+                mut data_changes,
+                mut relationship_changes,
+            },
+            Update {
+                property_changes: next_property_changes,
+                data_changes: next_data_changes,
+                relationship_changes: next_relationship_changes,
+                ..
+            },
+        ) => {
+            property_changes.extend(next_property_changes);
+            data_changes.extend(next_data_changes);
+            relationship_changes.extend(next_relationship_changes);
+            Merged::One(Update {
+                osm_id,
+                property_changes,
+                data_changes,
+                relationship_changes,
+            })
+        }
+        // Updating a removed entity does nothing.
+        (previous @ Remove { .. }, Update { .. }) | (previous @ Remove { .. }, Remove { .. }) => {
+            Merged::One(previous)
+        }
+        (previous, next) => Merged::Both(previous, next),
+    }
+}
+
+/// Simplifies the entry and relationship changes of a change, returns None when nothing is left to apply.
+fn simplify(change: SemanticChange) -> Option<SemanticChange> {
+    use SemanticChange::*;
+    match change {
+        Update {
+            osm_id,
+            property_changes,
+            data_changes,
+            relationship_changes,
+        } => {
+            let property_changes = coalesce_entry_changes(property_changes);
+            let data_changes = coalesce_entry_changes(data_changes);
+            let relationship_changes = coalesce_relationship_changes(relationship_changes);
+            if property_changes.is_empty()
+                && data_changes.is_empty()
+                && relationship_changes.is_empty()
+            {
+                None
+            } else {
+                Some(Update {
+                    osm_id,
+                    property_changes,
+                    data_changes,
+                    relationship_changes,
+                })
+            }
+        }
+        Create {
+            id,
+            geometry,
+            discriminator,
+            data,
+            effective_width,
+            entity_relationships,
+        } => Some(Create {
+            id,
+            geometry,
+            discriminator,
+            data,
+            effective_width,
+            entity_relationships: dedup_relationships(entity_relationships),
+        }),
+        change @ Remove { .. } => Some(change),
+    }
+}
+
+/// Whether moving the change to the position adds a relationship before its child is created, the relationship would be skipped there.
+fn adds_relationship_before_child(
+    change: &SemanticChange,
+    pos: usize,
+    latest_positions: &HashMap<String, usize>,
+) -> bool {
+    match change {
+        SemanticChange::Update {
+            relationship_changes,
+            ..
+        } => relationship_changes.iter().any(|c| match c {
+            RelationshipChange::Add { value } => latest_positions
+                .get(value.child_id.as_str())
+                .is_some_and(|&child_pos| child_pos > pos),
+            RelationshipChange::Remove { .. } => false,
+        }),
+        _ => false,
+    }
+}
+
+/// Merges the sequential updates of an entity, folds the updates into the creation of the entity, drops the entities created and removed again, and removes the redundant relationship changes.
+pub fn coalesce_changes(changes: Vec<SemanticChange>) -> Vec<SemanticChange> {
+    let mut coalesced: Vec<Option<SemanticChange>> = Vec::with_capacity(changes.len());
+    let mut latest_positions: HashMap<String, usize> = HashMap::new();
+    let mut removed_ids = HashSet::new();
+    for change in changes {
+        let osm_id = change.osm_id().to_string();
+        match latest_positions.get(&osm_id).copied() {
+            // The child was created or changed after the previous change of the entity, so it may not exist there yet.
+            Some(pos) if adds_relationship_before_child(&change, pos, &latest_positions) => {
+                latest_positions.insert(osm_id, coalesced.len());
+                coalesced.push(Some(change));
+            }
+            Some(pos) => {
+                let previous = coalesced[pos].take().expect("Latest change missing");
+                match merge(previous, change) {
+                    Merged::One(merged) => coalesced[pos] = Some(merged),
+                    Merged::Both(previous, next) => {
+                        coalesced[pos] = Some(previous);
+                        latest_positions.insert(osm_id, coalesced.len());
+                        coalesced.push(Some(next));
+                    }
+                    Merged::Nothing => {
+                        latest_positions.remove(&osm_id);
+                        removed_ids.insert(osm_id);
+                    }
+                }
+            }
+            // The entity does not exist any more, so only its new creation matters.
+            None if removed_ids.contains(&osm_id) && !change.is_create() => {}
+            None => {
+                removed_ids.remove(&osm_id);
+                latest_positions.insert(osm_id, coalesced.len());
+                coalesced.push(Some(change));
+            }
+        }
+    }
+    coalesced
+        .into_iter()
+        .flatten()
+        .filter_map(simplify)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::area_db::AreaDatabase;
+    use fastrand::Rng;
+    use serde_json::{json, Value};
+
+    /// The entities the generated changes create, update and remove.
+    const IDS: [&str; 3] = ["w1", "w2", "w3"];
+    /// The relationship children which exist before the changes and are never changed, the entities of the run are the children of the relationships as well.
+    const CHILD_IDS: [&str; 2] = ["n100", "n101"];
+    const DISCRIMINATORS: [&str; 2] = ["Road", "Building"];
+    /// The values of the address key are always maps, otherwise creating a nested key could not create the intermediate map.
+    const DATA_KEYS: [&str; 4] = ["name", "address", "address/street", "address/city"];
+    const CASES: u64 = 100;
+    const MAX_CHANGES: usize = 12;
+
+    fn point_wkb(x: f64, y: f64) -> Vec<u8> {
+        let mut wkb = vec![1];
+        wkb.extend_from_slice(&1u32.to_le_bytes());
+        wkb.extend_from_slice(&x.to_le_bytes());
+        wkb.extend_from_slice(&y.to_le_bytes());
+        wkb
+    }
+
+    fn pick<'a>(rng: &Rng, items: &[&'a str]) -> &'a str {
+        items[rng.usize(..items.len())]
+    }
+
+    fn data_value(rng: &Rng, key: &str) -> Value {
+        if key == "address" {
+            json!({"street": format!("Street {}", rng.u8(..3))})
+        } else {
+            json!(format!("value {}", rng.u8(..3)))
+        }
+    }
+
+    fn data(rng: &Rng) -> String {
+        let mut data = json!({"name": format!("name {}", rng.u8(..3))});
+        if rng.bool() {
+            data["address"] = data_value(rng, "address");
+        }
+        data.to_string()
+    }
+
+    fn relationship(rng: &Rng) -> RootedEntityRelationship {
+        let child_id = if rng.bool() {
+            pick(rng, &CHILD_IDS)
+        } else {
+            pick(rng, &IDS)
+        };
+        if rng.u8(..4) == 0 {
+            RootedEntityRelationship::new(child_id, EntityRelationshipKind::Street)
+        } else {
+            let mut relationship =
+                RootedEntityRelationship::new(child_id, EntityRelationshipKind::OSMChild);
+            relationship.role = Some(pick(rng, &["inner", "outer"]).to_string());
+            relationship.sequence_index = Some(rng.u32(..2));
+            relationship
+        }
+    }
+
+    fn relationships(rng: &Rng) -> Vec<RootedEntityRelationship> {
+        (0..rng.usize(..4)).map(|_| relationship(rng)).collect()
+    }
+
+    fn property_change(rng: &Rng) -> EntryChange {
+        let (key, value) = match rng.u8(..4) {
+            0 => (
+                "geometry",
+                json!(base64::encode(point_wkb(rng.f64(), rng.f64()))),
+            ),
+            1 => ("discriminator", json!(pick(rng, &DISCRIMINATORS))),
+            2 => ("data", json!(data(rng))),
+            _ => (
+                "effective_width",
+                if rng.bool() {
+                    Value::Null
+                } else {
+                    json!(rng.u8(..10) as f64 / 2.0)
+                },
+            ),
+        };
+        EntryChange::updating(key, Value::Null, value)
+    }
+
+    fn data_change(rng: &Rng) -> EntryChange {
+        let key = pick(rng, &DATA_KEYS);
+        match rng.u8(..3) {
+            0 => EntryChange::creating(key, data_value(rng, key)),
+            1 => EntryChange::updating(key, Value::Null, data_value(rng, key)),
+            _ => EntryChange::removing(key),
+        }
+    }
+
+    fn relationship_change(rng: &Rng) -> RelationshipChange {
+        if rng.bool() {
+            RelationshipChange::adding(relationship(rng))
+        } else {
+            RelationshipChange::removing(relationship(rng))
+        }
+    }
+
+    fn creation(rng: &Rng, id: &str) -> SemanticChange {
+        SemanticChange::creating(
+            id.to_string(),
+            point_wkb(rng.f64(), rng.f64()),
+            pick(rng, &DISCRIMINATORS).to_string(),
+            data(rng),
+            None,
+            relationships(rng),
+        )
+    }
+
+    /// The creations of the entities existing before an update run and the changes of the run, the entities are created only when missing and updated or removed only when present.
+    fn scenario(rng: &Rng) -> (Vec<SemanticChange>, Vec<SemanticChange>) {
+        let mut existing: Vec<&str> = IDS.iter().copied().filter(|_| rng.bool()).collect();
+        let initial = CHILD_IDS
+            .iter()
+            .chain(existing.iter())
+            .map(|id| creation(rng, id))
+            .collect();
+        let mut changes = vec![];
+        for _ in 0..rng.usize(1..=MAX_CHANGES) {
+            let id = pick(rng, &IDS);
+            let change = match existing.iter().position(|e| *e == id) {
+                None => {
+                    existing.push(id);
+                    creation(rng, id)
+                }
+                Some(pos) if rng.u8(..4) == 0 => {
+                    existing.remove(pos);
+                    SemanticChange::removing(id)
+                }
+                Some(_) => SemanticChange::updating(
+                    id,
+                    (0..rng.usize(..2)).map(|_| property_change(rng)).collect(),
+                    (0..rng.usize(..4)).map(|_| data_change(rng)).collect(),
+                    (0..rng.usize(..3))
+                        .map(|_| relationship_change(rng))
+                        .collect(),
+                ),
+            };
+            changes.push(change);
+        }
+        (initial, changes)
+    }
+
+    /// The entities and relationships of the database, with the data parsed, so the key order does not matter.
+    type State = (
+        Vec<(String, String, Vec<u8>, Option<f64>, Value)>,
+        Vec<(String, String, i64, Option<String>, Option<u32>)>,
+    );
+
+    fn state_of(db: &AreaDatabase) -> State {
+        let entities = db
+            .conn
+            .prepare("SELECT id, discriminator, AsBinary(geometry), effective_width, data FROM entities ORDER BY id")
+            .unwrap()
+            .query_map([], |row| {
+                let data: String = row.get(4)?;
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    serde_json::from_str(&data).unwrap(),
+                ))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        let relationships = db
+            .conn
+            .prepare("SELECT parent_id, child_id, kind, role, sequence_index FROM entity_relationships ORDER BY parent_id, child_id, kind, sequence_index, role")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        (entities, relationships)
+    }
+
+    fn apply_all(db: &mut AreaDatabase, changes: &[SemanticChange]) {
+        for change in changes {
+            db.apply_change(change).unwrap();
+        }
+    }
+
+    #[test]
+    fn coalesced_changes_leave_the_same_state() {
+        for seed in 0..CASES {
+            let rng = Rng::with_seed(seed);
+            let (initial, original) = scenario(&rng);
+            let coalesced = coalesce_changes(original.clone());
+            assert!(coalesced.len() <= original.len());
+            let mut databases = Vec::with_capacity(2);
+            for changes in [&original, &coalesced] {
+                let mut db = AreaDatabase::create_in_memory()
+                    .expect("The comparison needs the mod_spatialite SQLite extension");
+                apply_all(&mut db, &initial);
+                apply_all(&mut db, changes);
+                databases.push(state_of(&db));
+            }
+            assert_eq!(
+                databases[0], databases[1],
+                "The coalesced changes differ for the seed {}, the original changes: {:?}, the coalesced changes: {:?}",
+                seed, original, coalesced
+            );
+        }
+    }
+}
@@ -0,0 +1,415 @@
+//! The binary encoding of the semantic changes.
+//!
+//! An encoded change starts with the version of its format, followed by the bincode serialization of the wire types of that version. The geometries are raw WKB and the entity data and the changed values are stored as bincode values, instead of base64 and JSON strings nested in JSON.
+//! Bincode is not self-describing, so these types must not change, a new format needs a new version and new wire types, while the decoding of the old versions stays.
+use crate::entity_relationship::RootedEntityRelationship;
+use crate::entity_relationship_kind::EntityRelationshipKind;
+use crate::semantic_change::{EntryChange, RelationshipChange, SemanticChange};
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub(crate) const BINARY_V1: u8 = 1;
+
+/// A JSON value, bincode can't read back the self-describing serde_json ones.
+#[derive(Serialize, Deserialize)]
+enum WireJson {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Unsigned(u64),
+    Float(f64),
+    String(String),
+    Array(Vec<WireJson>),
+    Object(Vec<(String, WireJson)>),
+}
+
+impl From<&Value> for WireJson {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => WireJson::Null,
+            Value::Bool(value) => WireJson::Bool(*value),
+            Value::Number(number) => match (number.as_i64(), number.as_u64(), number.as_f64()) {
+                (Some(value), _, _) => WireJson::Integer(value),
+                (None, Some(value), _) => WireJson::Unsigned(value),
+                (None, None, value) => WireJson::Float(value.unwrap_or_default()),
+            },
+            Value::String(value) => WireJson::String(value.clone()),
+            Value::Array(values) => WireJson::Array(values.iter().map(WireJson::from).collect()),
+            Value::Object(entries) => WireJson::Object(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<WireJson> for Value {
+    fn from(value: WireJson) -> Self {
+        match value {
+            WireJson::Null => Value::Null,
+            WireJson::Bool(value) => Value::Bool(value),
+            WireJson::Integer(value) => Value::from(value),
+            WireJson::Unsigned(value) => Value::from(value),
+            WireJson::Float(value) => Value::from(value),
+            WireJson::String(value) => Value::String(value),
+            WireJson::Array(values) => Value::Array(values.into_iter().map(Value::from).collect()),
+            WireJson::Object(entries) => Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect::<Map<_, _>>(),
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum WireValue {
+    Json(WireJson),
+    /// A geometry, which is base64-encoded in the JSON values.
+    Wkb(Vec<u8>),
+}
+
+impl WireValue {
+    /// Only the geometry property is a geometry, a data field of the same name is not.
+    fn from_value(key: &str, value: &Value, is_property: bool) -> Result<Self> {
+        match value {
+            Value::String(encoded) if is_property && key == "geometry" => {
+                Ok(WireValue::Wkb(base64::decode(encoded)?))
+            }
+            _ => Ok(WireValue::Json(value.into())),
+        }
+    }
+
+    fn into_value(self) -> Result<Value> {
+        match self {
+            WireValue::Json(json) => Ok(json.into()),
+            WireValue::Wkb(wkb) => Ok(Value::String(base64::encode(&wkb))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum WireEntryChange {
+    Create {
+        key: String,
+        value: WireValue,
+    },
+    Update {
+        key: String,
+        old_value: WireValue,
+        new_value: WireValue,
+    },
+    Remove {
+        key: String,
+    },
+}
+
+impl WireEntryChange {
+    fn from_change(change: &EntryChange, is_property: bool) -> Result<Self> {
+        Ok(match change {
+            EntryChange::Create { key, value } => WireEntryChange::Create {
+                key: key.clone(),
+                value: WireValue::from_value(key, value, is_property)?,
+            },
+            EntryChange::Update {
+                key,
+                old_value,
+                new_value,
+            } => WireEntryChange::Update {
+                key: key.clone(),
+                old_value: WireValue::from_value(key, old_value, is_property)?,
+                new_value: WireValue::from_value(key, new_value, is_property)?,
+            },
+            EntryChange::Remove { key } => WireEntryChange::Remove { key: key.clone() },
+        })
+    }
+
+    fn into_change(self) -> Result<EntryChange> {
+        Ok(match self {
+            WireEntryChange::Create { key, value } => EntryChange::Create {
+                key,
+                value: value.into_value()?,
+            },
+            WireEntryChange::Update {
+                key,
+                old_value,
+                new_value,
+            } => EntryChange::Update {
+                key,
+                old_value: old_value.into_value()?,
+                new_value: new_value.into_value()?,
+            },
+            WireEntryChange::Remove { key } => EntryChange::Remove { key },
+        })
+    }
+}
+
+// The relationships skip their empty fields when serialized, which bincode can't read back.
+#[derive(Serialize, Deserialize)]
+struct WireRelationship {
+    child_id: String,
+    kind: EntityRelationshipKind,
+    role: Option<String>,
+    sequence_index: Option<u32>,
+}
+
+impl From<&RootedEntityRelationship> for WireRelationship {
+    fn from(relationship: &RootedEntityRelationship) -> Self {
+        WireRelationship {
+            child_id: relationship.child_id.clone(),
+            kind: relationship.kind,
+            role: relationship.role.clone(),
+            sequence_index: relationship.sequence_index,
+        }
+    }
+}
+
+impl From<WireRelationship> for RootedEntityRelationship {
+    fn from(relationship: WireRelationship) -> Self {
+        RootedEntityRelationship {
+            child_id: relationship.child_id,
+            kind: relationship.kind,
+            role: relationship.role,
+            sequence_index: relationship.sequence_index,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum WireRelationshipChange {
+    Add(WireRelationship),
+    Remove(WireRelationship),
+}
+
+#[derive(Serialize, Deserialize)]
+enum WireChange {
+    Create {
+        id: String,
+        geometry: Vec<u8>,
+        discriminator: String,
+        data: WireJson,
+        effective_width: Option<f64>,
+        entity_relationships: Vec<WireRelationship>,
+    },
+    Remove {
+        osm_id: String,
+    },
+    Update {
+        osm_id: String,
+        property_changes: Vec<WireEntryChange>,
+        data_changes: Vec<WireEntryChange>,
+        relationship_changes: Vec<WireRelationshipChange>,
+    },
+}
+
+fn wire_entry_changes(
+    changes: &[EntryChange],
+    are_properties: bool,
+) -> Result<Vec<WireEntryChange>> {
+    changes
+        .iter()
+        .map(|change| WireEntryChange::from_change(change, are_properties))
+        .collect()
+}
+
+fn entry_changes(changes: Vec<WireEntryChange>) -> Result<Vec<EntryChange>> {
+    changes
+        .into_iter()
+        .map(WireEntryChange::into_change)
+        .collect()
+}
+
+pub(crate) fn encode_v1(change: &SemanticChange) -> Result<Vec<u8>> {
+    let wire = match change {
+        SemanticChange::Create {
+            id,
+            geometry,
+            discriminator,
+            data,
+            effective_width,
+            entity_relationships,
+        } => WireChange::Create {
+            id: id.clone(),
+            geometry: base64::decode(geometry)?,
+            discriminator: discriminator.clone(),
+            data: (&serde_json::from_str::<Value>(data)?).into(),
+            effective_width: *effective_width,
+            entity_relationships: entity_relationships
+                .iter()
+                .map(WireRelationship::from)
+                .collect(),
+        },
+        SemanticChange::Remove { osm_id } => WireChange::Remove {
+            osm_id: osm_id.clone(),
+        },
+        SemanticChange::Update {
+            osm_id,
+            property_changes,
+            data_changes,
+            relationship_changes,
+        } => WireChange::Update {
+            osm_id: osm_id.clone(),
+            property_changes: wire_entry_changes(property_changes, true)?,
+            data_changes: wire_entry_changes(data_changes, false)?,
+            relationship_changes: relationship_changes
+                .iter()
+                .map(|change| match change {
+                    RelationshipChange::Add { value } => WireRelationshipChange::Add(value.into()),
+                    RelationshipChange::Remove { value } => {
+                        WireRelationshipChange::Remove(value.into())
+                    }
+                })
+                .collect(),
+        },
+    };
+    let mut encoded = vec![BINARY_V1];
+    bincode::serialize_into(&mut encoded, &wire)?;
+    Ok(encoded)
+}
+
+pub(crate) fn decode_v1(encoded: &[u8]) -> Result<SemanticChange> {
+    let wire: WireChange = bincode::deserialize(encoded)?;
+    Ok(match wire {
+        WireChange::Create {
+            id,
+            geometry,
+            discriminator,
+            data,
+            effective_width,
+            entity_relationships,
+        } => SemanticChange::creating(
+            id,
+            geometry,
+            discriminator,
+            serde_json::to_string(&Value::from(data))?,
+            effective_width,
+            entity_relationships.into_iter().map(|r| r.into()).collect(),
+        ),
+        WireChange::Remove { osm_id } => SemanticChange::Remove { osm_id },
+        WireChange::Update {
+            osm_id,
+            property_changes,
+            data_changes,
+            relationship_changes,
+        } => SemanticChange::Update {
+            osm_id,
+            property_changes: entry_changes(property_changes)?,
+            data_changes: entry_changes(data_changes)?,
+            relationship_changes: relationship_changes
+                .into_iter()
+                .map(|change| match change {
+                    WireRelationshipChange::Add(value) => RelationshipChange::adding(value.into()),
+                    WireRelationshipChange::Remove(value) => {
+                        RelationshipChange::removing(value.into())
+                    }
+                })
+                .collect(),
+        },
+    })
+}
+
+/// Whether the first byte of an encoded change can start a JSON document, the older changes are JSON.
+pub(crate) fn is_json_start(byte: u8) -> bool {
+    byte >= 0x20 || byte == b'\t' || byte == b'\n' || byte == b'\r'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic_change::ChangeFormat;
+    use crate::Error;
+    use serde_json::json;
+
+    // A WKB point.
+    const GEOMETRY: [u8; 21] = [
+        1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 36, 64, 0, 0, 0, 0, 0, 0, 73, 64,
+    ];
+
+    fn creation() -> SemanticChange {
+        SemanticChange::creating(
+            "r1".to_string(),
+            GEOMETRY.to_vec(),
+            "Route".to_string(),
+            json!({"name": "Line 1", "ref": 1, "offset": -2, "width": 2.5, "oneway": false, "address": {"street": "Main"}, "stops": ["a", null]}).to_string(),
+            Some(2.5),
+            vec![
+                RootedEntityRelationship::member("n1", Some("stop"), 0),
+                RootedEntityRelationship::member("w2", None, 1),
+                RootedEntityRelationship::new("r3", EntityRelationshipKind::Street),
+            ],
+        )
+    }
+
+    fn update() -> SemanticChange {
+        SemanticChange::updating(
+            "w2",
+            vec![
+                EntryChange::updating(
+                    "geometry",
+                    json!(base64::encode(&GEOMETRY[..])),
+                    json!(base64::encode(&GEOMETRY[..])),
+                ),
+                EntryChange::creating("effective_width", json!(3.0)),
+            ],
+            vec![
+                // Not a geometry, only a data field of the same name.
+                EntryChange::creating("geometry", json!("not base64!")),
+                EntryChange::updating("lanes", json!(1), json!(2)),
+                EntryChange::removing("name"),
+            ],
+            vec![
+                RelationshipChange::adding(RootedEntityRelationship::member("n4", Some(""), 3)),
+                RelationshipChange::removing(RootedEntityRelationship::new(
+                    "r3",
+                    EntityRelationshipKind::Street,
+                )),
+            ],
+        )
+    }
+
+    fn as_json(change: &SemanticChange) -> Value {
+        serde_json::to_value(change).unwrap()
+    }
+
+    #[test]
+    fn binary_encoding_round_trips() {
+        for change in [creation(), update(), SemanticChange::removing("n5")] {
+            let encoded = encode_v1(&change).unwrap();
+            assert_eq!(encoded[0], BINARY_V1);
+            assert_eq!(
+                as_json(&decode_v1(&encoded[1..]).unwrap()),
+                as_json(&change)
+            );
+        }
+    }
+
+    #[test]
+    fn entity_data_is_not_nested_json() {
+        let encoded = encode_v1(&creation()).unwrap();
+        let nested = br#""name":"Line 1""#;
+        assert!(!encoded.windows(nested.len()).any(|w| w == nested));
+    }
+
+    #[test]
+    fn decode_tells_the_formats_apart() {
+        for change in [creation(), update()] {
+            for format in [ChangeFormat::Json, ChangeFormat::Binary] {
+                let encoded = change.encode_as(format).unwrap();
+                assert_eq!(
+                    as_json(&SemanticChange::decode(&encoded).unwrap()),
+                    as_json(&change)
+                );
+            }
+        }
+        let mut unknown = encode_v1(&creation()).unwrap();
+        unknown[0] = 2;
+        assert!(matches!(
+            SemanticChange::decode(&unknown),
+            Err(Error::UnsupportedChangeFormat(2))
+        ));
+    }
+}
@@ -0,0 +1,211 @@
+use crate::opening_hours::OpeningHours;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComparisonOperator {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+impl ComparisonOperator {
+    fn compare(&self, left: f64, right: f64) -> bool {
+        use ComparisonOperator::*;
+        match self {
+            Lt => left < right,
+            Le => left <= right,
+            Gt => left > right,
+            Ge => left >= right,
+            Eq => (left - right).abs() < f64::EPSILON,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RestrictionCondition {
+    /// A time restriction in the opening_hours syntax, e.g. `Mo-Fr 07:00-19:00`.
+    Time(OpeningHours),
+    /// A vehicle or user property comparison, e.g. `weight>7.5`.
+    Comparison {
+        property: String,
+        operator: ComparisonOperator,
+        value: f64,
+    },
+    /// Anything else, e.g. `wet` or `snow`.
+    Other(String),
+}
+
+/// The circumstances under which a conditional restriction is evaluated.
+#[derive(Default)]
+pub struct ConditionContext {
+    /// The local time at the place.
+    pub time: Option<NaiveDateTime>,
+    /// Properties like the weight, length or number of occupants of the vehicle.
+    pub properties: HashMap<String, f64>,
+    /// Other conditions which currently hold, e.g. `wet`.
+    pub flags: HashSet<String>,
+}
+
+impl RestrictionCondition {
+    /// Returns `None` when the context does not say enough to decide.
+    pub fn evaluate(&self, context: &ConditionContext) -> Option<bool> {
+        use RestrictionCondition::*;
+        match self {
+            Time(hours) => context.time.and_then(|t| hours.is_open_at(t)),
+            Comparison {
+                property,
+                operator,
+                value,
+            } => context
+                .properties
+                .get(property)
+                .map(|v| operator.compare(*v, *value)),
+            Other(flag) => Some(context.flags.contains(flag)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConditionalEntry {
+    pub value: String,
+    pub conditions: Vec<RestrictionCondition>,
+}
+
+impl ConditionalEntry {
+    fn applies_in(&self, context: &ConditionContext) -> bool {
+        self.conditions
+            .iter()
+            .all(|c| c.evaluate(context) == Some(true))
+    }
+}
+
+/// A parsed `*:conditional` tag value, e.g. `no @ (Mo-Fr 07:00-19:00); destination @ (weight>7.5)`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConditionalRestriction {
+    pub raw: String,
+    pub entries: Vec<ConditionalEntry>,
+}
+
+impl ConditionalRestriction {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut entries = vec![];
+        for part in split_top_level(value, ';') {
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+            let (restriction_value, condition) = part
+                .split_once('@')
+                .ok_or_else(|| format!("Missing condition in {}", part))?;
+            let condition = condition.trim();
+            let condition = condition
+                .strip_prefix('(')
+                .and_then(|c| c.strip_suffix(')'))
+                .unwrap_or(condition);
+            let mut conditions = vec![];
+            for single in condition.split(" AND ") {
+                conditions.push(parse_condition(single.trim())?);
+            }
+            entries.push(ConditionalEntry {
+                value: restriction_value.trim().to_string(),
+                conditions,
+            });
+        }
+        if entries.is_empty() {
+            return Err("No conditional entries".to_string());
+        }
+        Ok(Self {
+            raw: value.to_string(),
+            entries,
+        })
+    }
+
+    /// Returns the restriction value valid in the given context, or `None` when no condition applies and the unconditional value should be used instead.
+    pub fn value_in(&self, context: &ConditionContext) -> Option<&str> {
+        // When more conditions apply, the later one wins.
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.applies_in(context))
+            .map(|e| e.value.as_str())
+    }
+}
+
+fn split_top_level(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if c == separator && depth == 0 => {
+                parts.push(&value[start..idx]);
+                start = idx + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn parse_comparison(condition: &str) -> Option<RestrictionCondition> {
+    use ComparisonOperator::*;
+    let operator_start = condition.find(['<', '>', '='])?;
+    let property = condition[..operator_start].trim();
+    if property.is_empty()
+        || !property
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+    {
+        return None;
+    }
+    let rest = &condition[operator_start..];
+    let (operator, rest) = if let Some(rest) = rest.strip_prefix("<=") {
+        (Le, rest)
+    } else if let Some(rest) = rest.strip_prefix(">=") {
+        (Ge, rest)
+    } else if let Some(rest) = rest.strip_prefix('<') {
+        (Lt, rest)
+    } else if let Some(rest) = rest.strip_prefix('>') {
+        (Gt, rest)
+    } else {
+        (Eq, rest.strip_prefix('=')?)
+    };
+    // We ignore the unit, the OSM defaults (tons, meters) are used in practice.
+    let rest = rest.trim();
+    let number_len = rest
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(rest.len());
+    let value = rest[..number_len].parse().ok()?;
+    Some(RestrictionCondition::Comparison {
+        property: property.to_string(),
+        operator,
+        value,
+    })
+}
+
+fn parse_condition(condition: &str) -> Result<RestrictionCondition, String> {
+    if condition.is_empty() {
+        return Err("Empty condition".to_string());
+    }
+    if let Some(comparison) = parse_comparison(condition) {
+        return Ok(comparison);
+    }
+    if let Ok(hours) = OpeningHours::parse(condition) {
+        return Ok(RestrictionCondition::Time(hours));
+    }
+    if condition
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ' ')
+    {
+        Ok(RestrictionCondition::Other(condition.to_string()))
+    } else {
+        Err(format!("Unsupported condition {}", condition))
+    }
+}
@@ -1,5 +1,8 @@
+use crate::area_db::entity_columns_sql;
 use crate::entities_query_condition::FieldCondition;
 use crate::entity_relationship_kind::EntityRelationshipKind;
+use crate::geometry_metrics::GeometryMetric;
+use crate::simplification::SimplificationLevel;
 use rusqlite::types::ToSql;
 use std::f64;
 
@@ -8,6 +11,8 @@ const CHILD_ID_FILTER_SQL: &str =
     "id in (select child_id from entity_relationships where parent_id = :parent_id";
 const PARENT_ID_FILTER_SQL: &str =
     "id in (select parent_id from entity_relationships where child_id = :child_id";
+const CHILD_MEMBER_INDEX_SQL: &str = "(select min(sequence_index) from entity_relationships where parent_id = :parent_id and child_id = entities.id";
+const PARENT_MEMBER_INDEX_SQL: &str = "(select min(sequence_index) from entity_relationships where child_id = :child_id and parent_id = entities.id";
 
 pub struct EntitiesQuery {
     included_discriminators: Vec<String>,
@@ -22,6 +27,9 @@ pub struct EntitiesQuery {
     relationship_kind: Option<EntityRelationshipKind>,
     conditions: Vec<FieldCondition>,
     limit: Option<usize>,
+    ordered_by_member_index: bool,
+    metric_orderings: Vec<(GeometryMetric, bool)>,
+    simplification_level: SimplificationLevel,
 }
 
 impl Default for EntitiesQuery {
@@ -39,6 +47,9 @@ impl Default for EntitiesQuery {
             relationship_kind: None,
             conditions: Vec::new(),
             limit: None,
+            ordered_by_member_index: false,
+            metric_orderings: Vec::new(),
+            simplification_level: SimplificationLevel::Full,
         }
     }
 }
@@ -79,11 +90,32 @@ impl EntitiesQuery {
         self.limit = Some(limit);
     }
 
+    /// Orders the results of a relationship query by the position of the members in their relation, e.g. the stops of a route.
+    pub fn set_ordered_by_member_index(&mut self, ordered: bool) {
+        self.ordered_by_member_index = ordered;
+    }
+
+    /// Orders the results by a geometry metric, after any previously added ordering. The entities without the metric go last.
+    pub fn add_metric_ordering(&mut self, metric: GeometryMetric, descending: bool) {
+        self.metric_orderings.push((metric, descending));
+    }
+
+    /// The level of detail of the returned geometries, the interest rectangle is still tested against the full ones.
+    pub fn set_simplification_level(&mut self, level: SimplificationLevel) {
+        self.simplification_level = level;
+    }
+
     pub fn to_query_sql(&self) -> String {
         let base_query = if self.has_interest_rectangle {
-            "select id, discriminator, AsBinary(geometry) as geometry, data, effective_width from entities, idx_entities_geometry"
+            format!(
+                "select {} from entities, idx_entities_geometry",
+                entity_columns_sql(self.simplification_level)
+            )
         } else {
-            "select id, discriminator, AsBinary(geometry) as geometry, data, effective_width from entities"
+            format!(
+                "select {} from entities",
+                entity_columns_sql(self.simplification_level)
+            )
         };
         let mut condition_fragments = if self.has_interest_rectangle {
             vec![RECTANGLE_CONDITION_SQL.to_string()]
@@ -119,10 +151,34 @@ impl EntitiesQuery {
         for (idx, condition) in self.conditions.iter().enumerate() {
             condition_fragments.push(condition.to_query_fragment(idx));
         }
-        let mut query_sql = base_query.to_string();
+        let mut query_sql = base_query;
         if !condition_fragments.is_empty() {
             query_sql.push_str(&format!(" WHERE {}", condition_fragments.join(" AND ")));
         }
+        let mut ordering_fragments = vec![];
+        if self.ordered_by_member_index {
+            let index_expr = if self.child_id.is_some() {
+                Some(self.prepare_relationship_id_filter(CHILD_MEMBER_INDEX_SQL))
+            } else if self.parent_id.is_some() {
+                Some(self.prepare_relationship_id_filter(PARENT_MEMBER_INDEX_SQL))
+            } else {
+                None
+            };
+            if let Some(index_expr) = index_expr {
+                // The entities which are not members, e.g. the inferred relationships, go last.
+                ordering_fragments.push(format!("{expr} IS NULL, {expr}", expr = index_expr));
+            }
+        }
+        for (metric, descending) in &self.metric_orderings {
+            ordering_fragments.push(format!(
+                "{column} IS NULL, {column}{direction}",
+                column = metric.column_name(),
+                direction = if *descending { " DESC" } else { "" }
+            ));
+        }
+        if !ordering_fragments.is_empty() {
+            query_sql.push_str(&format!(" ORDER BY {}", ordering_fragments.join(", ")));
+        }
         if let Some(limit) = self.limit {
             query_sql.push_str(&format!(" LIMIT {}", limit));
         }
@@ -1,3 +1,4 @@
+use crate::geometry_metrics::GeometryMetric;
 use rusqlite::types::ToSql;
 use std::sync::Arc;
 
@@ -12,6 +13,25 @@ pub enum Condition {
     Gt { value: Arc<dyn ToSql + Send + Sync> },
     Ge { value: Arc<dyn ToSql + Send + Sync> },
     Like { value: Arc<dyn ToSql + Send + Sync> },
+    Contains { value: Arc<dyn ToSql + Send + Sync> },
+    DoesNotContain { value: Arc<dyn ToSql + Send + Sync> },
+}
+
+impl Condition {
+    fn operation_fragment(&self, condition_placeholder_base: &str) -> String {
+        match self {
+            Condition::IsNull => "IS NULL".to_string(),
+            Condition::IsNotNull => "IS NOT NULL".to_string(),
+            // A metric is a single number, so it can only contain itself.
+            Condition::Eq { .. } | Condition::Contains { .. } => format!("= :param{}", condition_placeholder_base),
+            Condition::Neq { .. } | Condition::DoesNotContain { .. } => format!("!= :param{}", condition_placeholder_base),
+            Condition::Lt { .. } => format!("< :param{}", condition_placeholder_base),
+            Condition::Le { .. } => format!("<= :param{}", condition_placeholder_base),
+            Condition::Gt { .. } => format!("> :param{}", condition_placeholder_base),
+            Condition::Ge { .. } => format!(">= :param{}", condition_placeholder_base),
+            Condition::Like { .. } => format!("LIKE :param{}", condition_placeholder_base),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -19,13 +39,19 @@ pub enum FieldCondition {
     Concrete { field: String,
     condition: Condition,
     },
-    Or {left: Box<FieldCondition>, right: Box<FieldCondition>}
+    Or {left: Box<FieldCondition>, right: Box<FieldCondition>},
+    /// A condition on one of the metrics derived from the geometry, instead of on the data.
+    Metric { metric: GeometryMetric, condition: Condition },
 }
 
 impl FieldCondition {
     pub fn new(field: String, condition: Condition) -> Self {
         Self::Concrete { field, condition }
     }
+
+    pub fn on_metric(metric: GeometryMetric, condition: Condition) -> Self {
+        Self::Metric { metric, condition }
+    }
     
     pub fn to_query_fragment(&self, condition_index: usize) -> String {
         self.to_query_fragment_internal(condition_index.to_string())
@@ -34,19 +60,17 @@ impl FieldCondition {
     fn to_query_fragment_internal(&self, condition_placeholder_base: String) -> String {
         match self {
         Self::Concrete { field, condition } => {
+            // Membership tests on list fields must look at the individual array items.
+            match condition {
+                Condition::Contains { .. } => return format!("EXISTS (SELECT 1 FROM json_each(entities.data, '$.{}') WHERE json_each.value = :param{})", field, condition_placeholder_base),
+                Condition::DoesNotContain { .. } => return format!("NOT EXISTS (SELECT 1 FROM json_each(entities.data, '$.{}') WHERE json_each.value = :param{})", field, condition_placeholder_base),
+                _ => {}
+            }
             let field_expr = format!("json_extract(data, '$.{}')", field);
-        let operation = match condition {
-            Condition::IsNull => "IS NULL".to_string(),
-            Condition::IsNotNull => "IS NOT NULL".to_string(),
-            Condition::Eq { .. } => format!("= :param{}", condition_placeholder_base),
-            Condition::Neq { .. } => format!("!= :param{}", condition_placeholder_base),
-            Condition::Lt { .. } => format!("< :param{}", condition_placeholder_base),
-            Condition::Le { .. } => format!("<= :param{}", condition_placeholder_base),
-            Condition::Gt { .. } => format!("> :param{}", condition_placeholder_base),
-            Condition::Ge { .. } => format!(">= :param{}", condition_placeholder_base),
-            Condition::Like { .. } => format!("LIKE :param{}", condition_placeholder_base),
-        };
-        format!("{} {}", field_expr, operation)
+        format!("{} {}", field_expr, condition.operation_fragment(&condition_placeholder_base))
+    },
+    Self::Metric { metric, condition } => {
+        format!("entities.{} {}", metric.column_name(), condition.operation_fragment(&condition_placeholder_base))
     },
     Self::Or {left, right } => {
         format!("({} OR {})", left.to_query_fragment_internal(format!("{}l", condition_placeholder_base)), right.to_query_fragment_internal(format!("{}r", condition_placeholder_base)))
@@ -60,7 +84,7 @@ impl FieldCondition {
 
     fn to_param_values_internal(&self, condition_placeholder_base: String) -> Option<Vec<(String, &dyn ToSql)>> {
         match self {
-            Self::Concrete {condition, .. } => {
+            Self::Concrete {condition, .. } | Self::Metric { condition, .. } => {
         match &condition {
             Condition::Eq { value }
             | Condition::Neq { value }
@@ -68,7 +92,9 @@ impl FieldCondition {
             | Condition::Le { value }
             | Condition::Gt { value }
             | Condition::Ge { value }
-            | Condition::Like { value } => Some(vec![(format!(":param{}", condition_placeholder_base), value.as_ref())]),
+            | Condition::Like { value }
+            | Condition::Contains { value }
+            | Condition::DoesNotContain { value } => Some(vec![(format!(":param{}", condition_placeholder_base), value.as_ref())]),
             Condition::IsNull | Condition::IsNotNull => None,
         }}
         Self::Or {left, right } => {
@@ -1,4 +1,9 @@
+use crate::access::{self, TravelMode};
+use crate::conditional::{ConditionContext, ConditionalRestriction};
+use crate::geometry_metrics::GeometryMetrics;
+use crate::opening_hours::OpeningHours;
 use crate::semantic_change::EntryChange;
+use crate::translation::{conversions, record::TranslationRecord};
 use log::warn;
 use osm_api::SmolStr;
 use serde_json::{Map, Value};
@@ -12,6 +17,8 @@ pub struct Entity {
     pub data: String,
     pub parsed_data: Option<Value>,
     pub effective_width: Option<f64>,
+    /// Computed by the database, so they're unknown until the entity is stored.
+    pub metrics: GeometryMetrics,
 }
 
 impl Entity {
@@ -40,6 +47,39 @@ impl Entity {
         obj.get(key).unwrap_or(&Value::Null)
     }
 
+    pub fn opening_hours_of(&mut self, key: &str) -> Option<OpeningHours> {
+        // The values we could not parse, or not yet migrated by oesc change-field-type, are the raw strings.
+        match self.value_of_field(key) {
+            Value::String(raw) => OpeningHours::parse(raw).ok(),
+            value => serde_json::from_value(value.clone()).ok(),
+        }
+    }
+
+    pub fn conditional_restriction_of(&mut self, key: &str) -> Option<ConditionalRestriction> {
+        // The databases not yet migrated by oesc change-field-type keep the raw values.
+        match self.value_of_field(key) {
+            Value::Null => None,
+            Value::String(raw) => ConditionalRestriction::parse(raw).ok(),
+            value => serde_json::from_value(value.clone()).ok(),
+        }
+    }
+
+    /// The speed limit in km/h valid in the given context, taking maxspeed:conditional into account.
+    pub fn maxspeed_in(&mut self, context: &ConditionContext) -> Option<f64> {
+        let conditional = self
+            .conditional_restriction_of("maxspeed_conditional")
+            .and_then(|r| {
+                let value = r.value_in(context)?;
+                conversions::convert_field_value(value, "kmh", &mut TranslationRecord::new())
+            })
+            .and_then(|v| v.as_f64());
+        conditional.or_else(|| self.value_of_field("maxspeed").as_f64())
+    }
+
+    pub fn is_usable_by(&mut self, mode: TravelMode, context: &ConditionContext) -> Option<bool> {
+        access::usability_for(self, mode, context)
+    }
+
     pub fn defined_field_names(&mut self) -> Vec<&String> {
         if self.parsed_data.is_none() {
             self.parsed_data =
@@ -54,6 +94,19 @@ impl Entity {
             .collect()
     }
 
+    /// For a route, returns the stops, or platforms, which follow the given one, in the order the line serves them.
+    pub fn next_stops(&mut self, stop_id: &str) -> Vec<String> {
+        for field in &["stops", "platforms"] {
+            if let Value::Array(ids) = self.value_of_field(field) {
+                let ids: Vec<&str> = ids.iter().filter_map(|id| id.as_str()).collect();
+                if let Some(pos) = ids.iter().position(|id| *id == stop_id) {
+                    return ids[pos + 1..].iter().map(|id| id.to_string()).collect();
+                }
+            }
+        }
+        vec![]
+    }
+
     pub fn apply_property_changes(&mut self, property_changes: &[EntryChange]) {
         for change in property_changes {
             if let EntryChange::Update { key, new_value, .. } = change {
@@ -1,3 +1,4 @@
+use crate::catalog;
 use crate::file_finder;
 use indexmap::IndexMap;
 use once_cell::sync::Lazy;
@@ -80,6 +81,24 @@ impl EntityMetadata {
     pub fn parent_metadata(&self) -> Option<Self> {
         EntityMetadata::for_discriminator(&self.inherits.clone()?)
     }
+
+    /// The discriminators of this entity and of all the entities inheriting from it, directly or not.
+    pub fn discriminators_with_descendants(&self) -> Vec<String> {
+        all_known_discriminators()
+            .into_iter()
+            .filter(|discriminator| {
+                let mut current = EntityMetadata::for_discriminator(discriminator);
+                while let Some(metadata) = current {
+                    if metadata.discriminator == self.discriminator {
+                        return true;
+                    }
+                    current = metadata.parent_metadata();
+                }
+                false
+            })
+            .cloned()
+            .collect()
+    }
     pub fn all_fields(&self) -> IndexMap<String, Field> {
         let mut ret = self.fields.clone();
         if let Some(parent) = self.parent_metadata() {
@@ -87,6 +106,55 @@ impl EntityMetadata {
         }
         ret
     }
+
+    pub fn localized_name(&self, language: &str) -> String {
+        catalog::gettext(&format_class_name(&self.discriminator), language)
+    }
+
+    pub fn localized_field_name(&self, field: &str, language: &str) -> String {
+        catalog::gettext(&underscored_to_words(field), language)
+    }
+}
+
+/// Mirrors `format_class_name` of the Python client, so the message ids match the extracted ones.
+fn format_class_name(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut ret = String::with_capacity(name.len() + 8);
+    let mut idx = 0;
+    while idx < chars.len() {
+        if idx + 2 < chars.len()
+            && (chars[idx].is_ascii_lowercase() || chars[idx].is_ascii_digit())
+            && chars[idx + 1].is_ascii_uppercase()
+            && (chars[idx + 2].is_ascii_lowercase() || chars[idx + 2].is_ascii_digit())
+        {
+            ret.push(chars[idx]);
+            ret.push(' ');
+            ret.push(chars[idx + 1].to_ascii_lowercase());
+            ret.push(chars[idx + 2]);
+            idx += 3;
+        } else {
+            ret.push(chars[idx]);
+            idx += 1;
+        }
+    }
+    ret
+}
+
+/// Mirrors `underscored_to_words` of the Python client.
+fn underscored_to_words(underscored: &str) -> String {
+    let underscored = if underscored.is_empty() {
+        "unknown"
+    } else {
+        underscored
+    };
+    let words = underscored.replace('_', " ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) if first.is_alphanumeric() || first == '_' => {
+            first.to_uppercase().chain(chars).collect()
+        }
+        _ => words,
+    }
 }
 
 pub struct Enum {
@@ -120,4 +188,9 @@ impl Enum {
     pub fn name_for_value(&self, value: i32) -> Option<&'static String> {
         self.reverse_members.get(&value).copied()
     }
+
+    pub fn localized_name_for_value(&self, value: i32, language: &str) -> Option<String> {
+        self.name_for_value(value)
+            .map(|name| catalog::gettext(&underscored_to_words(name), language))
+    }
 }
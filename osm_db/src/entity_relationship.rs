@@ -7,6 +7,10 @@ pub struct EntityRelationship {
     pub parent_id: SmolStr,
     pub child_id: SmolStr,
     pub kind: EntityRelationshipKind,
+    /// The role of the child in the parent relation, only known for OSM relation members.
+    pub role: Option<SmolStr>,
+    /// The position of the child among the members of the parent relation.
+    pub sequence_index: Option<u32>,
 }
 
 impl EntityRelationship {
@@ -15,6 +19,17 @@ impl EntityRelationship {
             parent_id: SmolStr::new_inline(parent_id),
             child_id: SmolStr::new_inline(child_id),
             kind,
+            role: None,
+            sequence_index: None,
+        }
+    }
+
+    pub fn rooted(&self) -> RootedEntityRelationship {
+        RootedEntityRelationship {
+            child_id: self.child_id.to_string(),
+            kind: self.kind,
+            role: self.role.as_ref().map(|r| r.to_string()),
+            sequence_index: self.sequence_index,
         }
     }
 }
@@ -23,6 +38,10 @@ impl EntityRelationship {
 pub struct RootedEntityRelationship {
     pub child_id: String,
     pub kind: EntityRelationshipKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence_index: Option<u32>,
 }
 
 impl RootedEntityRelationship {
@@ -30,6 +49,28 @@ impl RootedEntityRelationship {
         RootedEntityRelationship {
             child_id: child_id.to_string(),
             kind,
+            role: None,
+            sequence_index: None,
+        }
+    }
+
+    /// A relationship to the member of an OSM relation, or to a node of a way, at the given position.
+    pub fn member(child_id: &str, role: Option<&str>, sequence_index: u32) -> Self {
+        RootedEntityRelationship {
+            child_id: child_id.to_string(),
+            kind: EntityRelationshipKind::OSMChild,
+            role: role.filter(|r| !r.is_empty()).map(|r| r.to_string()),
+            sequence_index: Some(sequence_index),
+        }
+    }
+
+    pub fn with_parent(&self, parent_id: &str) -> EntityRelationship {
+        EntityRelationship {
+            parent_id: SmolStr::new_inline(parent_id),
+            child_id: SmolStr::new_inline(&self.child_id),
+            kind: self.kind,
+            role: self.role.as_deref().map(SmolStr::new),
+            sequence_index: self.sequence_index,
         }
     }
 }
@@ -10,6 +10,20 @@ pub enum Error {
     SerdeError(#[from] serde_json::error::Error),
     #[error("Zstd error: {0}")]
     ZstdError(#[from] zstd_util::Error),
+    #[error("Malformed message catalog: {0}")]
+    MalformedCatalog(String),
     #[error("Attempted an application of a change type which should be never applied")]
     IllegalChangeType,
+    #[error("Can not guess the export format of {0}, use the .geojson or .gpkg extension")]
+    UnsupportedExportFormat(String),
+    #[error("Invalid overlay: {0}")]
+    InvalidOverlay(String),
+    #[error("The overlay namespace {0} must start with a letter other than n, w and r and contain only letters, digits, underscores and dashes")]
+    InvalidOverlayNamespace(String),
+    #[error("Bincode error: {0}")]
+    BincodeError(#[from] bincode::Error),
+    #[error("Base64 decoding error: {0}")]
+    Base64Error(#[from] base64::DecodeError),
+    #[error("Unsupported semantic change format version {0}, a newer version is needed to read it")]
+    UnsupportedChangeFormat(u8),
 }
@@ -0,0 +1,206 @@
+//! Exports of area databases, or of the results of an entities query, into formats which GIS tools like QGIS understand.
+use crate::area_db::{init_extensions, AreaDatabase};
+use crate::entities_query::EntitiesQuery;
+use crate::entity_relationship::RootedEntityRelationship;
+use crate::{Error, Result};
+use rusqlite::{params, Connection, Row};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+const RELATIONSHIPS_OF_PARENT_SQL: &str = "SELECT child_id, kind, role, sequence_index FROM entity_relationships WHERE parent_id = ? ORDER BY kind, sequence_index";
+const CREATE_GEOPACKAGE_TABLES_SQL: &str = "SELECT gpkgCreateBaseTables(); SELECT gpkgInsertEpsgSRID(4326);
+CREATE TABLE entities (fid INTEGER PRIMARY KEY AUTOINCREMENT, id TEXT NOT NULL UNIQUE, discriminator TEXT NOT NULL, effective_width REAL, data TEXT NOT NULL, geometry BLOB);
+INSERT INTO gpkg_contents (table_name, data_type, identifier, srs_id) VALUES ('entities', 'features', 'entities', 4326);
+INSERT INTO gpkg_geometry_columns (table_name, column_name, geometry_type_name, srs_id, z, m) VALUES ('entities', 'geometry', 'GEOMETRY', 4326, 0, 0);
+CREATE TABLE entity_relationships (parent_id TEXT NOT NULL, child_id TEXT NOT NULL, kind TEXT NOT NULL, role TEXT, sequence_index INTEGER);
+INSERT INTO gpkg_contents (table_name, data_type, identifier) VALUES ('entity_relationships', 'attributes', 'entity_relationships');";
+const INSERT_GEOPACKAGE_ENTITY_SQL: &str = "INSERT INTO entities (id, discriminator, effective_width, data, geometry) VALUES (?, ?, ?, ?, ?)";
+const INSERT_GEOPACKAGE_RELATIONSHIP_SQL: &str = "INSERT INTO entity_relationships (parent_id, child_id, kind, role, sequence_index) VALUES (?, ?, ?, ?, ?)";
+
+/// The columns of the exported entity, with the geometry converted by SpatiaLite to the given format.
+fn export_query_sql(query: &EntitiesQuery, geometry_conversion: &str) -> String {
+    format!(
+        "SELECT q.id, q.discriminator, q.effective_width, q.data, {}(GeomFromWKB(q.geometry, 4326)) FROM ({}) AS q",
+        geometry_conversion,
+        query.to_query_sql()
+    )
+}
+
+fn child_relationships(
+    db: &AreaDatabase,
+    parent_id: &str,
+) -> Result<Vec<RootedEntityRelationship>> {
+    let mut stmt = db.conn.prepare_cached(RELATIONSHIPS_OF_PARENT_SQL)?;
+    let relationships = stmt
+        .query_and_then(
+            params![parent_id],
+            |row| -> Result<RootedEntityRelationship> {
+                let mut relationship =
+                    RootedEntityRelationship::new(&row.get::<_, String>(0)?, row.get(1)?);
+                relationship.role = row.get(2)?;
+                relationship.sequence_index = row.get(3)?;
+                Ok(relationship)
+            },
+        )?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(relationships)
+}
+
+/// Copies the entries of the nested maps to the top level, joining the keys by slashes like the data changes do, e.g. `address/street`.
+fn flatten_into(prefix: &str, value: Value, properties: &mut Map<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}/{}", prefix, key)
+                };
+                flatten_into(&key, value, properties);
+            }
+        }
+        value => {
+            properties.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+fn geojson_feature(row: &Row, relationships: Vec<RootedEntityRelationship>) -> Result<Value> {
+    let id: String = row.get(0)?;
+    let mut properties = Map::new();
+    properties.insert("discriminator".to_string(), Value::String(row.get(1)?));
+    properties.insert(
+        "effective_width".to_string(),
+        json!(row.get::<_, Option<f64>>(2)?),
+    );
+    flatten_into(
+        "",
+        serde_json::from_str(&row.get::<_, String>(3)?)?,
+        &mut properties,
+    );
+    let geometry = match row.get::<_, Option<String>>(4)? {
+        Some(geometry) => serde_json::from_str(&geometry)?,
+        None => Value::Null,
+    };
+    // A foreign member, the references to the child features should not be mixed with the tag derived properties.
+    Ok(json!({
+        "type": "Feature",
+        "id": id,
+        "geometry": geometry,
+        "properties": properties,
+        "relationships": relationships,
+    }))
+}
+
+/// Streams the entities matching the query as a GeoJSON feature collection, returning the number of the written features.
+///
+/// The entity data are flattened into the feature properties, the child relationships of every entity are in its `relationships` member.
+pub fn write_geojson<W: Write>(
+    db: &AreaDatabase,
+    query: &EntitiesQuery,
+    mut out: W,
+) -> Result<usize> {
+    let mut stmt = db.conn.prepare(&export_query_sql(query, "AsGeoJSON"))?;
+    let orig_params = query.to_query_params();
+    let params: Vec<_> = orig_params
+        .iter()
+        .map(|(name, value)| (name.as_str(), *value))
+        .collect();
+    let mut rows = stmt.query(params.as_slice())?;
+    let mut count = 0;
+    out.write_all(b"{\"type\": \"FeatureCollection\", \"features\": [\n")?;
+    while let Some(row) = rows.next()? {
+        let relationships = child_relationships(db, &row.get::<_, String>(0)?)?;
+        if count > 0 {
+            out.write_all(b",\n")?;
+        }
+        serde_json::to_writer(&mut out, &geojson_feature(row, relationships)?)?;
+        count += 1;
+    }
+    out.write_all(b"\n]}\n")?;
+    out.flush()?;
+    Ok(count)
+}
+
+/// Writes the entities matching the query into a new GeoPackage, returning the number of the written entities.
+///
+/// The entities are in the `entities` layer with their data as JSON, their relationships in the `entity_relationships` attribute table.
+pub fn write_geopackage(db: &AreaDatabase, query: &EntitiesQuery, target: &Path) -> Result<usize> {
+    if target.exists() {
+        fs::remove_file(target)?;
+    }
+    let mut package = Connection::open(target)?;
+    init_extensions(&package)?;
+    package.execute_batch(CREATE_GEOPACKAGE_TABLES_SQL)?;
+    let transaction = package.transaction()?;
+    let mut insert_entity_stmt = transaction.prepare(INSERT_GEOPACKAGE_ENTITY_SQL)?;
+    let mut insert_relationship_stmt = transaction.prepare(INSERT_GEOPACKAGE_RELATIONSHIP_SQL)?;
+    let mut stmt = db.conn.prepare(&export_query_sql(query, "AsGPB"))?;
+    let orig_params = query.to_query_params();
+    let params: Vec<_> = orig_params
+        .iter()
+        .map(|(name, value)| (name.as_str(), *value))
+        .collect();
+    let mut rows = stmt.query(params.as_slice())?;
+    let mut count = 0;
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        insert_entity_stmt.execute(params![
+            id,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<f64>>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<Vec<u8>>>(4)?,
+        ])?;
+        for relationship in child_relationships(db, &id)? {
+            insert_relationship_stmt.execute(params![
+                id,
+                relationship.child_id,
+                format!("{:?}", relationship.kind),
+                relationship.role,
+                relationship.sequence_index,
+            ])?;
+        }
+        count += 1;
+    }
+    drop(insert_entity_stmt);
+    drop(insert_relationship_stmt);
+    transaction.commit()?;
+    // Let GIS tools zoom to the exported data.
+    package.execute_batch("UPDATE gpkg_contents SET min_x = (SELECT min(ST_MinX(GeomFromGPB(geometry))) FROM entities), min_y = (SELECT min(ST_MinY(GeomFromGPB(geometry))) FROM entities), max_x = (SELECT max(ST_MaxX(GeomFromGPB(geometry))) FROM entities), max_y = (SELECT max(ST_MaxY(GeomFromGPB(geometry))) FROM entities) WHERE table_name = 'entities'")?;
+    Ok(count)
+}
+
+/// The export format, guessed from the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    GeoJson,
+    GeoPackage,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "geojson" | "json" => Some(ExportFormat::GeoJson),
+            "gpkg" => Some(ExportFormat::GeoPackage),
+            _ => None,
+        }
+    }
+}
+
+/// Exports the entities matching the query into the target file, in the format given by its extension.
+pub fn export(db: &AreaDatabase, query: &EntitiesQuery, target: &Path) -> Result<usize> {
+    match ExportFormat::from_path(target) {
+        Some(ExportFormat::GeoJson) => write_geojson(
+            db,
+            query,
+            std::io::BufWriter::new(fs::File::create(target)?),
+        ),
+        Some(ExportFormat::GeoPackage) => write_geopackage(db, query, target),
+        None => Err(Error::UnsupportedExportFormat(
+            target.to_string_lossy().to_string(),
+        )),
+    }
+}
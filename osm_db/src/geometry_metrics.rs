@@ -0,0 +1,103 @@
+//! Metrics derived from the geometry of an entity, computed by SpatiaLite when the entity is stored, so they can be queried and sorted on.
+use rusqlite::Row;
+use std::str::FromStr;
+
+// The metrics are computed on the ellipsoid, the centroid is the only one which uses the plain coordinates.
+const METRIC_ASSIGNMENTS_SQL: &str = "geodesic_length = CASE WHEN ST_Dimension(geometry) > 0 THEN ST_Length(geometry, 1) END, geodesic_area = CASE WHEN ST_Dimension(geometry) = 2 THEN ST_Area(geometry, 1) END, centroid_lon = ST_X(ST_Centroid(geometry)), centroid_lat = ST_Y(ST_Centroid(geometry)), start_bearing = CASE WHEN GeometryType(geometry) = 'LINESTRING' THEN Degrees(ST_Azimuth(ST_StartPoint(geometry), ST_PointN(geometry, 2))) END, end_bearing = CASE WHEN GeometryType(geometry) = 'LINESTRING' THEN Degrees(ST_Azimuth(ST_PointN(geometry, ST_NumPoints(geometry) - 1), ST_EndPoint(geometry))) END";
+// Area databases created before the metrics existed lack these columns.
+pub(crate) const ADD_METRIC_COLUMNS_SQL: &str = "ALTER TABLE entities ADD COLUMN geodesic_length FLOAT; ALTER TABLE entities ADD COLUMN geodesic_area FLOAT; ALTER TABLE entities ADD COLUMN centroid_lon FLOAT; ALTER TABLE entities ADD COLUMN centroid_lat FLOAT; ALTER TABLE entities ADD COLUMN start_bearing FLOAT; ALTER TABLE entities ADD COLUMN end_bearing FLOAT;";
+
+pub(crate) fn update_metrics_of_entity_sql() -> String {
+    format!(
+        "UPDATE entities SET {} WHERE id = ?",
+        METRIC_ASSIGNMENTS_SQL
+    )
+}
+
+/// Every non-empty geometry has a centroid, so a missing one means that the metrics were not computed yet.
+pub(crate) fn update_missing_metrics_sql() -> String {
+    format!(
+        "UPDATE entities SET {} WHERE centroid_lon IS NULL",
+        METRIC_ASSIGNMENTS_SQL
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GeometryMetric {
+    Length,
+    Area,
+    CentroidLon,
+    CentroidLat,
+    StartBearing,
+    EndBearing,
+}
+
+impl GeometryMetric {
+    pub fn column_name(&self) -> &'static str {
+        use GeometryMetric::*;
+        match self {
+            Length => "geodesic_length",
+            Area => "geodesic_area",
+            CentroidLon => "centroid_lon",
+            CentroidLat => "centroid_lat",
+            StartBearing => "start_bearing",
+            EndBearing => "end_bearing",
+        }
+    }
+}
+
+impl FromStr for GeometryMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use GeometryMetric::*;
+        match s {
+            "length" | "geodesic_length" => Ok(Length),
+            "area" | "geodesic_area" => Ok(Area),
+            "centroid_lon" => Ok(CentroidLon),
+            "centroid_lat" => Ok(CentroidLat),
+            "start_bearing" => Ok(StartBearing),
+            "end_bearing" => Ok(EndBearing),
+            _ => Err(format!("Unknown geometry metric {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GeometryMetrics {
+    /// In meters, for areas the length of their boundary.
+    pub length: Option<f64>,
+    /// In square meters, only for areas.
+    pub area: Option<f64>,
+    pub centroid_lon: Option<f64>,
+    pub centroid_lat: Option<f64>,
+    /// In degrees clockwise from the north, only for simple lines.
+    pub start_bearing: Option<f64>,
+    pub end_bearing: Option<f64>,
+}
+
+impl GeometryMetrics {
+    pub fn get(&self, metric: GeometryMetric) -> Option<f64> {
+        use GeometryMetric::*;
+        match metric {
+            Length => self.length,
+            Area => self.area,
+            CentroidLon => self.centroid_lon,
+            CentroidLat => self.centroid_lat,
+            StartBearing => self.start_bearing,
+            EndBearing => self.end_bearing,
+        }
+    }
+
+    /// Reads the metrics from six consecutive row columns, in the order of their declaration, starting at the given index.
+    pub(crate) fn from_row(row: &Row, first_column: usize) -> rusqlite::Result<Self> {
+        Ok(GeometryMetrics {
+            length: row.get(first_column)?,
+            area: row.get(first_column + 1)?,
+            centroid_lon: row.get(first_column + 2)?,
+            centroid_lat: row.get(first_column + 3)?,
+            start_bearing: row.get(first_column + 4)?,
+            end_bearing: row.get(first_column + 5)?,
+        })
+    }
+}
@@ -3,9 +3,19 @@ CREATE TABLE entities (
 	id VARCHAR(16) PRIMARY KEY,
 	discriminator VARCHAR(64) NOT NULL,
 	data TEXT NOT NULL,
-	effective_width FLOAT);
+	effective_width FLOAT,
+	geodesic_length FLOAT,
+	geodesic_area FLOAT,
+	centroid_lon FLOAT,
+	centroid_lat FLOAT,
+	start_bearing FLOAT,
+	end_bearing FLOAT,
+	geometry_fine BLOB,
+	geometry_medium BLOB,
+	geometry_coarse BLOB);
 SELECT AddGeometryColumn("entities", "geometry", 4326, "GEOMETRY", 2, 1);
 SELECT CreateSpatialIndex('entities', 'geometry');
 CREATE INDEX idx_entity_by_name on entities(json_extract(data, "$.name"));
 CREATE INDEX idx_entity_by_name_lower on entities(lower(json_extract(data, "$.name")));
-CREATE TABLE entity_relationships (parent_id VARCHAR(16) REFERENCES entities(id) ON DELETE CASCADE, child_id VARCHAR(16) REFERENCES entities(id) ON DELETE CASCADE, kind INTEGER CHECK (KIND in (0, 1, 2)), PRIMARY KEY (parent_id, child_id, kind));
\ No newline at end of file
+CREATE TABLE entity_relationships (parent_id VARCHAR(16) REFERENCES entities(id) ON DELETE CASCADE, child_id VARCHAR(16) REFERENCES entities(id) ON DELETE CASCADE, kind INTEGER CHECK (KIND in (0, 1, 2)), role TEXT, sequence_index INTEGER);
+CREATE UNIQUE INDEX idx_entity_relationship_key ON entity_relationships (parent_id, child_id, kind, coalesce(sequence_index, -1));
\ No newline at end of file
@@ -1,4 +1,9 @@
+pub mod access;
 pub mod area_db;
+pub mod catalog;
+pub mod change_coalescing;
+mod change_encoding;
+pub mod conditional;
 pub mod entities_query;
 pub mod entities_query_condition;
 mod entities_query_executor;
@@ -7,9 +12,13 @@ pub mod entity_metadata;
 pub mod entity_relationship;
 pub mod entity_relationship_kind;
 mod error;
+pub mod export;
 mod file_finder;
+pub mod geometry_metrics;
+pub mod opening_hours;
 pub mod relationship_inference;
 pub mod semantic_change;
+pub mod simplification;
 pub mod translation;
 pub use crate::area_db::AreaDatabase;
 pub use crate::error::Error;
@@ -0,0 +1,517 @@
+use crate::catalog;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+// How far to look for the next state change, rules restricted to some months may need almost a year.
+const NEXT_CHANGE_SEARCH_DAYS: i64 = 366;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuleState {
+    Open,
+    Closed,
+    Unknown,
+}
+
+/// A span of minutes after midnight, the end may be past midnight, e.g. for `22:00-02:00`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeSpan {
+    pub start: u16,
+    pub end: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Rule {
+    /// Inclusive ranges of months, January is 1, the range can wrap around the year end.
+    pub months: Vec<(u8, u8)>,
+    /// Inclusive ranges of weekdays, Monday is 0, the range can wrap around the week end.
+    pub weekdays: Vec<(u8, u8)>,
+    pub public_holidays: bool,
+    pub school_holidays: bool,
+    pub times: Vec<TimeSpan>,
+    pub state: RuleState,
+    pub comment: Option<String>,
+}
+
+/// A parsed OSM `opening_hours` value, the subset we support consists of month and weekday selectors, time spans, `24/7` and the state modifiers.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpeningHours {
+    pub raw: String,
+    pub rules: Vec<Rule>,
+}
+
+impl OpeningHours {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut rules = vec![];
+        for rule_str in value.split([';', '|']).flat_map(split_additional_rules) {
+            let rule_str = rule_str.trim();
+            if rule_str.is_empty() {
+                continue;
+            }
+            rules.push(parse_rule(rule_str)?);
+        }
+        if rules.is_empty() {
+            return Err("No rules".to_string());
+        }
+        Ok(Self {
+            raw: value.to_string(),
+            rules,
+        })
+    }
+
+    fn rule_for(&self, date: NaiveDate) -> Option<&Rule> {
+        // Later rules override the earlier ones for the days they select.
+        self.rules.iter().rev().find(|r| r.applies_to(date))
+    }
+
+    pub fn state_at(&self, time: NaiveDateTime) -> RuleState {
+        let minute = (time.hour() * 60 + time.minute()) as u16;
+        if let Some(previous) = self.rule_for(time.date() - Duration::days(1)) {
+            for span in &previous.times {
+                if span.end > MINUTES_PER_DAY && minute < span.end - MINUTES_PER_DAY {
+                    return previous.state;
+                }
+            }
+        }
+        match self.rule_for(time.date()) {
+            None => RuleState::Closed,
+            Some(rule) if rule.times.is_empty() => rule.state,
+            Some(rule) => {
+                if rule
+                    .times
+                    .iter()
+                    .any(|s| s.start <= minute && minute < s.end.min(MINUTES_PER_DAY))
+                {
+                    rule.state
+                } else {
+                    RuleState::Closed
+                }
+            }
+        }
+    }
+
+    /// Returns `None` when the state at the given time can not be determined.
+    pub fn is_open_at(&self, time: NaiveDateTime) -> Option<bool> {
+        match self.state_at(time) {
+            RuleState::Open => Some(true),
+            RuleState::Closed => Some(false),
+            RuleState::Unknown => None,
+        }
+    }
+
+    pub fn next_change_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let current = self.state_at(time);
+        for day_offset in 0..NEXT_CHANGE_SEARCH_DAYS {
+            let date = time.date() + Duration::days(day_offset);
+            let mut candidates = vec![0];
+            if let Some(rule) = self.rule_for(date) {
+                for span in &rule.times {
+                    candidates.push(span.start);
+                    candidates.push(span.end);
+                }
+            }
+            if let Some(previous) = self.rule_for(date - Duration::days(1)) {
+                for span in &previous.times {
+                    if span.end > MINUTES_PER_DAY {
+                        candidates.push(span.end - MINUTES_PER_DAY);
+                    }
+                }
+            }
+            candidates.sort_unstable();
+            candidates.dedup();
+            for minute in candidates {
+                if minute >= MINUTES_PER_DAY {
+                    continue;
+                }
+                let candidate = date.and_time(
+                    NaiveTime::from_hms_opt((minute / 60).into(), (minute % 60).into(), 0)
+                        .expect("Minute out of range"),
+                );
+                if candidate > time && self.state_at(candidate) != current {
+                    return Some(candidate);
+                }
+            }
+        }
+        None
+    }
+
+    /// A human readable summary of the rules, with the day and month names translated for the given language.
+    pub fn weekly_summary(&self, language: &str) -> String {
+        self.rules
+            .iter()
+            .map(|r| r.describe(language))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+fn range_contains(range: &(u8, u8), value: u8) -> bool {
+    if range.0 <= range.1 {
+        range.0 <= value && value <= range.1
+    } else {
+        value >= range.0 || value <= range.1
+    }
+}
+
+impl Rule {
+    fn applies_to(&self, date: NaiveDate) -> bool {
+        let month = date.month() as u8;
+        if !self.months.is_empty() && !self.months.iter().any(|r| range_contains(r, month)) {
+            return false;
+        }
+        if self.weekdays.is_empty() {
+            // We don't know when the holidays are, so a holidays only rule never applies.
+            return !(self.public_holidays || self.school_holidays);
+        }
+        let weekday = date.weekday().num_days_from_monday() as u8;
+        self.weekdays.iter().any(|r| range_contains(r, weekday))
+    }
+
+    fn describe(&self, language: &str) -> String {
+        let mut parts = vec![];
+        if !self.months.is_empty() {
+            parts.push(describe_ranges(&self.months, &MONTH_NAMES, 1, language));
+        }
+        let mut days = vec![];
+        if !self.weekdays.is_empty() {
+            days.push(describe_ranges(&self.weekdays, &WEEKDAY_NAMES, 0, language));
+        }
+        if self.public_holidays {
+            days.push(catalog::gettext("public holidays", language));
+        }
+        if self.school_holidays {
+            days.push(catalog::gettext("school holidays", language));
+        }
+        if days.is_empty() {
+            days.push(catalog::gettext("every day", language));
+        }
+        parts.push(days.join(", "));
+        let mut ret = parts.join(" ");
+        ret.push_str(": ");
+        if self.times.is_empty() {
+            ret.push_str(&catalog::gettext(
+                match self.state {
+                    RuleState::Open => "open",
+                    RuleState::Closed => "closed",
+                    RuleState::Unknown => "unknown",
+                },
+                language,
+            ));
+        } else {
+            ret.push_str(
+                &self
+                    .times
+                    .iter()
+                    .map(|s| format!("{}–{}", format_minute(s.start), format_minute(s.end)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+            if self.state == RuleState::Unknown {
+                ret.push_str(&format!(" ({})", catalog::gettext("unknown", language)));
+            }
+        }
+        if let Some(comment) = &self.comment {
+            ret.push_str(&format!(" ({})", comment));
+        }
+        ret
+    }
+}
+
+fn describe_ranges(ranges: &[(u8, u8)], names: &[&str], base: u8, language: &str) -> String {
+    let translate = |idx: u8| catalog::gettext(names[(idx - base) as usize], language);
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                translate(*start)
+            } else {
+                format!(
+                    "{} {} {}",
+                    translate(*start),
+                    catalog::gettext("to", language),
+                    translate(*end)
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_minute(minute: u16) -> String {
+    let minute = if minute > MINUTES_PER_DAY {
+        minute - MINUTES_PER_DAY
+    } else {
+        minute
+    };
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+/// Splits the rules separated by commas, e.g. `Mo-Fr 08:00-12:00, Sa 09:00-11:00`, they are treated like the rules separated by semicolons.
+///
+/// A comma separates rules only after a time or a comment and before something else than a time, otherwise it separates the items of a list, e.g. `Mo,We` or `08:00-12:00,13:00-17:00`.
+fn split_additional_rules(rules: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    for (idx, _) in rules.match_indices(',') {
+        let before = rules[start..idx].trim_end();
+        let after = rules[idx + 1..].trim_start();
+        let in_comment = rules[..idx].matches('"').count() % 2 == 1;
+        if !in_comment
+            && before.ends_with(|c: char| c.is_ascii_digit() || c == '+' || c == '"')
+            && !after.starts_with(|c: char| c.is_ascii_digit())
+        {
+            parts.push(&rules[start..idx]);
+            start = idx + 1;
+        }
+    }
+    parts.push(&rules[start..]);
+    parts
+}
+
+fn parse_rule(rule_str: &str) -> Result<Rule, String> {
+    let mut rule = Rule {
+        months: vec![],
+        weekdays: vec![],
+        public_holidays: false,
+        school_holidays: false,
+        times: vec![],
+        state: RuleState::Open,
+        comment: None,
+    };
+    let mut rest = rule_str;
+    if let Some(quote_start) = rest.find('"') {
+        let comment = rest[quote_start + 1..]
+            .strip_suffix('"')
+            .ok_or_else(|| format!("Unterminated comment in {}", rule_str))?;
+        rule.comment = Some(comment.to_string());
+        rest = &rest[..quote_start];
+    }
+    // Allow spaces after the list separators, e.g. `Mo-Fr, Su`.
+    let normalized = rest.replace(", ", ",");
+    for token in normalized.split_whitespace() {
+        match token {
+            "24/7" => rule.times.push(TimeSpan {
+                start: 0,
+                end: MINUTES_PER_DAY,
+            }),
+            "open" => rule.state = RuleState::Open,
+            "closed" | "off" => rule.state = RuleState::Closed,
+            "unknown" => rule.state = RuleState::Unknown,
+            _ if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                for span in token.split(',') {
+                    rule.times.push(parse_time_span(span)?);
+                }
+            }
+            _ if MONTHS.iter().any(|m| token.starts_with(m)) => {
+                for range in token.split(',') {
+                    rule.months.push(parse_range(range, &MONTHS, 1)?);
+                }
+            }
+            _ => {
+                for range in token.split(',') {
+                    match range {
+                        "PH" => rule.public_holidays = true,
+                        "SH" => rule.school_holidays = true,
+                        _ => rule.weekdays.push(parse_range(range, &WEEKDAYS, 0)?),
+                    }
+                }
+            }
+        }
+    }
+    Ok(rule)
+}
+
+fn parse_range(range: &str, names: &[&str], base: u8) -> Result<(u8, u8), String> {
+    let index_of = |name: &str| {
+        names
+            .iter()
+            .position(|n| *n == name)
+            .map(|idx| idx as u8 + base)
+            .ok_or_else(|| format!("Unsupported selector {}", name))
+    };
+    match range.split_once('-') {
+        Some((start, end)) => Ok((index_of(start)?, index_of(end)?)),
+        None => {
+            let idx = index_of(range)?;
+            Ok((idx, idx))
+        }
+    }
+}
+
+fn parse_time(time: &str) -> Result<u16, String> {
+    let (hours, minutes) = time
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid time {}", time))?;
+    let hours: u16 = hours
+        .parse()
+        .map_err(|_| format!("Invalid hours in {}", time))?;
+    let minutes: u16 = minutes
+        .parse()
+        .map_err(|_| format!("Invalid minutes in {}", time))?;
+    if minutes >= 60 || hours > 48 {
+        return Err(format!("Time {} out of range", time));
+    }
+    Ok(hours * 60 + minutes)
+}
+
+fn parse_time_span(span: &str) -> Result<TimeSpan, String> {
+    // An open end, e.g. `18:00+`, we treat it as open until midnight.
+    if let Some(start) = span.strip_suffix('+') {
+        return Ok(TimeSpan {
+            start: parse_time(start)?,
+            end: MINUTES_PER_DAY,
+        });
+    }
+    let (start, end) = span
+        .split_once('-')
+        .ok_or_else(|| format!("Invalid time span {}", span))?;
+    let start = parse_time(start)?;
+    let mut end = parse_time(end)?;
+    if end <= start {
+        end += MINUTES_PER_DAY;
+    }
+    Ok(TimeSpan { start, end })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A time in the week of the 2nd May 2022, which starts with Monday.
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2022, 5, day).and_hms(hour, minute, 0)
+    }
+
+    fn parse(value: &str) -> OpeningHours {
+        OpeningHours::parse(value).unwrap()
+    }
+
+    #[test]
+    fn weekdays_and_times() {
+        let hours = parse("Mo-Fr 08:00-18:00; Sa 09:00-12:00");
+        assert_eq!(hours.rules.len(), 2);
+        assert_eq!(hours.is_open_at(at(2, 10, 0)), Some(true));
+        assert_eq!(hours.is_open_at(at(2, 18, 0)), Some(false));
+        assert_eq!(hours.is_open_at(at(7, 11, 59)), Some(true));
+        assert_eq!(hours.is_open_at(at(7, 13, 0)), Some(false));
+        assert_eq!(hours.is_open_at(at(8, 10, 0)), Some(false));
+    }
+
+    #[test]
+    fn later_rules_override_the_earlier_ones() {
+        let hours = parse("Mo-Su 08:00-20:00; Su off");
+        assert_eq!(hours.is_open_at(at(7, 10, 0)), Some(true));
+        assert_eq!(hours.is_open_at(at(8, 10, 0)), Some(false));
+    }
+
+    #[test]
+    fn comma_separated_rules() {
+        let hours = parse("Mo-Fr 08:00-12:00, Sa 09:00-11:00");
+        assert_eq!(hours.rules.len(), 2);
+        assert_eq!(hours.rules[1].weekdays, vec![(5, 5)]);
+        assert_eq!(hours.is_open_at(at(7, 10, 0)), Some(true));
+        assert_eq!(hours.is_open_at(at(3, 10, 0)), Some(true));
+    }
+
+    #[test]
+    fn comma_separated_lists() {
+        let hours = parse("Mo,We 08:00-12:00,13:00-17:00");
+        assert_eq!(hours.rules.len(), 1);
+        assert_eq!(hours.rules[0].weekdays, vec![(0, 0), (2, 2)]);
+        assert_eq!(hours.rules[0].times.len(), 2);
+        assert_eq!(hours.is_open_at(at(4, 14, 0)), Some(true));
+        assert_eq!(hours.is_open_at(at(4, 12, 30)), Some(false));
+        assert_eq!(hours.is_open_at(at(3, 10, 0)), Some(false));
+        let hours = parse("Mo-Fr, Su 10:00-16:00");
+        assert_eq!(hours.rules.len(), 1);
+        assert_eq!(hours.rules[0].weekdays, vec![(0, 4), (6, 6)]);
+    }
+
+    #[test]
+    fn commas_in_comments_do_not_split_rules() {
+        let hours = parse("Mo 08:00-12:00 \"by appointment, call first\"");
+        assert_eq!(hours.rules.len(), 1);
+        assert_eq!(
+            hours.rules[0].comment.as_deref(),
+            Some("by appointment, call first")
+        );
+    }
+
+    #[test]
+    fn spans_past_midnight() {
+        let hours = parse("Fr 22:00-02:00");
+        assert_eq!(hours.is_open_at(at(6, 23, 0)), Some(true));
+        assert_eq!(hours.is_open_at(at(7, 1, 30)), Some(true));
+        assert_eq!(hours.is_open_at(at(7, 2, 0)), Some(false));
+    }
+
+    #[test]
+    fn months_and_states() {
+        let hours = parse("Nov-Feb Mo-Su 10:00-16:00");
+        assert_eq!(
+            hours.is_open_at(NaiveDate::from_ymd(2022, 12, 5).and_hms(12, 0, 0)),
+            Some(true)
+        );
+        assert_eq!(hours.is_open_at(at(2, 12, 0)), Some(false));
+        assert_eq!(parse("24/7").is_open_at(at(8, 3, 0)), Some(true));
+        assert_eq!(
+            parse("Mo-Fr 08:00-12:00 unknown").is_open_at(at(2, 9, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn next_change() {
+        let hours = parse("Mo-Fr 08:00-18:00");
+        assert_eq!(hours.next_change_after(at(2, 10, 0)), Some(at(2, 18, 0)));
+        assert_eq!(
+            hours.next_change_after(at(6, 19, 0)),
+            Some(NaiveDate::from_ymd(2022, 5, 9).and_hms(8, 0, 0))
+        );
+        assert_eq!(parse("24/7").next_change_after(at(2, 10, 0)), None);
+    }
+
+    #[test]
+    fn weekly_summary() {
+        let hours = parse("Mo-Fr 08:00-18:00; Sa 09:00-12:00 \"summer\"");
+        assert_eq!(
+            hours.weekly_summary("en"),
+            "Monday to Friday: 08:00–18:00; Saturday: 09:00–12:00 (summer)"
+        );
+    }
+
+    #[test]
+    fn unsupported_values() {
+        assert!(OpeningHours::parse("").is_err());
+        assert!(OpeningHours::parse("whenever").is_err());
+        assert!(OpeningHours::parse("Mo 25:00-26:00x").is_err());
+        assert!(OpeningHours::parse("Mo 08:00-12:00 \"unterminated").is_err());
+    }
+}
@@ -1,18 +1,43 @@
+use crate::change_encoding;
 use crate::entity_relationship::RootedEntityRelationship;
-use crate::Result;
+use crate::{Error, Result};
 use once_cell::sync::Lazy;
+use osm_api::compression::{Dictionaries, LEGACY_DICTIONARY_ID};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::sync::Mutex;
-use zstd_util::ZstdContext;
-
-static ZSTD_CONTEXT: Lazy<Mutex<ZstdContext>> = Lazy::new(|| {
-    Mutex::new(ZstdContext::new(
-        10,
-        Some(include_bytes!("../../changes.dict")),
-    ))
+use std::collections::BTreeMap;
+
+/// The clients need all the dictionaries of the changes still waiting for them, so every version is compiled in, the newer ones from the changes_dictionaries directory.
+pub static CHANGES_DICTIONARIES: Lazy<Dictionaries> = Lazy::new(|| {
+    let mut dictionaries = BTreeMap::new();
+    dictionaries.insert(
+        LEGACY_DICTIONARY_ID,
+        include_bytes!("../../changes.dict").to_vec(),
+    );
+    let newer: &[(u32, &[u8])] =
+        include!(concat!(env!("OUT_DIR"), "/changes_dictionaries.rs"));
+    for (id, dictionary) in newer {
+        dictionaries.insert(*id, dictionary.to_vec());
+    }
+    Dictionaries::new("changes", 10, dictionaries)
 });
 
+/// The serialized changes which every client understands, compressed with the legacy dictionary and without the dictionary header.
+pub const LEGACY_CHANGES_VERSION: u32 = 1;
+/// The newest version of the serialized changes, the clients report the one they understand when downloading an area.
+///
+/// The version 2 added the dictionary headers, the version 3 the binary encoding.
+pub const CHANGES_VERSION: u32 = 3;
+
+/// How the changes are encoded before their compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeFormat {
+    /// Understood by all the clients.
+    Json,
+    /// Smaller and faster, but needs a client knowing its version.
+    Binary,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum EntryChange {
     Create {
@@ -171,16 +196,51 @@ impl SemanticChange {
         }
     }
 
+    pub fn encode_as(&self, format: ChangeFormat) -> Result<Vec<u8>> {
+        match format {
+            ChangeFormat::Json => Ok(serde_json::to_vec(&self)?),
+            ChangeFormat::Binary => change_encoding::encode_v1(self),
+        }
+    }
+
+    /// The uncompressed JSON form of the change, the binary one is used only for the clients negotiating it.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        self.encode_as(ChangeFormat::Json)
+    }
+
+    /// Decodes an uncompressed change of any known format.
+    pub fn decode(encoded: &[u8]) -> Result<Self> {
+        match encoded.first() {
+            Some(&change_encoding::BINARY_V1) => change_encoding::decode_v1(&encoded[1..]),
+            Some(&version) if !change_encoding::is_json_start(version) => {
+                Err(Error::UnsupportedChangeFormat(version))
+            }
+            _ => Ok(serde_json::from_slice(encoded)?),
+        }
+    }
+
+    pub fn serialize_as(&self, format: ChangeFormat) -> Result<Vec<u8>> {
+        Ok(CHANGES_DICTIONARIES.compress(&self.encode_as(format)?)?)
+    }
+
+    /// Serializes the change for the clients understanding the given version of the changes.
+    pub fn serialize_for(&self, version: u32) -> Result<Vec<u8>> {
+        if version >= 3 {
+            self.serialize_as(ChangeFormat::Binary)
+        } else if version == 2 {
+            self.serialize_as(ChangeFormat::Json)
+        } else {
+            Ok(CHANGES_DICTIONARIES.compress_legacy(&self.encode()?)?)
+        }
+    }
+
+    /// Serializes the change in the form every client understands.
     pub fn serialize(&self) -> Result<Vec<u8>> {
-        let encoded = serde_json::to_string(&self)?;
-        Ok(ZSTD_CONTEXT
-            .lock()
-            .unwrap()
-            .compress(&encoded.into_bytes())?)
+        self.serialize_for(LEGACY_CHANGES_VERSION)
     }
 
+    /// Reads a change serialized in any of the formats, they are told apart by their first byte.
     pub fn from_serialized(data: &[u8]) -> Result<Self> {
-        let decompressed = ZSTD_CONTEXT.lock().unwrap().decompress(data)?;
-        Ok(serde_json::from_slice(&decompressed)?)
+        Self::decode(&CHANGES_DICTIONARIES.decompress(data)?)
     }
 }
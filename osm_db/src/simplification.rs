@@ -0,0 +1,111 @@
+//! Simplified variants of the entity geometries, for the clients which can't afford the full detail of e.g. coastlines or administrative boundaries.
+use std::str::FromStr;
+
+// Area databases created before the simplified geometries existed lack these columns.
+pub(crate) const ADD_SIMPLIFIED_GEOMETRY_COLUMNS_SQL: &str = "ALTER TABLE entities ADD COLUMN geometry_fine BLOB; ALTER TABLE entities ADD COLUMN geometry_medium BLOB; ALTER TABLE entities ADD COLUMN geometry_coarse BLOB;";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimplificationLevel {
+    Full,
+    Fine,
+    Medium,
+    Coarse,
+}
+
+impl SimplificationLevel {
+    pub const SIMPLIFIED: [SimplificationLevel; 3] = [
+        SimplificationLevel::Fine,
+        SimplificationLevel::Medium,
+        SimplificationLevel::Coarse,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        use SimplificationLevel::*;
+        match self {
+            Full => "full",
+            Fine => "fine",
+            Medium => "medium",
+            Coarse => "coarse",
+        }
+    }
+
+    /// The Douglas-Peucker tolerance in degrees, roughly one, ten and hundred meters.
+    pub fn tolerance(&self) -> Option<f64> {
+        use SimplificationLevel::*;
+        match self {
+            Full => None,
+            Fine => Some(0.00001),
+            Medium => Some(0.0001),
+            Coarse => Some(0.001),
+        }
+    }
+
+    pub fn column_name(&self) -> &'static str {
+        use SimplificationLevel::*;
+        match self {
+            Full => "geometry",
+            Fine => "geometry_fine",
+            Medium => "geometry_medium",
+            Coarse => "geometry_coarse",
+        }
+    }
+
+    /// The simplified variant is stored only when it dropped some points, otherwise the full geometry stands in for it.
+    pub(crate) fn geometry_expr(&self) -> String {
+        match self {
+            SimplificationLevel::Full => "geometry".to_string(),
+            _ => format!("COALESCE({}, geometry)", self.column_name()),
+        }
+    }
+}
+
+impl FromStr for SimplificationLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use SimplificationLevel::*;
+        match s {
+            "full" => Ok(Full),
+            "fine" => Ok(Fine),
+            "medium" => Ok(Medium),
+            "coarse" => Ok(Coarse),
+            _ => Err(format!("Unknown simplification level {}", s)),
+        }
+    }
+}
+
+/// Computes the simplified geometries of the entities matching the given SQL condition.
+pub(crate) fn update_simplified_geometries_sql(condition: &str) -> String {
+    let assignments: Vec<String> = SimplificationLevel::SIMPLIFIED
+        .iter()
+        .map(|level| {
+            let simplified = format!(
+                "ST_SimplifyPreserveTopology(geometry, {})",
+                level.tolerance().unwrap()
+            );
+            format!(
+                "{column} = CASE WHEN ST_NPoints({simplified}) < ST_NPoints(geometry) THEN {simplified} END",
+                column = level.column_name(),
+                simplified = simplified
+            )
+        })
+        .collect();
+    format!(
+        "UPDATE entities SET {} WHERE {}",
+        assignments.join(", "),
+        condition
+    )
+}
+
+/// Replaces the geometries by their simplified variants, which are then dropped, used on a copy of the database before it's shipped to a client.
+pub(crate) fn replace_geometries_sql(level: SimplificationLevel) -> String {
+    let cleared: Vec<String> = SimplificationLevel::SIMPLIFIED
+        .iter()
+        .map(|level| format!("{} = NULL", level.column_name()))
+        .collect();
+    format!(
+        "UPDATE entities SET geometry = {}; UPDATE entities SET {}; VACUUM;",
+        level.geometry_expr(),
+        cleared.join(", ")
+    )
+}
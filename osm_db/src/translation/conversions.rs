@@ -1,11 +1,40 @@
 use super::record::TranslationRecord;
+use crate::conditional::ConditionalRestriction;
 use crate::entity_metadata::EntityMetadata;
 use crate::entity_metadata::Enum;
+use crate::opening_hours::OpeningHours;
 use hashbrown::HashMap;
 use serde_json::{Number, Value};
-use uom::si::f64::{Length, Mass};
-use uom::si::length::meter;
-use uom::si::mass::ton;
+use uom::si::angle::{degree, radian};
+use uom::si::electric_potential::{kilovolt, megavolt, volt};
+use uom::si::f64::{Angle, ElectricPotential, Frequency, Length, Mass, Time, Velocity};
+use uom::si::frequency::{hertz, kilohertz};
+use uom::si::length::{
+    centimeter, foot, inch, kilometer, meter, mile, millimeter, nautical_mile, yard,
+};
+use uom::si::mass::{hundredweight_long, kilogram, pound, ton, ton_long, ton_short};
+use uom::si::time::{day, hour, minute, second};
+use uom::si::velocity::{kilometer_per_hour, knot, meter_per_second, mile_per_hour};
+
+const KNOWN_SCALAR_TYPES: &[&str] = &[
+    "str",
+    "Address",
+    "int",
+    "bool",
+    "float",
+    "tons",
+    "meters",
+    "kmh",
+    "minutes",
+    "percent",
+    "volts",
+    "hertz",
+    "opening_hours",
+    "conditional",
+];
+
+// The parsed types whose values are kept as the raw strings when they can not be parsed.
+const KEPT_RAW_TYPES: &[&str] = &["opening_hours", "conditional"];
 
 pub fn convert_address(tags: &HashMap<String, String>) -> (HashMap<String, String>, Vec<&str>) {
     let mut address_field_names = vec![];
@@ -24,6 +53,9 @@ pub fn convert_field_value(
     value_type: &str,
     record: &mut TranslationRecord,
 ) -> Option<Value> {
+    if let Some(item_type) = list_item_type(value_type) {
+        return convert_list(raw_value, item_type, record);
+    }
     match value_type {
         "str" | "Address" => Some(Value::String(raw_value.to_string())),
         "int" => convert_int(raw_value, record),
@@ -31,6 +63,13 @@ pub fn convert_field_value(
         "float" => convert_float(raw_value, record),
         "tons" => convert_to_tons(raw_value, record),
         "meters" => convert_to_meters(raw_value, record),
+        "kmh" => convert_to_kmh(raw_value, record),
+        "minutes" => convert_to_minutes(raw_value, record),
+        "percent" => convert_to_percent(raw_value, record),
+        "volts" => convert_to_volts(raw_value, record),
+        "hertz" => convert_to_hertz(raw_value, record),
+        "opening_hours" => convert_opening_hours(raw_value, record),
+        "conditional" => convert_conditional(raw_value, record),
         _ => {
             if let Some(enum_spec) = Enum::with_name(value_type) {
                 convert_value_of_enum(raw_value, &enum_spec, record)
@@ -41,6 +80,32 @@ pub fn convert_field_value(
     }
 }
 
+pub fn is_known_type(value_type: &str) -> bool {
+    let value_type = list_item_type(value_type).unwrap_or(value_type);
+    KNOWN_SCALAR_TYPES.contains(&value_type) || Enum::with_name(value_type).is_some()
+}
+
+/// For a list type specifier, e.g. `list[str]`, returns the type of its items.
+pub fn list_item_type(value_type: &str) -> Option<&str> {
+    value_type
+        .strip_prefix("list[")
+        .and_then(|t| t.strip_suffix(']'))
+}
+
+fn convert_list(value: &str, item_type: &str, record: &mut TranslationRecord) -> Option<Value> {
+    let items: Vec<Value> = value
+        .split(';')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .filter_map(|item| convert_field_value(item, item_type, record))
+        .collect();
+    if items.is_empty() {
+        None
+    } else {
+        Some(Value::Array(items))
+    }
+}
+
 pub fn convert_entity_data(
     discriminator: &str,
     entity_data: &HashMap<String, String>,
@@ -57,8 +122,15 @@ pub fn convert_entity_data(
             .get(key)
             .map(|f| f.type_name.as_str())
             .unwrap_or("str");
-        if let Some(converted) = convert_field_value(value, type_name, record) {
-            converted_data.insert(key.clone(), converted);
+        match convert_field_value(value, type_name, record) {
+            Some(converted) => {
+                converted_data.insert(key.clone(), converted);
+            }
+            // We support only a subset of their syntax, so the users still get the raw values to read.
+            None if KEPT_RAW_TYPES.contains(&type_name) => {
+                converted_data.insert(key.clone(), Value::String(value.clone()));
+            }
+            None => {}
         }
     }
     converted_data
@@ -114,16 +186,25 @@ fn convert_float(value: &str, record: &mut TranslationRecord) -> Option<Value> {
     }
 }
 
+/// Splits a value like `2 ft`, `30mph` or `10%` into its magnitude and an optional unit.
 fn split_unit_spec<'a>(
     spec: &'a str,
     record: &mut TranslationRecord,
 ) -> Option<(f64, Option<&'a str>)> {
-    let parts: Vec<&str> = spec.split(' ').collect();
-    if parts.len() > 2 {
+    let spec = spec.trim();
+    let number_len = spec
+        .char_indices()
+        .find(|(idx, c)| {
+            !(c.is_ascii_digit() || *c == '.' || (*idx == 0 && (*c == '-' || *c == '+')))
+        })
+        .map(|(idx, _)| idx)
+        .unwrap_or_else(|| spec.len());
+    let unit = spec[number_len..].trim();
+    if unit.contains(' ') {
         record.record_type_violation(spec);
         None
-    } else if let Ok(num) = parts[0].parse::<f64>() {
-        Some((num, parts.get(1).cloned()))
+    } else if let Ok(num) = spec[..number_len].parse::<f64>() {
+        Some((num, if unit.is_empty() { None } else { Some(unit) }))
     } else {
         record.record_type_violation(spec);
         None
@@ -132,28 +213,172 @@ fn split_unit_spec<'a>(
 
 fn convert_to_tons(value: &str, record: &mut TranslationRecord) -> Option<Value> {
     let (magnitude, unit_str) = split_unit_spec(value, record)?;
-    match unit_str {
-        None => construct_json_f64(magnitude),
-        Some(unit) => match unit {
-            "t" => construct_json_f64(Mass::new::<ton>(magnitude).get::<ton>()),
-            _ => {
+    let mass = match unit_str {
+        None | Some("t") => Mass::new::<ton>(magnitude),
+        Some("kg") => Mass::new::<kilogram>(magnitude),
+        Some("lbs") | Some("lb") => Mass::new::<pound>(magnitude),
+        Some("st") => Mass::new::<ton_short>(magnitude),
+        Some("lt") => Mass::new::<ton_long>(magnitude),
+        Some("cwt") => Mass::new::<hundredweight_long>(magnitude),
+        Some(_) => {
+            record.record_type_violation(value);
+            return None;
+        }
+    };
+    construct_json_f64(mass.get::<ton>())
+}
+
+/// Parses the imperial `6'5"` notation, either part may be missing.
+fn parse_feet_and_inches(value: &str) -> Option<Length> {
+    let value = value.trim();
+    let (feet, rest) = match value.split_once('\'') {
+        Some((feet, rest)) => (feet.trim().parse::<f64>().ok()?, rest.trim()),
+        None => (0.0, value),
+    };
+    let inches = if rest.is_empty() {
+        0.0
+    } else {
+        rest.strip_suffix('"')?.trim().parse::<f64>().ok()?
+    };
+    Some(Length::new::<foot>(feet) + Length::new::<inch>(inches))
+}
+
+fn convert_to_meters(value: &str, record: &mut TranslationRecord) -> Option<Value> {
+    if value.contains('\'') || value.ends_with('"') {
+        return match parse_feet_and_inches(value) {
+            Some(length) => construct_json_f64(length.get::<meter>()),
+            None => {
                 record.record_type_violation(value);
                 None
             }
-        },
+        };
     }
+    let (magnitude, unit_str) = split_unit_spec(value, record)?;
+    let length = match unit_str {
+        None | Some("m") => Length::new::<meter>(magnitude),
+        Some("km") => Length::new::<kilometer>(magnitude),
+        Some("cm") => Length::new::<centimeter>(magnitude),
+        Some("mm") => Length::new::<millimeter>(magnitude),
+        Some("ft") => Length::new::<foot>(magnitude),
+        Some("in") => Length::new::<inch>(magnitude),
+        Some("yd") => Length::new::<yard>(magnitude),
+        Some("mi") => Length::new::<mile>(magnitude),
+        Some("nmi") => Length::new::<nautical_mile>(magnitude),
+        Some(_) => {
+            record.record_type_violation(value);
+            return None;
+        }
+    };
+    construct_json_f64(length.get::<meter>())
 }
 
-fn convert_to_meters(value: &str, record: &mut TranslationRecord) -> Option<Value> {
+fn convert_to_kmh(value: &str, record: &mut TranslationRecord) -> Option<Value> {
     let (magnitude, unit_str) = split_unit_spec(value, record)?;
-    match unit_str {
-        None => construct_json_f64(magnitude),
-        Some(unit) => match unit {
-            "m" => construct_json_f64(Length::new::<meter>(magnitude).get::<meter>()),
+    let speed = match unit_str {
+        None | Some("km/h") | Some("kmh") | Some("kph") => {
+            Velocity::new::<kilometer_per_hour>(magnitude)
+        }
+        Some("mph") => Velocity::new::<mile_per_hour>(magnitude),
+        Some("knots") | Some("kn") => Velocity::new::<knot>(magnitude),
+        Some("m/s") => Velocity::new::<meter_per_second>(magnitude),
+        Some(_) => {
+            record.record_type_violation(value);
+            return None;
+        }
+    };
+    construct_json_f64(speed.get::<kilometer_per_hour>())
+}
+
+fn convert_to_minutes(value: &str, record: &mut TranslationRecord) -> Option<Value> {
+    // The hh:mm notation, used e.g. for maxstay and duration.
+    if let Some((hours, minutes)) = value.trim().split_once(':') {
+        return match (hours.parse::<f64>(), minutes.parse::<f64>()) {
+            (Ok(hours), Ok(minutes)) => construct_json_f64(
+                (Time::new::<hour>(hours) + Time::new::<minute>(minutes)).get::<minute>(),
+            ),
             _ => {
                 record.record_type_violation(value);
                 None
             }
-        },
+        };
+    }
+    let (magnitude, unit_str) = split_unit_spec(value, record)?;
+    let duration = match unit_str {
+        None | Some("min") | Some("mins") | Some("minute") | Some("minutes") => {
+            Time::new::<minute>(magnitude)
+        }
+        Some("s") | Some("sec") | Some("seconds") => Time::new::<second>(magnitude),
+        Some("h") | Some("hour") | Some("hours") => Time::new::<hour>(magnitude),
+        Some("d") | Some("day") | Some("days") => Time::new::<day>(magnitude),
+        Some("week") | Some("weeks") => Time::new::<day>(magnitude * 7.0),
+        Some(_) => {
+            record.record_type_violation(value);
+            return None;
+        }
+    };
+    construct_json_f64(duration.get::<minute>())
+}
+
+fn convert_to_percent(value: &str, record: &mut TranslationRecord) -> Option<Value> {
+    let (magnitude, unit_str) = split_unit_spec(value, record)?;
+    match unit_str {
+        None | Some("%") => construct_json_f64(magnitude),
+        Some("°") => {
+            construct_json_f64(Angle::new::<degree>(magnitude).get::<radian>().tan() * 100.0)
+        }
+        Some(_) => {
+            record.record_type_violation(value);
+            None
+        }
+    }
+}
+
+fn convert_to_volts(value: &str, record: &mut TranslationRecord) -> Option<Value> {
+    let (magnitude, unit_str) = split_unit_spec(value, record)?;
+    let voltage = match unit_str {
+        None | Some("V") => ElectricPotential::new::<volt>(magnitude),
+        Some("kV") => ElectricPotential::new::<kilovolt>(magnitude),
+        Some("MV") => ElectricPotential::new::<megavolt>(magnitude),
+        Some(_) => {
+            record.record_type_violation(value);
+            return None;
+        }
+    };
+    construct_json_f64(voltage.get::<volt>())
+}
+
+fn convert_to_hertz(value: &str, record: &mut TranslationRecord) -> Option<Value> {
+    let (magnitude, unit_str) = split_unit_spec(value, record)?;
+    let frequency = match unit_str {
+        None | Some("Hz") => Frequency::new::<hertz>(magnitude),
+        Some("kHz") => Frequency::new::<kilohertz>(magnitude),
+        Some(_) => {
+            record.record_type_violation(value);
+            return None;
+        }
+    };
+    construct_json_f64(frequency.get::<hertz>())
+}
+
+fn convert_opening_hours(value: &str, record: &mut TranslationRecord) -> Option<Value> {
+    match OpeningHours::parse(value) {
+        Ok(hours) => Some(serde_json::to_value(hours).expect("Could not serialize opening hours.")),
+        Err(_) => {
+            record.record_type_violation(value);
+            None
+        }
+    }
+}
+
+fn convert_conditional(value: &str, record: &mut TranslationRecord) -> Option<Value> {
+    match ConditionalRestriction::parse(value) {
+        Ok(restriction) => Some(
+            serde_json::to_value(restriction)
+                .expect("Could not serialize conditional restriction."),
+        ),
+        Err(_) => {
+            record.record_type_violation(value);
+            None
+        }
     }
 }
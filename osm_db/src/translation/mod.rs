@@ -1,5 +1,7 @@
 mod checks;
 pub mod conversions;
+pub mod overlay;
 pub mod record;
+pub mod reservoir;
 mod spec;
 pub mod translator;
@@ -0,0 +1,222 @@
+//! Curated local additions, e.g. indoor or private paths which can't go into OSM, imported from a GeoJSON overlay.
+//!
+//! The overlay entities have ids in a namespace of their own, `namespace:feature_id`, so the OSM updates of an area never touch them.
+use super::checks;
+use super::record::TranslationRecord;
+use crate::area_db::AreaDatabase;
+use crate::entity_relationship::RootedEntityRelationship;
+use crate::semantic_change::SemanticChange;
+use crate::{Error, Result};
+use hashbrown::{HashMap, HashSet};
+use log::warn;
+use serde_json::{Map, Value};
+
+// The entity ids are stored inline in a SmolStr.
+const MAX_ID_LENGTH: usize = 22;
+
+/// The OSM ids start with the type letter, and some code tells the relations by it, so the namespaces must avoid them.
+fn validate_namespace(namespace: &str) -> Result<()> {
+    let mut chars = namespace.chars();
+    let valid = match chars.next() {
+        Some(first) => {
+            first.is_ascii_alphabetic()
+                && !matches!(first.to_ascii_lowercase(), 'n' | 'w' | 'r')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidOverlayNamespace(namespace.to_string()))
+    }
+}
+
+pub fn overlay_id(namespace: &str, feature_id: &str) -> Result<String> {
+    let prefix = format!("{}:", namespace);
+    // Features exported from the area database already have the full id.
+    let id = if feature_id.starts_with(&prefix) {
+        feature_id.to_string()
+    } else {
+        format!("{}{}", prefix, feature_id)
+    };
+    if id.len() > MAX_ID_LENGTH {
+        return Err(Error::InvalidOverlay(format!(
+            "the id {} is longer than {} bytes",
+            id, MAX_ID_LENGTH
+        )));
+    }
+    Ok(id)
+}
+
+/// Reverses the flattening of the exported properties, e.g. `address/street` becomes the `street` of the `address` map.
+fn unflatten(properties: Map<String, Value>) -> Map<String, Value> {
+    let mut data = Map::new();
+    for (key, value) in properties {
+        if value.is_null() {
+            continue;
+        }
+        let mut parts: Vec<&str> = key.split('/').collect();
+        let last = parts.pop().unwrap();
+        let mut target = &mut data;
+        for part in parts {
+            let entry = target
+                .entry(part.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+            target = entry.as_object_mut().unwrap();
+        }
+        target.insert(last.to_string(), value);
+    }
+    data
+}
+
+pub struct OverlayChanges {
+    pub changes: Vec<SemanticChange>,
+    /// The ids of the features which failed the entity metadata checks, the details are in the translation record.
+    pub rejected_ids: Vec<String>,
+}
+
+struct OverlayEntity {
+    id: String,
+    discriminator: String,
+    geometry: Vec<u8>,
+    data: String,
+    effective_width: Option<f64>,
+    relationships: Vec<RootedEntityRelationship>,
+}
+
+fn feature_id(feature: &Value) -> Result<String> {
+    match feature.get("id") {
+        Some(Value::String(id)) => Ok(id.clone()),
+        Some(Value::Number(id)) => Ok(id.to_string()),
+        _ => Err(Error::InvalidOverlay("a feature has no id".to_string())),
+    }
+}
+
+/// Returns None when the feature data fail the checks against the entity metadata.
+fn feature_to_entity(
+    db: &AreaDatabase,
+    feature: &Value,
+    id: String,
+    record: &mut TranslationRecord,
+) -> Result<Option<OverlayEntity>> {
+    let mut properties = match feature.get("properties") {
+        Some(Value::Object(properties)) => properties.clone(),
+        _ => Map::new(),
+    };
+    let discriminator = match properties.remove("discriminator") {
+        Some(Value::String(discriminator)) => discriminator,
+        _ => {
+            return Err(Error::InvalidOverlay(format!(
+                "the feature {} has no discriminator property",
+                id
+            )))
+        }
+    };
+    let effective_width = properties
+        .remove("effective_width")
+        .and_then(|w| w.as_f64());
+    let data = unflatten(properties);
+    record.set_current_object_id(&id);
+    let checked_data: HashMap<String, Value> = data.clone().into_iter().collect();
+    if !checks::check_entity_data_consistency(&discriminator, &checked_data, record) {
+        return Ok(None);
+    }
+    let geometry = match feature.get("geometry") {
+        Some(geometry) if !geometry.is_null() => db.geojson_to_wkb(&geometry.to_string())?,
+        _ => None,
+    }
+    .ok_or_else(|| Error::InvalidOverlay(format!("the feature {} has no valid geometry", id)))?;
+    let relationships = match feature.get("relationships") {
+        Some(relationships) => serde_json::from_value(relationships.clone())?,
+        None => vec![],
+    };
+    Ok(Some(OverlayEntity {
+        id,
+        discriminator,
+        geometry,
+        data: serde_json::to_string(&data)?,
+        effective_width,
+        relationships,
+    }))
+}
+
+fn is_unchanged(db: &AreaDatabase, entity: &OverlayEntity) -> Result<bool> {
+    Ok(match db.get_entity(&entity.id)? {
+        Some(old) => {
+            old.discriminator == entity.discriminator
+                && old.data == entity.data
+                && old.geometry == entity.geometry
+                && old.effective_width == entity.effective_width
+                && db.get_entity_child_relationships(&entity.id)? == entity.relationships
+        }
+        None => false,
+    })
+}
+
+/// Computes the changes which make the overlay entities of the namespace match the features of the GeoJSON feature collection.
+///
+/// The features need a `discriminator` property, the other properties become the entity data, like in the exports of the area databases.
+/// The overlay entities which are no longer in the collection are removed, the changed ones are replaced.
+pub fn overlay_changes(
+    db: &AreaDatabase,
+    geojson: &Value,
+    namespace: &str,
+    record: &mut TranslationRecord,
+) -> Result<OverlayChanges> {
+    validate_namespace(namespace)?;
+    let features = geojson
+        .get("features")
+        .and_then(|f| f.as_array())
+        .ok_or_else(|| Error::InvalidOverlay("not a feature collection".to_string()))?;
+    let mut stale_ids: HashSet<String> = db
+        .get_entity_ids_with_prefix(&format!("{}:", namespace))?
+        .into_iter()
+        .collect();
+    let mut seen_ids = HashSet::new();
+    let mut changes = vec![];
+    let mut rejected_ids = vec![];
+    for feature in features {
+        let id = overlay_id(namespace, &feature_id(feature)?)?;
+        if !seen_ids.insert(id.clone()) {
+            return Err(Error::InvalidOverlay(format!(
+                "the id {} is used more than once",
+                id
+            )));
+        }
+        // A rejected feature keeps its previous entity, so a typo does not remove it.
+        let existed = stale_ids.remove(&id);
+        let entity = match feature_to_entity(db, feature, id.clone(), record)? {
+            Some(entity) => entity,
+            None => {
+                warn!("Overlay feature {} failed the entity checks.", id);
+                rejected_ids.push(id);
+                continue;
+            }
+        };
+        if existed {
+            if is_unchanged(db, &entity)? {
+                continue;
+            }
+            changes.push(SemanticChange::removing(&entity.id));
+        }
+        changes.push(SemanticChange::creating(
+            entity.id,
+            entity.geometry,
+            entity.discriminator,
+            entity.data,
+            entity.effective_width,
+            entity.relationships,
+        ));
+    }
+    for id in stale_ids {
+        changes.push(SemanticChange::removing(&id));
+    }
+    Ok(OverlayChanges {
+        changes,
+        rejected_ids,
+    })
+}
@@ -1,20 +1,128 @@
 #![allow(clippy::new_without_default)]
+use super::reservoir::Reservoir;
 use crate::Result;
 use osm_api::object::OSMObject;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+// The tags which usually decide what an object is, the untranslated objects are grouped by them.
+const MAIN_TAG_KEYS: &[&str] = &[
+    "amenity",
+    "shop",
+    "tourism",
+    "leisure",
+    "office",
+    "craft",
+    "healthcare",
+    "emergency",
+    "historic",
+    "man_made",
+    "public_transport",
+    "railway",
+    "highway",
+    "barrier",
+    "power",
+    "natural",
+    "waterway",
+    "landuse",
+    "building",
+];
+const UNINTERESTING_TAG_KEYS: &[&str] = &["created_by", "source"];
+
+type Problems<T> = HashMap<String, HashMap<String, Reservoir<T>>>;
+
+/// An offending value together with the object it came from, if known.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Example {
+    pub value: String,
+    pub object_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InterestingObject {
+    pub id: String,
+    pub tags: BTreeMap<String, String>,
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct TranslationRecord {
+    type_violations: Problems<Example>,
+    /// The samples contain the ids of the objects.
+    missing_enum_members: Problems<String>,
+    unknown_fields: Problems<Example>,
+    /// The samples contain the ids of the objects.
+    missing_required_fields: Problems<String>,
+    /// Keyed by the main tag, e.g. `amenity=bench`.
+    potentially_interesting_objects: HashMap<String, Reservoir<InterestingObject>>,
+    /// Keyed by the discriminator and the fix name, the samples contain the ids of the objects.
+    #[serde(default)]
+    geometry_fixes: Problems<String>,
+    current_field: Option<String>,
+    current_discriminator: Option<String>,
+    #[serde(skip)]
+    current_object_id: Option<String>,
+}
+
+/// The format used before the records were bounded, kept so the old records can still be loaded.
+#[derive(Deserialize)]
+struct UnboundedTranslationRecord {
     type_violations: HashMap<String, HashMap<String, Vec<String>>>,
     missing_enum_members: HashMap<String, HashMap<String, u32>>,
     unknown_fields: HashMap<String, HashMap<String, Vec<String>>>,
     missing_required_fields: HashMap<String, HashMap<String, u32>>,
     potentially_interesting_objects: Vec<OSMObject>,
-    current_field: Option<String>,
-    current_discriminator: Option<String>,
+}
+
+fn problem_reservoir<'a, T>(
+    problems: &'a mut Problems<T>,
+    outer: &str,
+    inner: &str,
+) -> &'a mut Reservoir<T> {
+    problems
+        .entry(outer.to_string())
+        .or_default()
+        .entry(inner.to_string())
+        .or_default()
+}
+
+fn add_problem<T>(problems: &mut Problems<T>, outer: &str, inner: &str, item: T) {
+    problem_reservoir(problems, outer, inner).add(item);
+}
+
+fn merge_problems<T>(source: Problems<T>, target: &mut Problems<T>) {
+    for (outer, inner_problems) in source.into_iter() {
+        let target_inner = target.entry(outer).or_default();
+        for (inner, reservoir) in inner_problems.into_iter() {
+            target_inner.entry(inner).or_default().merge(reservoir);
+        }
+    }
+}
+
+fn counts_to_problems(
+    source: HashMap<String, HashMap<String, u32>>,
+    target: &mut Problems<String>,
+) {
+    for (outer, counts) in source.into_iter() {
+        let target_inner = target.entry(outer).or_default();
+        for (inner, count) in counts.into_iter() {
+            target_inner.insert(inner, Reservoir::with_count(count.into()));
+        }
+    }
+}
+
+fn group_key_for(object: &OSMObject) -> Option<String> {
+    if let Some(key) = MAIN_TAG_KEYS.iter().find(|k| object.tags.contains_key(**k)) {
+        return Some(format!("{}={}", key, object.tags[*key]));
+    }
+    // Without a main tag, grouping by the values would produce too many groups.
+    object
+        .tags
+        .keys()
+        .filter(|k| !UNINTERESTING_TAG_KEYS.contains(&k.as_str()))
+        .min()
+        .map(|k| format!("{}=*", k))
 }
 
 impl TranslationRecord {
@@ -24,14 +132,131 @@ impl TranslationRecord {
             missing_enum_members: HashMap::new(),
             unknown_fields: HashMap::new(),
             missing_required_fields: HashMap::new(),
-            potentially_interesting_objects: Vec::new(),
+            potentially_interesting_objects: HashMap::new(),
+            geometry_fixes: HashMap::new(),
             current_discriminator: None,
             current_field: None,
+            current_object_id: None,
         }
     }
 
+    pub fn load_from_str(serialized: &str) -> Result<Self> {
+        match serde_json::from_str(serialized) {
+            Ok(record) => Ok(record),
+            Err(e) => match serde_json::from_str::<UnboundedTranslationRecord>(serialized) {
+                Ok(unbounded) => Ok(Self::from_unbounded(unbounded)),
+                Err(_) => Err(e.into()),
+            },
+        }
+    }
+
+    fn from_unbounded(unbounded: UnboundedTranslationRecord) -> Self {
+        let mut record = Self::new();
+        for (discriminator, fields) in unbounded.type_violations {
+            for (field, values) in fields {
+                for value in values {
+                    add_problem(
+                        &mut record.type_violations,
+                        &discriminator,
+                        &field,
+                        Example {
+                            value,
+                            object_id: None,
+                        },
+                    );
+                }
+            }
+        }
+        for (discriminator, fields) in unbounded.unknown_fields {
+            for (field, values) in fields {
+                for value in values {
+                    add_problem(
+                        &mut record.unknown_fields,
+                        &discriminator,
+                        &field,
+                        Example {
+                            value,
+                            object_id: None,
+                        },
+                    );
+                }
+            }
+        }
+        counts_to_problems(
+            unbounded.missing_enum_members,
+            &mut record.missing_enum_members,
+        );
+        counts_to_problems(
+            unbounded.missing_required_fields,
+            &mut record.missing_required_fields,
+        );
+        for object in unbounded.potentially_interesting_objects {
+            record.add_potentially_interesting_object(object);
+        }
+        record
+    }
+
+    /// Loads a record written at once by `save_to_file`, or in parts by `append_to_file`.
+    pub fn load_from_file(path: &str) -> Result<Self> {
+        let mut record = Self::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                Self::load_from_str(&line)?.merge_to(&mut record);
+            }
+        }
+        Ok(record)
+    }
+
+    pub fn to_json_string(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self)?)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer(writer, &self)?;
+        Ok(())
+    }
+
+    pub fn type_violations(&self) -> &Problems<Example> {
+        &self.type_violations
+    }
+
+    pub fn missing_enum_members(&self) -> &Problems<String> {
+        &self.missing_enum_members
+    }
+
+    pub fn unknown_fields(&self) -> &Problems<Example> {
+        &self.unknown_fields
+    }
+
+    pub fn missing_required_fields(&self) -> &Problems<String> {
+        &self.missing_required_fields
+    }
+
+    pub fn potentially_interesting_objects(
+        &self,
+    ) -> &HashMap<String, Reservoir<InterestingObject>> {
+        &self.potentially_interesting_objects
+    }
+
+    pub fn geometry_fixes(&self) -> &Problems<String> {
+        &self.geometry_fixes
+    }
+
     pub fn add_potentially_interesting_object(&mut self, object: OSMObject) {
-        self.potentially_interesting_objects.push(object);
+        if let Some(key) = group_key_for(&object) {
+            self.potentially_interesting_objects
+                .entry(key)
+                .or_default()
+                .add(InterestingObject {
+                    id: object.unique_id().to_string(),
+                    tags: object.tags.into_iter().collect(),
+                });
+        }
+    }
+
+    pub fn set_current_object_id(&mut self, id: &str) {
+        self.current_object_id = Some(id.to_string());
     }
 
     pub fn set_current_discriminator(&mut self, discriminator: &str) {
@@ -42,83 +267,119 @@ impl TranslationRecord {
         self.current_field = Some(field.to_string());
     }
 
+    fn current_example(&self, value: &str) -> Example {
+        Example {
+            value: value.to_string(),
+            object_id: self.current_object_id.clone(),
+        }
+    }
+
     pub fn record_missing_enum_member(&mut self, enum_name: &str, member: &str) {
-        *(self
-            .missing_enum_members
-            .entry(enum_name.to_string())
-            .or_insert_with(HashMap::new)
-            .entry(member.to_string())
-            .or_insert(0)) += 1;
+        let id = self.current_object_id.clone();
+        problem_reservoir(&mut self.missing_enum_members, enum_name, member).add_if_some(id);
     }
 
     pub fn record_type_violation(&mut self, value: &str) {
         let discriminator = self
             .current_discriminator
-            .as_ref()
+            .clone()
             .expect("You should set a discriminator first.");
         let field = self
             .current_field
-            .as_ref()
+            .clone()
             .expect("You should set a current field first.");
-        self.type_violations
-            .entry(discriminator.to_string())
-            .or_insert_with(HashMap::new)
-            .entry(field.to_string())
-            .or_insert_with(Vec::new)
-            .push(value.to_string());
+        let example = self.current_example(value);
+        add_problem(&mut self.type_violations, &discriminator, &field, example);
     }
     pub fn record_missing_required_field(&mut self, discriminator: &str, field: &str) {
-        *(self
-            .missing_required_fields
-            .entry(discriminator.to_string())
-            .or_insert_with(HashMap::new)
-            .entry(field.to_string())
-            .or_insert(0)) += 1;
+        let id = self.current_object_id.clone();
+        problem_reservoir(&mut self.missing_required_fields, discriminator, field).add_if_some(id);
+    }
+
+    pub fn record_unknown_field(&mut self, discriminator: &str, field: &str, value: &str) {
+        let example = self.current_example(value);
+        add_problem(&mut self.unknown_fields, discriminator, field, example);
     }
 
-        pub fn record_unknown_field(&mut self, discriminator: &str, field: &str, value: &str) {
-        self.unknown_fields
-            .entry(discriminator.to_string())
-            .or_insert_with(HashMap::new)
-            .entry(field.to_string())
-            .or_insert_with(Vec::new)
-            .push(value.to_string());
+    pub fn record_geometry_fix(&mut self, discriminator: &str, fix: &str) {
+        let id = self.current_object_id.clone();
+        problem_reservoir(&mut self.geometry_fixes, discriminator, fix).add_if_some(id);
     }
 
     pub fn save_to_file(&self, path: &str) -> Result<()> {
-        let serialized = serde_json::to_string(&self)?;
-        let mut fp = File::create(&path)?;
-        write!(fp, "{}", serialized)?;
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    /// Appends what was recorded so far as a single line to the file and starts over, so a long running translation does not have to keep everything in memory.
+    pub fn append_to_file(&mut self, path: &str) -> Result<()> {
+        let mut writer = BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?);
+        self.write_to(&mut writer)?;
+        writeln!(writer)?;
+        writer.flush()?;
+        let mut empty = Self::new();
+        empty.current_discriminator = self.current_discriminator.take();
+        empty.current_field = self.current_field.take();
+        empty.current_object_id = self.current_object_id.take();
+        *self = empty;
         Ok(())
     }
 
-    pub fn merge_to(mut self, target: &mut Self) {
-        target.potentially_interesting_objects.append(&mut self.potentially_interesting_objects);
-        for (discriminator, missing) in self.missing_required_fields.into_iter() {
-            let target_fields = target.missing_required_fields.entry(discriminator).or_insert_with(HashMap::new);
-            for (field, occurrences) in missing.into_iter() {
-                *(target_fields
-                    .entry(field)
-                    .or_insert(0)) += occurrences;
-            }
-        }
-        for (discriminator, unknown) in self.unknown_fields.into_iter() {
-            let target_unknown = target.unknown_fields.entry(discriminator).or_insert_with(HashMap::new);
-            for (field, mut values) in unknown.into_iter() {
-                target_unknown.entry(field).or_insert_with(Vec::new).append(&mut values);
-            }
-        }
-        for (discriminator, members) in self.missing_enum_members.into_iter() {
-            let other_missing = target.missing_enum_members.entry(discriminator).or_insert_with(HashMap::new);
-            for (member, occurrences) in members.into_iter() {
-                *other_missing.entry(member).or_insert(0) += occurrences;
-            }
-        }
-        for (discriminator, violations) in self.type_violations.into_iter() {
-            let other_violations = target.type_violations.entry(discriminator).or_insert_with(HashMap::new);
-            for (field, mut values) in violations.into_iter() {
-                other_violations.entry(field).or_insert_with(Vec::new).append(&mut values);
-            }
+    pub fn merge_to(self, target: &mut Self) {
+        merge_problems(self.type_violations, &mut target.type_violations);
+        merge_problems(self.missing_enum_members, &mut target.missing_enum_members);
+        merge_problems(self.unknown_fields, &mut target.unknown_fields);
+        merge_problems(
+            self.missing_required_fields,
+            &mut target.missing_required_fields,
+        );
+        merge_problems(self.geometry_fixes, &mut target.geometry_fixes);
+        for (key, reservoir) in self.potentially_interesting_objects.into_iter() {
+            target
+                .potentially_interesting_objects
+                .entry(key)
+                .or_default()
+                .merge(reservoir);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unknown_field_count(record: &TranslationRecord) -> u64 {
+        record.unknown_fields()["Shop"]["colour"].count()
+    }
+
+    #[test]
+    fn appended_records_load_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("record.jsonl");
+        let path = path.to_str().unwrap();
+        let mut record = TranslationRecord::new();
+        record.record_unknown_field("Shop", "colour", "red");
+        record.append_to_file(path).unwrap();
+        assert!(record.unknown_fields().is_empty());
+        record.record_unknown_field("Shop", "colour", "blue");
+        record.record_missing_enum_member("ShopType", "bakery");
+        record.append_to_file(path).unwrap();
+        let loaded = TranslationRecord::load_from_file(path).unwrap();
+        assert_eq!(unknown_field_count(&loaded), 2);
+        assert_eq!(
+            loaded.missing_enum_members()["ShopType"]["bakery"].count(),
+            1
+        );
+    }
+
+    #[test]
+    fn saved_record_loads_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("record.json");
+        let path = path.to_str().unwrap();
+        let mut record = TranslationRecord::new();
+        record.record_unknown_field("Shop", "colour", "red");
+        record.save_to_file(path).unwrap();
+        let loaded = TranslationRecord::load_from_file(path).unwrap();
+        assert_eq!(unknown_field_count(&loaded), 1);
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+/// How many items a reservoir keeps at most.
+pub const SAMPLE_SIZE: usize = 16;
+
+/// Counts the items added to it and keeps a bounded uniform sample of them.
+///
+/// The randomness is derived from the item counts, so the sampling is deterministic and there's no generator state to serialize.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Reservoir<T> {
+    count: u64,
+    sample: Vec<T>,
+}
+
+// The splitmix64 finalizer, good enough to spread consecutive counts.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl<T> Reservoir<T> {
+    pub fn new() -> Self {
+        Self {
+            count: 0,
+            sample: Vec::new(),
+        }
+    }
+
+    /// A reservoir which only knows how many items were seen, used for records which did not keep any.
+    pub fn with_count(count: u64) -> Self {
+        Self {
+            count,
+            sample: Vec::new(),
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sample(&self) -> &[T] {
+        &self.sample
+    }
+
+    pub fn add(&mut self, item: T) {
+        self.count += 1;
+        if self.sample.len() < SAMPLE_SIZE {
+            self.sample.push(item);
+        } else {
+            let idx = (mix(self.count) % self.count) as usize;
+            if idx < SAMPLE_SIZE {
+                self.sample[idx] = item;
+            }
+        }
+    }
+
+    /// Counts the item, but keeps it in the sample only when there is one.
+    pub fn add_if_some(&mut self, item: Option<T>) {
+        match item {
+            Some(item) => self.add(item),
+            None => self.count += 1,
+        }
+    }
+
+    /// Merges the other reservoir into this one, each item of the result comes from one of them with a probability proportional to their counts.
+    pub fn merge(&mut self, other: Self) {
+        let total = self.count + other.count;
+        if self.sample.len() + other.sample.len() <= SAMPLE_SIZE {
+            self.sample.extend(other.sample);
+            self.count = total;
+            return;
+        }
+        let mut ours = std::mem::take(&mut self.sample).into_iter();
+        let mut theirs = other.sample.into_iter();
+        let mut merged = Vec::with_capacity(SAMPLE_SIZE);
+        for idx in 0..SAMPLE_SIZE {
+            let take_ours = total == 0 || mix(total + idx as u64) % total < self.count;
+            let item = if take_ours {
+                ours.next().or_else(|| theirs.next())
+            } else {
+                theirs.next().or_else(|| ours.next())
+            };
+            match item {
+                Some(item) => merged.push(item),
+                None => break,
+            }
+        }
+        self.sample = merged;
+        self.count = total;
+    }
+}
+
+impl<T> Default for Reservoir<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -3,21 +3,25 @@ use super::conversions;
 use super::record::TranslationRecord;
 use super::spec::TranslationSpec;
 use crate::entity::Entity;
+use crate::entity_relationship::RootedEntityRelationship;
+use crate::geometry_metrics::GeometryMetrics;
 use crate::Error;
 use hashbrown::HashMap;
 use log::{trace, warn};
 use osm_api::object::OSMObject;
 use osm_api::object_manager::OSMObjectManager;
+use osm_api::route::RouteMembers;
 use osm_api::SmolStr;
 use serde_json::Value;
 
-type RelatedIdsIterator = Box<dyn Iterator<Item = String>>;
+type RelationshipsIterator = Box<dyn Iterator<Item = RootedEntityRelationship>>;
 
 pub fn translate(
     object: &OSMObject,
     manager: &OSMObjectManager,
     record: &mut TranslationRecord,
-) -> Result<Option<(Entity, RelatedIdsIterator)>, Error> {
+) -> Result<Option<(Entity, RelationshipsIterator)>, Error> {
+    record.set_current_object_id(&object.unique_id());
     let lookup_res = TranslationSpec::primary_discriminator_for_object(object);
     match lookup_res {
         None => {
@@ -76,6 +80,20 @@ pub fn translate(
             entity_data.insert("changeset".to_string(), object.changeset.to_string());
             entity_data.insert("user".to_string(), object.user.clone());
             entity_data.insert("uid".to_string(), object.uid.to_string());
+            // The order of the members matters for routes, so they are kept as lists.
+            if discriminator == "Route" {
+                if let Some(members) = RouteMembers::of(object) {
+                    for (field, ids) in [
+                        ("stops", members.stops),
+                        ("platforms", members.platforms),
+                        ("routes", members.routes),
+                    ] {
+                        if !ids.is_empty() {
+                            entity_data.insert(field.to_string(), ids.join(";"));
+                        }
+                    }
+                }
+            }
             let mut converted_data =
                 conversions::convert_entity_data(&discriminator, &entity_data, record);
             // Address, must be done there, because we need to nest the object.
@@ -106,6 +124,9 @@ pub fn translate(
             let raw_data =
                 serde_json::to_string(&converted_data).expect("Could not serialize entity data.");
             let geometry = manager.get_geometry_as_wkb(object)?;
+            for fix in manager.geometry_fixes_of(&object.unique_id()) {
+                record.record_geometry_fix(&discriminator, fix.name());
+            }
             match geometry {
                 Some(geom) => {
                     let effective_width = calculate_effective_width(&discriminator, &entity_data);
@@ -117,8 +138,11 @@ pub fn translate(
                             geometry: geom,
                             data: raw_data,
                             parsed_data: None,
+                            metrics: GeometryMetrics::default(),
                         },
-                        Box::new(object.related_ids().map(|(id, _)| id)),
+                        Box::new(object.related_ids().enumerate().map(|(idx, (id, role))| {
+                            RootedEntityRelationship::member(&id, role.as_deref(), idx as u32)
+                        })),
                     )))
                 }
                 None => {
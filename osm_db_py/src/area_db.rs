@@ -2,6 +2,7 @@ use crate::entities_query::PyEntitiesQuery;
 use crate::entity::PyEntity;
 use crate::semantic_change::PySemanticChange;
 use osm_db::area_db::AreaDatabase;
+use osm_db::simplification::SimplificationLevel;
 use pyo3::exceptions;
 use pyo3::prelude::*;
 use std::sync::Mutex;
@@ -44,18 +45,24 @@ impl PyAreaDatabase {
             },
         )
     }
+    #[args(level = "\"full\"")]
     pub fn get_entities_really_intersecting(
         &self,
         candidate_ids: Vec<&str>,
         x: f64,
         y: f64,
         fast: bool,
+        level: &str,
     ) -> PyResult<Vec<PyEntity>> {
+        let level = level
+            .parse::<SimplificationLevel>()
+            .map_err(exceptions::PyValueError::new_err)?;
         match self.inner.lock().unwrap().get_entities_really_intersecting(
             &candidate_ids,
             x,
             y,
             fast,
+            level,
         ) {
             Ok(res) => Ok(res.into_iter().map(|e| PyEntity { inner: e }).collect()),
             Err(e) => Err(exceptions::PyValueError::new_err(format!(
@@ -104,6 +111,16 @@ impl PyAreaDatabase {
         }
     }
 
+    pub fn get_lines_stopping_at(&self, stop_id: &str) -> PyResult<Vec<PyEntity>> {
+        match self.inner.lock().unwrap().get_lines_stopping_at(stop_id) {
+            Ok(res) => Ok(res.into_iter().map(|e| PyEntity { inner: e }).collect()),
+            Err(e) => Err(exceptions::PyValueError::new_err(format!(
+                "Failed to get the lines, error: {}",
+                e
+            ))),
+        }
+    }
+
     pub fn get_child_count(&self, parent_id: &str) -> PyResult<u32> {
         match self.inner.lock().unwrap().get_child_count(parent_id) {
             Ok(num) => Ok(num),
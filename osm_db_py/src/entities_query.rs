@@ -1,5 +1,8 @@
 use crate::field_condition::PyFieldCondition;
 use osm_db::entities_query::EntitiesQuery;
+use osm_db::geometry_metrics::GeometryMetric;
+use osm_db::simplification::SimplificationLevel;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 
 #[pyclass(name = "EntitiesQuery")]
@@ -43,4 +46,25 @@ impl PyEntitiesQuery {
     pub fn set_parent_id(&mut self, id: &str) {
         self.inner.set_parent_id(id.to_string())
     }
+
+    pub fn set_ordered_by_member_index(&mut self, ordered: bool) {
+        self.inner.set_ordered_by_member_index(ordered)
+    }
+
+    #[args(descending = "false")]
+    pub fn add_metric_ordering(&mut self, metric: &str, descending: bool) -> PyResult<()> {
+        let metric = metric
+            .parse::<GeometryMetric>()
+            .map_err(PyValueError::new_err)?;
+        self.inner.add_metric_ordering(metric, descending);
+        Ok(())
+    }
+
+    pub fn set_simplification_level(&mut self, level: &str) -> PyResult<()> {
+        let level = level
+            .parse::<SimplificationLevel>()
+            .map_err(PyValueError::new_err)?;
+        self.inner.set_simplification_level(level);
+        Ok(())
+    }
 }
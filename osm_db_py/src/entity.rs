@@ -1,9 +1,14 @@
 use crate::conversions;
+use chrono::NaiveDateTime;
+use osm_db::access::TravelMode;
+use osm_db::conditional::ConditionContext;
 use osm_db::entity::Entity;
 use pyo3::basic::CompareOp;
+use pyo3::exceptions;
 use pyo3::prelude::*;
 use pyo3::types::PyBytes;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 
 #[pyclass(name = "Entity")]
@@ -35,6 +40,34 @@ impl PyEntity {
         self.inner.discriminator.as_str()
     }
 
+    #[getter]
+    pub fn geodesic_length(&self) -> Option<f64> {
+        self.inner.metrics.length
+    }
+
+    #[getter]
+    pub fn geodesic_area(&self) -> Option<f64> {
+        self.inner.metrics.area
+    }
+
+    #[getter]
+    pub fn centroid(&self) -> Option<(f64, f64)> {
+        self.inner
+            .metrics
+            .centroid_lon
+            .zip(self.inner.metrics.centroid_lat)
+    }
+
+    #[getter]
+    pub fn start_bearing(&self) -> Option<f64> {
+        self.inner.metrics.start_bearing
+    }
+
+    #[getter]
+    pub fn end_bearing(&self) -> Option<f64> {
+        self.inner.metrics.end_bearing
+    }
+
     pub fn value_of_field(&mut self, key: &str) -> PyObject {
         let gil = Python::acquire_gil();
         let py = gil.python();
@@ -50,6 +83,62 @@ impl PyEntity {
         self.inner.is_road_like()
     }
 
+    /// Returns None when the field is missing, or when the state at the given local ISO 8601 time is unknown.
+    pub fn is_open_at(&mut self, key: &str, time: &str) -> PyResult<Option<bool>> {
+        let time = parse_time(time)?;
+        Ok(self
+            .inner
+            .opening_hours_of(key)
+            .and_then(|h| h.is_open_at(time)))
+    }
+
+    pub fn next_opening_hours_change(&mut self, key: &str, time: &str) -> PyResult<Option<String>> {
+        let time = parse_time(time)?;
+        Ok(self
+            .inner
+            .opening_hours_of(key)
+            .and_then(|h| h.next_change_after(time))
+            .map(|t| t.format("%Y-%m-%dT%H:%M:%S").to_string()))
+    }
+
+    pub fn opening_hours_summary(&mut self, key: &str, language: &str) -> Option<String> {
+        self.inner
+            .opening_hours_of(key)
+            .map(|h| h.weekly_summary(language))
+    }
+
+    pub fn next_stops(&mut self, stop_id: &str) -> Vec<String> {
+        self.inner.next_stops(stop_id)
+    }
+
+    /// Returns None when the access tags don't say whether the given mode (e.g. foot or bicycle) may use the entity.
+    #[args(time = "None", properties = "None", flags = "None")]
+    pub fn is_usable_by(
+        &mut self,
+        mode: &str,
+        time: Option<&str>,
+        properties: Option<HashMap<String, f64>>,
+        flags: Option<HashSet<String>>,
+    ) -> PyResult<Option<bool>> {
+        let mode = TravelMode::from_name(mode).ok_or_else(|| {
+            exceptions::PyValueError::new_err(format!("Unknown travel mode {}", mode))
+        })?;
+        let context = condition_context(time, properties, flags)?;
+        Ok(self.inner.is_usable_by(mode, &context))
+    }
+
+    /// Returns the speed limit in km/h, the conditional one when it applies under the given circumstances.
+    #[args(time = "None", properties = "None", flags = "None")]
+    pub fn maxspeed(
+        &mut self,
+        time: Option<&str>,
+        properties: Option<HashMap<String, f64>>,
+        flags: Option<HashSet<String>>,
+    ) -> PyResult<Option<f64>> {
+        let context = condition_context(time, properties, flags)?;
+        Ok(self.inner.maxspeed_in(&context))
+    }
+
     fn __hash__(&self) -> PyResult<u64> {
         let mut hasher = DefaultHasher::new();
         self.inner.id.hash(&mut hasher);
@@ -71,3 +160,24 @@ impl PyEntity {
     }
 
 }
+
+fn parse_time(time: &str) -> PyResult<NaiveDateTime> {
+    time.parse().map_err(|e| {
+        exceptions::PyValueError::new_err(format!(
+            "Could not parse {} as an ISO 8601 time, error: {}",
+            time, e
+        ))
+    })
+}
+
+fn condition_context(
+    time: Option<&str>,
+    properties: Option<HashMap<String, f64>>,
+    flags: Option<HashSet<String>>,
+) -> PyResult<ConditionContext> {
+    Ok(ConditionContext {
+        time: time.map(parse_time).transpose()?,
+        properties: properties.unwrap_or_default(),
+        flags: flags.unwrap_or_default(),
+    })
+}
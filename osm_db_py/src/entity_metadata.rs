@@ -56,6 +56,14 @@ impl PyEntityMetadata {
     pub fn parent_metadata(&self) -> Option<Self> {
         self.inner.parent_metadata().map(|m| Self { inner: m })
     }
+
+    pub fn localized_name(&self, language: &str) -> String {
+        self.inner.localized_name(language)
+    }
+
+    pub fn localized_field_name(&self, field: &str, language: &str) -> String {
+        self.inner.localized_field_name(field, language)
+    }
 }
 
 #[pyclass(name = "Field")]
@@ -105,6 +113,10 @@ impl PyEnum {
         self.inner.name_for_value(value)
     }
 
+    pub fn localized_name_for_value(&self, value: i32, language: &str) -> Option<String> {
+        self.inner.localized_name_for_value(value, language)
+    }
+
     #[getter]
     pub fn members(&self, py: Python) -> PyResult<PyObject> {
         let dict = PyDict::new(py);
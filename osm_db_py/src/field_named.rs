@@ -1,27 +1,49 @@
 use crate::conversions;
 use crate::field_condition::PyFieldCondition;
 use osm_db::entities_query_condition::{Condition, FieldCondition};
+use osm_db::geometry_metrics::GeometryMetric;
 use pyo3::exceptions;
 use pyo3::prelude::*;
 
 #[pyclass]
 pub struct FieldNamed {
     name: String,
+    metric: Option<GeometryMetric>,
+}
+
+impl FieldNamed {
+    fn condition(&self, condition: Condition) -> FieldCondition {
+        match self.metric {
+            Some(metric) => FieldCondition::on_metric(metric, condition),
+            None => FieldCondition::new(self.name.clone(), condition),
+        }
+    }
 }
 
 #[pymethods]
 impl FieldNamed {
     #[new]
     pub fn new(name: String) -> Self {
-        FieldNamed { name }
+        FieldNamed { name, metric: None }
+    }
+
+    /// A geometry metric, e.g. `length` or `start_bearing`, instead of a data field.
+    #[staticmethod]
+    pub fn metric(name: String) -> PyResult<Self> {
+        let metric = name
+            .parse::<GeometryMetric>()
+            .map_err(exceptions::PyValueError::new_err)?;
+        Ok(FieldNamed {
+            name,
+            metric: Some(metric),
+        })
     }
 
     pub fn eq(&self, val: PyObject, py: Python) -> PyResult<PyFieldCondition> {
         if let Some(converted) = conversions::convert_object_for_query(&val, py) {
-            Ok(PyFieldCondition::new(FieldCondition::new(
-                self.name.clone(),
-                Condition::Eq { value: converted },
-            )))
+            Ok(PyFieldCondition::new(
+                self.condition(Condition::Eq { value: converted }),
+            ))
         } else {
             Err(exceptions::PyTypeError::new_err(
                 "Unsupported type for query",
@@ -31,10 +53,9 @@ impl FieldNamed {
 
     pub fn neq(&self, val: PyObject, py: Python) -> PyResult<PyFieldCondition> {
         if let Some(converted) = conversions::convert_object_for_query(&val, py) {
-            Ok(PyFieldCondition::new(FieldCondition::new(
-                self.name.clone(),
-                Condition::Neq { value: converted },
-            )))
+            Ok(PyFieldCondition::new(
+                self.condition(Condition::Neq { value: converted }),
+            ))
         } else {
             Err(exceptions::PyTypeError::new_err(
                 "Unsupported type for query",
@@ -43,10 +64,9 @@ impl FieldNamed {
     }
     pub fn lt(&self, val: PyObject, py: Python) -> PyResult<PyFieldCondition> {
         if let Some(converted) = conversions::convert_object_for_query(&val, py) {
-            Ok(PyFieldCondition::new(FieldCondition::new(
-                self.name.clone(),
-                Condition::Lt { value: converted },
-            )))
+            Ok(PyFieldCondition::new(
+                self.condition(Condition::Lt { value: converted }),
+            ))
         } else {
             Err(exceptions::PyTypeError::new_err(
                 "Unsupported type for query",
@@ -56,10 +76,9 @@ impl FieldNamed {
 
     pub fn le(&self, val: PyObject, py: Python) -> PyResult<PyFieldCondition> {
         if let Some(converted) = conversions::convert_object_for_query(&val, py) {
-            Ok(PyFieldCondition::new(FieldCondition::new(
-                self.name.clone(),
-                Condition::Le { value: converted },
-            )))
+            Ok(PyFieldCondition::new(
+                self.condition(Condition::Le { value: converted }),
+            ))
         } else {
             Err(exceptions::PyTypeError::new_err(
                 "Unsupported type for query",
@@ -68,10 +87,9 @@ impl FieldNamed {
     }
     pub fn gt(&self, val: PyObject, py: Python) -> PyResult<PyFieldCondition> {
         if let Some(converted) = conversions::convert_object_for_query(&val, py) {
-            Ok(PyFieldCondition::new(FieldCondition::new(
-                self.name.clone(),
-                Condition::Gt { value: converted },
-            )))
+            Ok(PyFieldCondition::new(
+                self.condition(Condition::Gt { value: converted }),
+            ))
         } else {
             Err(exceptions::PyTypeError::new_err(
                 "Unsupported type for query",
@@ -80,10 +98,9 @@ impl FieldNamed {
     }
     pub fn ge(&self, val: PyObject, py: Python) -> PyResult<PyFieldCondition> {
         if let Some(converted) = conversions::convert_object_for_query(&val, py) {
-            Ok(PyFieldCondition::new(FieldCondition::new(
-                self.name.clone(),
-                Condition::Ge { value: converted },
-            )))
+            Ok(PyFieldCondition::new(
+                self.condition(Condition::Ge { value: converted }),
+            ))
         } else {
             Err(exceptions::PyTypeError::new_err(
                 "Unsupported type for query",
@@ -92,10 +109,31 @@ impl FieldNamed {
     }
     pub fn like(&self, val: PyObject, py: Python) -> PyResult<PyFieldCondition> {
         if let Some(converted) = conversions::convert_object_for_query(&val, py) {
-            Ok(PyFieldCondition::new(FieldCondition::new(
-                self.name.clone(),
-                Condition::Like { value: converted },
-            )))
+            Ok(PyFieldCondition::new(
+                self.condition(Condition::Like { value: converted }),
+            ))
+        } else {
+            Err(exceptions::PyTypeError::new_err(
+                "Unsupported type for query",
+            ))
+        }
+    }
+    pub fn contains(&self, val: PyObject, py: Python) -> PyResult<PyFieldCondition> {
+        if let Some(converted) = conversions::convert_object_for_query(&val, py) {
+            Ok(PyFieldCondition::new(
+                self.condition(Condition::Contains { value: converted }),
+            ))
+        } else {
+            Err(exceptions::PyTypeError::new_err(
+                "Unsupported type for query",
+            ))
+        }
+    }
+    pub fn does_not_contain(&self, val: PyObject, py: Python) -> PyResult<PyFieldCondition> {
+        if let Some(converted) = conversions::convert_object_for_query(&val, py) {
+            Ok(PyFieldCondition::new(
+                self.condition(Condition::DoesNotContain { value: converted }),
+            ))
         } else {
             Err(exceptions::PyTypeError::new_err(
                 "Unsupported type for query",
@@ -103,9 +141,9 @@ impl FieldNamed {
         }
     }
     pub fn is_null(&self) -> PyFieldCondition {
-        PyFieldCondition::new(FieldCondition::new(self.name.clone(), Condition::IsNull))
+        PyFieldCondition::new(self.condition(Condition::IsNull))
     }
     pub fn is_not_null(&self) -> PyFieldCondition {
-        PyFieldCondition::new(FieldCondition::new(self.name.clone(), Condition::IsNotNull))
+        PyFieldCondition::new(self.condition(Condition::IsNotNull))
     }
 }
@@ -47,5 +47,6 @@ fn osm_db(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<field_condition::PyFieldCondition>()?;
     m.add_class::<field_named::FieldNamed>()?;
     m.add_class::<area_db::PyAreaDatabase>()?;
+    m.add("CHANGES_VERSION", osm_db::semantic_change::CHANGES_VERSION)?;
     Ok(())
 }
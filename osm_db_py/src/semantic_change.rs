@@ -1,8 +1,9 @@
 use crate::dict_change::DictChange;
 use crate::ChangeType;
-use osm_db::semantic_change::SemanticChange;
+use osm_db::semantic_change::{ChangeFormat, SemanticChange};
 use pyo3::exceptions;
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
 use serde_json::Value;
 
 #[pyclass(name = "SemanticChange")]
@@ -12,12 +13,33 @@ pub struct PySemanticChange {
 
 #[pymethods]
 impl PySemanticChange {
+    /// Accepts both the JSON and the binary changes.
     #[staticmethod]
     fn from_serialized(data: &[u8]) -> PyResult<PySemanticChange> {
         match SemanticChange::from_serialized(data) {
             Ok(change) => Ok(PySemanticChange { inner: change }),
             Err(e) => Err(exceptions::PyValueError::new_err(format!(
-                "Could not parse json, error: {}",
+                "Could not parse the change, error: {}",
+                e
+            ))),
+        }
+    }
+
+    #[args(binary = "false")]
+    fn serialize(&self, binary: bool) -> PyResult<PyObject> {
+        let format = if binary {
+            ChangeFormat::Binary
+        } else {
+            ChangeFormat::Json
+        };
+        match self.inner.serialize_as(format) {
+            Ok(data) => {
+                let gil = Python::acquire_gil();
+                let py = gil.python();
+                Ok(PyBytes::new(py, &data).into())
+            }
+            Err(e) => Err(exceptions::PyValueError::new_err(format!(
+                "Could not serialize the change, error: {}",
                 e
             ))),
         }
@@ -0,0 +1,207 @@
+//! Answers of the queries which the osm_api crate sends, taken from the fixture directory.
+//!
+//! The directory looks like the following, every part is optional:
+//! - `areas/<area id>.json` - the Overpass JSON answer of the area data query
+//! - `adiff/<area id>_<node|way|rel>.xml` - the augmented diff answers of the area update queries
+//! - `objects/*.json` - more elements in the Overpass JSON format, e.g. the parent relations of the areas
+//!
+//! The queries for objects by their ids and for the parent relations are answered from all the elements of the JSON files.
+use anyhow::{Context, Result};
+use log::warn;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+static ADIFF_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\[adiff:.*area\((\d+)\);(node|way|rel)\(area\)").unwrap());
+static AREA_DATA_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"area\((\d+)\);node\(area\)").unwrap());
+static PARENTS_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"rel\((\d+)\);<<;").unwrap());
+static IDS_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(node|way|relation)\(id:([\d,]+)\)").unwrap());
+
+const EMPTY_ADIFF: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<osm version=\"0.6\" generator=\"Overpass API stand-in\">\n<note>The data included in this document is from www.openstreetmap.org. The data is made available under ODbL.</note>\n</osm>\n";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Xml,
+}
+
+pub struct Answer {
+    pub format: Format,
+    pub body: Vec<u8>,
+}
+
+type ElementKey = (String, u64);
+
+pub struct Fixtures {
+    dir: PathBuf,
+    elements: HashMap<ElementKey, Value>,
+    /// Appended to every JSON answer, to simulate the runtime errors of the queries.
+    remark: Option<String>,
+}
+
+fn elements_of(path: &Path) -> Result<Vec<Value>> {
+    let text = fs::read_to_string(path)?;
+    let mut response: Value =
+        serde_json::from_str(&text).with_context(|| format!("Invalid fixture {:?}", path))?;
+    match response.get_mut("elements").map(Value::take) {
+        Some(Value::Array(elements)) => Ok(elements),
+        _ => Ok(vec![]),
+    }
+}
+
+fn key_of(element: &Value) -> Option<ElementKey> {
+    Some((
+        element.get("type")?.as_str()?.to_string(),
+        element.get("id")?.as_u64()?,
+    ))
+}
+
+fn type_order(element_type: &str) -> u8 {
+    match element_type {
+        "node" => 0,
+        "way" => 1,
+        _ => 2,
+    }
+}
+
+impl Fixtures {
+    pub fn load(dir: &Path, remark: Option<String>) -> Result<Self> {
+        let mut elements = HashMap::new();
+        for subdir in &["areas", "objects"] {
+            let path = dir.join(subdir);
+            if !path.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(path)? {
+                let path = entry?.path();
+                if path.extension().is_none_or(|e| e != "json") {
+                    continue;
+                }
+                for element in elements_of(&path)? {
+                    if let Some(key) = key_of(&element) {
+                        elements.insert(key, element);
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            elements,
+            remark,
+        })
+    }
+
+    pub fn element_count(&self) -> usize {
+        self.elements.len()
+    }
+
+    fn json_answer(&self, mut elements: Vec<&Value>) -> Result<Answer> {
+        elements
+            .sort_by_key(|e| key_of(e).map(|(element_type, id)| (type_order(&element_type), id)));
+        let mut response = json!({
+            "version": 0.6,
+            "generator": "Overpass API stand-in",
+            "elements": elements,
+        });
+        if let Some(remark) = &self.remark {
+            response["remark"] = Value::String(remark.clone());
+        }
+        Ok(Answer {
+            format: Format::Json,
+            body: serde_json::to_vec(&response)?,
+        })
+    }
+
+    fn area_answer(&self, area: &str) -> Result<Answer> {
+        let path = self.dir.join("areas").join(format!("{}.json", area));
+        if !path.exists() {
+            warn!(
+                "No fixture for the area {}, answering with no elements.",
+                area
+            );
+            return self.json_answer(vec![]);
+        }
+        if self.remark.is_none() {
+            return Ok(Answer {
+                format: Format::Json,
+                body: fs::read(path)?,
+            });
+        }
+        let elements = elements_of(&path)?;
+        self.json_answer(elements.iter().collect())
+    }
+
+    fn adiff_answer(&self, area: &str, kind: &str) -> Result<Answer> {
+        let path = self
+            .dir
+            .join("adiff")
+            .join(format!("{}_{}.xml", area, kind));
+        let body = if path.exists() {
+            fs::read(path)?
+        } else {
+            EMPTY_ADIFF.as_bytes().to_vec()
+        };
+        Ok(Answer {
+            format: Format::Xml,
+            body,
+        })
+    }
+
+    fn ids_answer(&self, element_type: &str, ids: &str) -> Result<Answer> {
+        let mut found = vec![];
+        for id in ids.split(',').filter_map(|id| id.parse::<u64>().ok()) {
+            match self.elements.get(&(element_type.to_string(), id)) {
+                Some(element) => found.push(element),
+                None => warn!("The {} {} is not in the fixtures.", element_type, id),
+            }
+        }
+        self.json_answer(found)
+    }
+
+    /// The relations which have the given one as a member, directly or through other relations.
+    fn parents_answer(&self, relation_id: u64) -> Result<Answer> {
+        let mut parents = vec![];
+        let mut seen = HashSet::new();
+        let mut pending = vec![relation_id];
+        while let Some(child) = pending.pop() {
+            for ((element_type, id), element) in &self.elements {
+                if element_type != "relation" || seen.contains(id) {
+                    continue;
+                }
+                let has_child = element["members"].as_array().is_some_and(|members| {
+                    members
+                        .iter()
+                        .any(|m| m["type"] == "relation" && m["ref"].as_u64() == Some(child))
+                });
+                if has_child {
+                    seen.insert(*id);
+                    parents.push(element);
+                    pending.push(*id);
+                }
+            }
+        }
+        self.json_answer(parents)
+    }
+
+    /// Answers a query, None means that the stand-in does not understand it.
+    pub fn answer(&self, query: &str) -> Result<Option<Answer>> {
+        if let Some(caps) = ADIFF_RE.captures(query) {
+            return self.adiff_answer(&caps[1], &caps[2]).map(Some);
+        }
+        if let Some(caps) = AREA_DATA_RE.captures(query) {
+            return self.area_answer(&caps[1]).map(Some);
+        }
+        if let Some(caps) = PARENTS_RE.captures(query) {
+            return self.parents_answer(caps[1].parse()?).map(Some);
+        }
+        if let Some(caps) = IDS_RE.captures(query) {
+            return self.ids_answer(&caps[1], &caps[2]).map(Some);
+        }
+        Ok(None)
+    }
+}
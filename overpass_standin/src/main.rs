@@ -0,0 +1,131 @@
+//! A local stand-in of an Overpass API server, answering the queries of the osm_api crate from fixture files, so the area creation and updates can run offline.
+//!
+//! Point the area tools at it with e.g. `OVERPASS_SERVERS=http://localhost:8090`.
+#[macro_use]
+extern crate rocket;
+#[macro_use]
+extern crate log;
+
+mod fixtures;
+mod slots;
+
+use chrono::Utc;
+use clap::Parser;
+use fixtures::{Answer, Fixtures, Format};
+use rocket::data::{Limits, ToByteUnit};
+use rocket::form::Form;
+use rocket::http::{ContentType, Status};
+use rocket::tokio::time::sleep;
+use rocket::State;
+use slots::Slots;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[clap(about = "A local stand-in of an Overpass API server")]
+struct Args {
+    /// The directory with the answers of the queries
+    fixtures: PathBuf,
+    #[clap(long, default_value = "8090")]
+    port: u16,
+    /// The number of the concurrent query slots, zero for no limit
+    #[clap(long, default_value = "2")]
+    rate_limit: usize,
+    /// For how many seconds after its start a query keeps its slot taken
+    #[clap(long, default_value = "2")]
+    slot_duration: i64,
+    /// A delay of every interpreter answer, in milliseconds
+    #[clap(long, default_value = "0")]
+    delay: u64,
+    /// A remark added to every JSON answer, e.g. "runtime error: Query timed out"
+    #[clap(long)]
+    remark: Option<String>,
+}
+
+struct StandIn {
+    fixtures: Fixtures,
+    slots: Slots,
+    delay: Duration,
+}
+
+#[derive(FromForm)]
+struct InterpreterQuery {
+    data: String,
+}
+
+#[get("/status")]
+fn status(standin: &State<StandIn>) -> String {
+    standin.slots.status(Utc::now())
+}
+
+async fn interpret(query: &str, standin: &StandIn) -> Result<(ContentType, Vec<u8>), Status> {
+    debug!("Received query {}", query);
+    if !standin.slots.take(Utc::now()) {
+        warn!("Refusing a query, all the slots are taken.");
+        return Err(Status::TooManyRequests);
+    }
+    sleep(standin.delay).await;
+    match standin.fixtures.answer(query) {
+        Ok(Some(Answer { format, body })) => {
+            let content_type = match format {
+                Format::Json => ContentType::JSON,
+                Format::Xml => ContentType::XML,
+            };
+            Ok((content_type, body))
+        }
+        Ok(None) => {
+            warn!("Unsupported query {}", query);
+            Err(Status::BadRequest)
+        }
+        Err(e) => {
+            error!("Failed to answer query {}: {:?}", query, e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[post("/interpreter", data = "<query>")]
+async fn interpreter(
+    query: Form<InterpreterQuery>,
+    standin: &State<StandIn>,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    interpret(&query.data, standin).await
+}
+
+#[get("/interpreter?<data>")]
+async fn interpreter_get(
+    data: String,
+    standin: &State<StandIn>,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    interpret(&data, standin).await
+}
+
+#[launch]
+fn rocket() -> _ {
+    env_logger::Builder::from_env("FTS_LOG")
+        .format_timestamp(None)
+        .init();
+    let args = Args::parse();
+    let fixtures =
+        Fixtures::load(&args.fixtures, args.remark).expect("Could not load the fixtures");
+    info!(
+        "Loaded {} elements from the fixtures in {:?}.",
+        fixtures.element_count(),
+        args.fixtures
+    );
+    // The object lookups list thousands of ids.
+    let limits = Limits::default().limit("form", 64.mebibytes());
+    let figment = rocket::Config::figment()
+        .merge(("port", args.port))
+        .merge(("limits", limits));
+    rocket::custom(figment)
+        .manage(StandIn {
+            fixtures,
+            slots: Slots::new(
+                args.rate_limit,
+                chrono::Duration::seconds(args.slot_duration),
+            ),
+            delay: Duration::from_millis(args.delay),
+        })
+        .mount("/api", routes![status, interpreter, interpreter_get])
+}
@@ -0,0 +1,71 @@
+use chrono::{DateTime, Duration, Timelike, Utc};
+use std::sync::Mutex;
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// The status reports whole seconds, so the slots get free at whole seconds, otherwise a client trusting the status would come too early.
+fn next_whole_second(time: DateTime<Utc>) -> DateTime<Utc> {
+    match time.nanosecond() {
+        0 => time,
+        nanos => time + Duration::nanoseconds(1_000_000_000 - i64::from(nanos)),
+    }
+}
+
+/// The query slots of a client, each query occupies one for a fixed time after its start, like the Overpass cooldown.
+pub struct Slots {
+    rate_limit: usize,
+    duration: Duration,
+    busy_until: Mutex<Vec<DateTime<Utc>>>,
+}
+
+impl Slots {
+    /// A rate limit of zero means no limit, as it does for the real servers.
+    pub fn new(rate_limit: usize, duration: Duration) -> Self {
+        Self {
+            rate_limit,
+            duration,
+            busy_until: Mutex::new(vec![]),
+        }
+    }
+
+    /// Takes a slot for a query, false means the client should have waited.
+    pub fn take(&self, now: DateTime<Utc>) -> bool {
+        let mut busy_until = self.busy_until.lock().unwrap();
+        busy_until.retain(|until| *until > now);
+        if self.rate_limit > 0 && busy_until.len() >= self.rate_limit {
+            return false;
+        }
+        busy_until.push(next_whole_second(now + self.duration));
+        true
+    }
+
+    /// The text of the `/api/status` endpoint, in the format of the real servers.
+    pub fn status(&self, now: DateTime<Utc>) -> String {
+        let mut busy_until = self.busy_until.lock().unwrap();
+        busy_until.retain(|until| *until > now);
+        busy_until.sort();
+        let mut lines = vec![
+            "Connected as: 2130706433".to_string(),
+            format!("Current time: {}", now.format(TIME_FORMAT)),
+            "Announced endpoint: none".to_string(),
+            format!("Rate limit: {}", self.rate_limit),
+        ];
+        let available = self.rate_limit.saturating_sub(busy_until.len());
+        if self.rate_limit == 0 || available > 0 {
+            lines.push(format!("{} slots available now.", available));
+        }
+        if self.rate_limit > 0 {
+            for until in busy_until.iter() {
+                lines.push(format!(
+                    "Slot available after: {}, in {} seconds.",
+                    until.format(TIME_FORMAT),
+                    (*until - now).num_seconds()
+                ));
+            }
+        }
+        lines.push(
+            "Currently running queries (pid, space limit, time limit, start time):".to_string(),
+        );
+        lines.join("\n") + "\n"
+    }
+}
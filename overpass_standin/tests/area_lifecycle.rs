@@ -0,0 +1,172 @@
+//! Drives the area creation and update of the osm_api crate and the server against the stand-in and the fixtures next to this file.
+use chrono::{TimeZone, Utc};
+use diesel::{Connection, SqliteConnection};
+use osm_api::object_manager::OSMObjectManager;
+use osm_api::overpass_api::Servers;
+use osm_db::area_db::AreaDatabase;
+use server::area::{Area, AreaState};
+use server::background_tasks::area_db_creation::create_area_database;
+use server::background_tasks::area_db_update::update_area;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const AREA: i64 = 3_600_000_001;
+const SLOT_DURATION: u64 = 1;
+
+/// The tests working in a temporary directory change the current directory of the whole process.
+static WORKDIR_LOCK: Mutex<()> = Mutex::new(());
+
+/// Kills the stand-in when the test ends, even by a panic.
+struct StandIn(Child);
+
+impl Drop for StandIn {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("Could not find a free port")
+        .port()
+}
+
+fn start_standin(port: u16, rate_limit: usize) -> StandIn {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let child = Command::new(env!("CARGO_BIN_EXE_overpass_standin"))
+        .arg(fixtures)
+        .args(["--port", &port.to_string()])
+        .args(["--rate-limit", &rate_limit.to_string()])
+        .args(["--slot-duration", &SLOT_DURATION.to_string()])
+        .spawn()
+        .expect("Could not start the stand-in");
+    let standin = StandIn(child);
+    let started = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(
+            started.elapsed() < Duration::from_secs(30),
+            "The stand-in did not start listening"
+        );
+        thread::sleep(Duration::from_millis(100));
+    }
+    standin
+}
+
+/// Enters a temporary directory with the cache dictionary and the given files of the repository.
+fn enter_workdir(repository_files: &[&str]) -> TempDir {
+    let workdir = tempfile::tempdir().unwrap();
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // The cache compresses the objects with the fts dictionaries from the working directory.
+    fs::copy(
+        manifest_dir.join("tests/fts.dict"),
+        workdir.path().join("fts.dict"),
+    )
+    .unwrap();
+    for file in repository_files {
+        fs::copy(
+            manifest_dir.join("..").join(file),
+            workdir.path().join(file),
+        )
+        .unwrap();
+    }
+    env::set_current_dir(workdir.path()).unwrap();
+    workdir
+}
+
+#[test]
+fn cache_follows_the_area_data() {
+    let _lock = WORKDIR_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let _workdir = enter_workdir(&[]);
+    let port = free_port();
+    let _standin = start_standin(port, 0);
+    let servers = Servers::with_server_urls(vec![format!("http://127.0.0.1:{}", port)]);
+    let cache = sled::Config::new().temporary(true).open().unwrap();
+    let manager = OSMObjectManager::new_multithread(Arc::new(servers), Arc::new(cache)).unwrap();
+
+    // The creation caches the data of the area, its parents and the missing dependencies.
+    manager.lookup_objects_in(AREA).unwrap();
+    let mut cached: Vec<String> = manager
+        .cached_objects()
+        .map(|o| o.unique_id().to_string())
+        .collect();
+    cached.sort();
+    assert_eq!(cached, vec!["n1", "n2", "n3", "r1", "w10"]);
+    let parents = manager.get_area_parents(AREA).unwrap();
+    let parent_ids: Vec<String> = parents.iter().map(|o| o.unique_id().to_string()).collect();
+    assert_eq!(parent_ids, vec!["r100"]);
+    let way = manager.get_object("w10").unwrap().unwrap();
+    assert!(manager.get_geometry_as_wkb(&way).unwrap().is_some());
+    assert!(manager.get_object("n4").unwrap().is_some());
+
+    let after = Utc.ymd(2022, 5, 1).and_hms(12, 0, 0);
+    let changes: Vec<_> = manager
+        .lookup_differences_in(AREA, &after)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(changes.len(), 3);
+}
+
+#[test]
+fn dispatcher_waits_for_a_free_slot() {
+    let port = free_port();
+    let _standin = start_standin(port, 1);
+    let servers = Servers::with_server_urls(vec![format!("http://127.0.0.1:{}", port)]);
+    let started = Instant::now();
+    for _ in 0..3 {
+        let mut response = String::new();
+        servers
+            .run_query("[out:json];node(id:1);out;", false)
+            .unwrap()
+            .read_to_string(&mut response)
+            .unwrap();
+        assert!(response.contains("bench"));
+    }
+    // A query sent before its slot got free would be refused and counted as a failure.
+    let health = &servers.health()[0];
+    assert_eq!(health.failures, 0);
+    assert_eq!(health.successes, 3);
+    assert!(started.elapsed() >= Duration::from_secs(2 * SLOT_DURATION));
+}
+
+#[test]
+#[ignore = "needs the mod_spatialite SQLite extension and a Redis server at REDIS_URL"]
+fn area_creation_and_update() {
+    let _lock = WORKDIR_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let _workdir = enter_workdir(&["entities.yml", "enums.yml", "translation_specs.yml"]);
+    let port = free_port();
+    let _standin = start_standin(port, 0);
+    env::set_var("OVERPASS_SERVERS", format!("http://127.0.0.1:{}", port));
+    let conn = SqliteConnection::establish("server.db").unwrap();
+    server::run_migrations(&conn).unwrap();
+    Area::create(AREA, "Testville", &conn).unwrap();
+
+    create_area_database(AREA).unwrap();
+    let area = Area::find_by_osm_id(AREA, &conn).unwrap();
+    assert_eq!(area.state, AreaState::Updated);
+    assert_eq!(area.parent_osm_ids.as_deref(), Some("r100"));
+    let area_db = AreaDatabase::open_existing(AREA, true).unwrap();
+    assert!(area_db.has_entity("n1").unwrap());
+    assert!(area_db.has_entity("n2").unwrap());
+    drop(area_db);
+
+    // The update applies the fixture differences: the bench got a backrest, the waste basket was removed and a drinking water appeared.
+    let manager = Arc::new(OSMObjectManager::new().unwrap());
+    update_area(area, Arc::new(Mutex::new(conn)), manager).unwrap();
+    let area_db = AreaDatabase::open_existing(AREA, true).unwrap();
+    let bench = area_db.get_entity("n1").unwrap().unwrap();
+    let data: serde_json::Value = serde_json::from_str(&bench.data).unwrap();
+    assert_eq!(data["backrest"], true);
+    assert!(!area_db.has_entity("n2").unwrap());
+    assert!(area_db.has_entity("n5").unwrap());
+}
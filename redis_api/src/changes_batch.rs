@@ -5,6 +5,8 @@ use osm_db::semantic_change::SemanticChange;
 pub struct ChangesBatch<'a> {
     stream: &'a mut ChangesStream,
     num_changes: u64,
+    /// The version of the changes understood by all the clients, found out with the first change.
+    version: Option<u32>,
 }
 
 impl<'a> ChangesBatch<'a> {
@@ -12,11 +14,16 @@ impl<'a> ChangesBatch<'a> {
         Self {
             stream,
             num_changes: 0,
+            version: None,
         }
     }
 
     pub fn add_change(&mut self, change: &SemanticChange) -> Result<()> {
-        match self.stream.add_change(change) {
+        let version = match self.version {
+            Some(version) => version,
+            None => *self.version.insert(self.stream.changes_version()?),
+        };
+        match self.stream.add_change(change, version) {
             Ok(()) => {
                 self.num_changes += 1;
                 Ok(())
@@ -1,8 +1,8 @@
 use crate::changes_batch::ChangesBatch;
 use crate::Result;
-use osm_db::semantic_change::SemanticChange;
+use osm_db::semantic_change::{SemanticChange, LEGACY_CHANGES_VERSION};
 use redis::acl::Rule;
-use redis::streams::{StreamInfoGroupsReply, StreamMaxlen};
+use redis::streams::{StreamInfoGroupsReply, StreamMaxlen, StreamRangeReply};
 use redis::{Client, Commands, Connection};
 use std::collections::HashMap;
 use std::env;
@@ -41,12 +41,39 @@ impl ChangesStream {
         format!("fts.{}.change_counts", self.area_id)
     }
 
-    pub(crate) fn add_change(&mut self, change: &SemanticChange) -> Result<()> {
-        self.redis_connection
-            .xadd(self.changes_key(), "*", &[("c", change.serialize()?)])?;
+    fn changes_versions_key(&self) -> String {
+        format!("fts.{}.changes_versions", self.area_id)
+    }
+
+    pub(crate) fn add_change(&mut self, change: &SemanticChange, version: u32) -> Result<()> {
+        let _: () = self.redis_connection.xadd(
+            self.changes_key(),
+            "*",
+            &[("c", change.serialize_for(version)?)],
+        )?;
+        Ok(())
+    }
+
+    /// Records the newest version of the changes which the client understands.
+    pub fn set_changes_version_for(&mut self, client_id: &str, version: u32) -> Result<()> {
+        let _: () = self
+            .redis_connection
+            .hset(self.changes_versions_key(), client_id, version)?;
         Ok(())
     }
 
+    /// The newest version of the changes which all the registered clients understand, the clients which never reported one know only the legacy version.
+    pub fn changes_version(&mut self) -> Result<u32> {
+        let mut versions = vec![];
+        for client_id in self.registered_clients()? {
+            let version: Option<u32> = self
+                .redis_connection
+                .hget(self.changes_versions_key(), &client_id)?;
+            versions.push(version.unwrap_or(LEGACY_CHANGES_VERSION));
+        }
+        Ok(versions.into_iter().min().unwrap_or(LEGACY_CHANGES_VERSION))
+    }
+
     pub fn register_client(&mut self, client_id: &str) -> Result<()> {
         self.redis_connection
             .xgroup_create_mkstream(self.changes_key(), client_id, "$")?;
@@ -199,6 +226,20 @@ impl ChangesStream {
         Ok(removed)
     }
 
+    /// Returns up to `count` of the newest changes, the newest first.
+    pub fn recent_changes(&mut self, count: usize) -> Result<Vec<SemanticChange>> {
+        let reply: StreamRangeReply =
+            self.redis_connection
+                .xrevrange_count(self.changes_key(), "+", "-", count)?;
+        let mut changes = Vec::with_capacity(reply.ids.len());
+        for entry in reply.ids {
+            if let Some(data) = entry.get::<Vec<u8>>("c") {
+                changes.push(SemanticChange::from_serialized(&data)?);
+            }
+        }
+        Ok(changes)
+    }
+
     pub fn begin_batch(&mut self) -> ChangesBatch {
         ChangesBatch::for_stream(self)
     }
@@ -0,0 +1 @@
+DROP TABLE translation_records;
\ No newline at end of file
@@ -0,0 +1,8 @@
+CREATE TABLE translation_records (
+        id INTEGER NOT NULL PRIMARY KEY,
+        area_osm_id BIGINT NOT NULL,
+        kind TEXT NOT NULL,
+        created_at DATETIME NOT NULL,
+        record TEXT NOT NULL
+);
+CREATE INDEX translation_records_area_osm_id ON translation_records (area_osm_id);
@@ -2,12 +2,16 @@ use crate::area::{Area, AreaState};
 use crate::background_tasks::CreateAreaDatabaseTask;
 use crate::{DbConn, Error, Result};
 use crate::names_cache::{OSMObjectNamesCache, CacheMap};
+use crate::tiles;
 use doitlater::{ExecutableExt, Queue};
+use osm_db::semantic_change::LEGACY_CHANGES_VERSION;
+use osm_db::simplification::SimplificationLevel;
 use osm_db::AreaDatabase;
 use redis_api::ChangesStream;
-use rocket::http::Status;
+use rocket::http::{ContentType, Status};
 use rocket::response::status;
 use rocket::serde::json::Json;
+use rocket::tokio::task::spawn_blocking;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{self, File};
@@ -68,8 +72,14 @@ pub async fn maybe_create_area(
     }
 }
 
-#[get("/areas/<area_osm_id>/download?<client_id>")]
-pub async fn download_area(area_osm_id: i64, client_id: String, conn: DbConn) -> Result<File> {
+#[get("/areas/<area_osm_id>/download?<client_id>&<level>&<changes_version>")]
+pub async fn download_area(
+    area_osm_id: i64,
+    client_id: String,
+    level: Option<String>,
+    changes_version: Option<u32>,
+    conn: DbConn,
+) -> Result<File> {
     let area = conn
         .run(move |c| Area::find_by_osm_id(area_osm_id, c))
         .await?;
@@ -83,9 +93,57 @@ pub async fn download_area(area_osm_id: i64, client_id: String, conn: DbConn) ->
             } else {
                 stream.register_client(&client_id)?;
             }
+            // The older clients do not report the version, they understand only the legacy changes.
+            stream.set_changes_version_for(
+                &client_id,
+                changes_version.unwrap_or(LEGACY_CHANGES_VERSION),
+            )?;
         }
-        Ok(File::open(AreaDatabase::path_for(area_osm_id, true))?)
+        let level = match level {
+            Some(level) => level
+                .parse::<SimplificationLevel>()
+                .map_err(Error::UnknownSimplificationLevel)?,
+            None => SimplificationLevel::Full,
+        };
+        // Writing a simplified copy takes a while, it must not block the request handling.
+        spawn_blocking(move || open_area_database_file(area_osm_id, level)).await?
+    }
+}
+
+fn open_area_database_file(area_osm_id: i64, level: SimplificationLevel) -> Result<File> {
+    let path = AreaDatabase::path_for(area_osm_id, true);
+    if level == SimplificationLevel::Full {
+        return Ok(File::open(path)?);
+    }
+    let simplified_path = AreaDatabase::simplified_path_for(area_osm_id, level);
+    // The copy is stale once any change was applied to the area database after its creation.
+    let is_fresh = match fs::metadata(&simplified_path) {
+        Ok(meta) => meta.modified()? >= fs::metadata(&path)?.modified()?,
+        Err(_) => false,
+    };
+    if !is_fresh {
+        AreaDatabase::open_existing(area_osm_id, true)?
+            .write_simplified_copy(level, &simplified_path)?;
+    }
+    Ok(File::open(simplified_path)?)
+}
+
+#[get("/areas/<area_osm_id>/tiles/<z>/<x>/<y>")]
+pub async fn area_tile(
+    area_osm_id: i64,
+    z: u32,
+    x: u32,
+    y: u32,
+) -> Result<Option<(ContentType, Vec<u8>)>> {
+    if !tiles::is_valid_tile(z, x, y) {
+        return Ok(None);
     }
+    // The clipping of the geometries takes a while, it must not block the request handling.
+    let tile = spawn_blocking(move || tiles::cached_tile(area_osm_id, z, x, y)).await??;
+    Ok(Some((
+        ContentType::new("application", "vnd.mapbox-vector-tile"),
+        tile,
+    )))
 }
 
 #[get("/ping")]
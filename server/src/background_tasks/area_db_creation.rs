@@ -1,37 +1,111 @@
 use crate::area;
-use crate::Result;
+use crate::{Error, Result};
 use crate::names_cache::OSMObjectNamesCache;
+use crate::translation_report::{self, StoredTranslationRecord, TranslationRunKind};
+use crossbeam_channel::Sender;
 use diesel::{Connection, SqliteConnection};
+use osm_api::object::OSMObject;
 use osm_api::SmolStr;
 use osm_api::object_manager::OSMObjectManager;
 use osm_db::area_db::AreaDatabase;
+use osm_db::entity::Entity;
+use osm_db::entity_relationship::RootedEntityRelationship;
 use osm_db::relationship_inference::infer_additional_relationships_for;
 use osm_db::translation::{record::TranslationRecord, translator};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::fs;
+use std::thread;
+
+// How many objects get read from the cache before being translated in parallel.
+const TRANSLATION_BATCH_SIZE: usize = 10_000;
+// How many translated entities can wait for the database writer.
+const WRITER_QUEUE_SIZE: usize = 1_000;
+
+type TranslatedEntity = (Entity, Vec<RootedEntityRelationship>);
+
+fn translate_batch(
+    objects: &[OSMObject],
+    manager: &OSMObjectManager,
+    sender: &Sender<TranslatedEntity>,
+) -> Result<TranslationRecord> {
+    objects
+        .par_iter()
+        .try_fold(TranslationRecord::new, |mut record, obj| {
+            if let Some((entity, relationships)) = translator::translate(obj, manager, &mut record)? {
+                sender
+                    .send((entity, relationships.collect()))
+                    .map_err(|_| Error::AreaDatabaseWriterGone)?;
+            }
+            Ok(record)
+        })
+        .try_reduce(TranslationRecord::new, |mut record, other| {
+            other.merge_to(&mut record);
+            Ok(record)
+        })
+}
+
+/// Translates the objects retrieved for the area on all the cores, while a single thread inserts them into the area database.
+///
+/// The record of every batch is appended to the record file, so the record of the whole area is never kept in memory during the translation.
+fn translate_into(db: AreaDatabase, manager: &OSMObjectManager, record_path: &str) -> Result<AreaDatabase> {
+    let from_network_ids = manager.get_ids_retrieved_from_network();
+    let (sender, receiver) = crossbeam_channel::bounded::<TranslatedEntity>(WRITER_QUEUE_SIZE);
+    let writer = thread::spawn(move || {
+        let mut db = db;
+        db.insert_entities(receiver.into_iter().map(|(entity, relationships)| {
+            (
+                entity,
+                Box::new(relationships.into_iter()) as Box<dyn Iterator<Item = RootedEntityRelationship>>,
+            )
+        }))?;
+        Ok::<_, osm_db::Error>(db)
+    });
+    let mut translated = Ok(());
+    let mut batch = Vec::with_capacity(TRANSLATION_BATCH_SIZE);
+    let mut objects = manager
+        .cached_objects()
+        .filter(|obj| from_network_ids.contains(&obj.unique_id()))
+        .peekable();
+    while objects.peek().is_some() {
+        batch.extend(objects.by_ref().take(TRANSLATION_BATCH_SIZE));
+        match translate_batch(&batch, manager, &sender)
+            .and_then(|mut batch_record| Ok(batch_record.append_to_file(record_path)?))
+        {
+            Ok(()) => {}
+            Err(e) => {
+                translated = Err(e);
+                break;
+            }
+        }
+        batch.clear();
+    }
+    // Lets the writer finish the insertion.
+    drop(sender);
+    let db = writer.join().expect("The area database writer panicked.")?;
+    // A failed writer makes the translation fail as well, so its error is the more interesting one.
+    translated?;
+    Ok(db)
+}
 
 pub fn create_area_database(area: i64) -> Result<()> {
     info!("Starting to create area with id {}.", area);
     let manager = OSMObjectManager::new()?;
-    let mut record = TranslationRecord::new();
     manager.lookup_objects_in(area)?;
-    let from_network_ids = manager.get_ids_retrieved_from_network();
-    let mut db = AreaDatabase::create(area)?;
-    db.insert_entities(
-        manager.cached_objects().filter_map(|obj| {
-            if !from_network_ids.contains(&obj.unique_id()) {
-                return None;
-            }
-            translator::translate(&obj, &manager, &mut record).expect("Translation failure.")
-        }),
-    )?;
-    drop(from_network_ids);
+    let record_path = translation_report::record_file_path(area, TranslationRunKind::Creation);
+    // A previous attempt to create the area might have left its record behind.
+    if fs::metadata(&record_path).is_ok() {
+        fs::remove_file(&record_path)?;
+    }
+    let db = translate_into(AreaDatabase::create(area)?, &manager, &record_path)?;
+    let record = TranslationRecord::load_from_file(&record_path)?;
     db.begin()?;
     infer_additional_relationships_for(&db)?;
     db.commit()?;
     let parent_ids_str = get_parent_ids_str_for(area, &manager)?;
     let area_db_conn = SqliteConnection::establish("server.db")?;
     area::finalize_area_creation(area, parent_ids_str, &area_db_conn)?;
-    record.save_to_file(&format!("creation_{}.json", area))?;
+    StoredTranslationRecord::save(area, TranslationRunKind::Creation, &record, &area_db_conn)?;
     info!("Area created successfully.");
     Ok(())
 }
@@ -1,16 +1,17 @@
 use crate::area::{Area, AreaState};
 use crate::diff_utils;
 use crate::diff_utils::ListChange;
+use crate::translation_report::{self, StoredTranslationRecord, TranslationRunKind};
 use crate::Result;
 use chrono::{DateTime, Utc};
 use diesel::{Connection, SqliteConnection};
 use osm_api::change::OSMObjectChangeType;
 use osm_api::object_manager::OSMObjectManager;
+use osm_db::change_coalescing::coalesce_changes;
 use osm_db::semantic_change::SemanticChange;
 use osm_db::translation::{record::TranslationRecord, translator};
 use osm_db::{
-    area_db::AreaDatabase, entity_relationship::RootedEntityRelationship,
-    entity_relationship_kind::EntityRelationshipKind, relationship_inference,
+    area_db::AreaDatabase, entity_relationship::RootedEntityRelationship, relationship_inference,
     semantic_change::RelationshipChange,
 };
 use redis_api::ChangesStream;
@@ -20,7 +21,6 @@ use std::{
     fs,
     sync::{Arc, Mutex},
 };
-use osm_api::overpass_api::Servers;
 
 fn find_or_create_suitable_change<'a>(
     changes: &'a mut Vec<SemanticChange>,
@@ -39,7 +39,7 @@ fn find_or_create_suitable_change<'a>(
 pub fn update_area(
     mut area: Area,
     conn: Arc<Mutex<SqliteConnection>>,
-    manager: OSMObjectManager
+    manager: Arc<OSMObjectManager>
 ) -> Result<TranslationRecord> {
     info!("Updating area {} (id {}).", area.name, area.osm_id);
     let mut record = TranslationRecord::new();
@@ -98,26 +98,23 @@ pub fn update_area(
         let semantic_change = match change.change_type {
             Create => {
                 let new = change.new.expect("No new for a create change");
-                manager.cache_object(&new);
+                manager.cache_area_object(area.osm_id, &new);
                 translator::translate(&new, &manager, &mut record)?
             }
-            .map(|(o, ids)| {
+            .map(|(o, relationships)| {
                 SemanticChange::creating(
                     o.id.to_string(),
                     o.geometry,
                     o.discriminator.to_string(),
                     o.data,
                     o.effective_width,
-                    ids.map(|id| {
-                        RootedEntityRelationship::new(&id, EntityRelationshipKind::OSMChild)
-                    })
-                    .collect(),
+                    relationships.collect(),
                 )
             }),
             Delete => {
                 let osm_id = change.old.expect("No old in a deletion change").unique_id();
                 manager
-                    .remove_cached_object(osm_id.as_str())
+                    .remove_area_object(area.osm_id, osm_id.as_str())
                     .expect("Could not remove cached entity");
                 if area_db.has_entity(&osm_id)? {
                     Some(SemanticChange::removing(&osm_id))
@@ -127,40 +124,27 @@ pub fn update_area(
             }
             Modify => {
                 let new_object = change.new.expect("No new during a modify");
-                manager.cache_object(&new_object);
+                manager.cache_area_object(area.osm_id, &new_object);
                 let osm_id = new_object.unique_id();
                 let old = area_db.get_entity(&osm_id)?;
                 let new = translator::translate(&new_object, &manager, &mut record)?;
                 match (old, new) {
                     (None, None) => None,
                     (Some(_), None) => Some(SemanticChange::removing(&osm_id)),
-                    (None, Some((new, new_ids))) => Some(SemanticChange::creating(
+                    (None, Some((new, new_relationships))) => Some(SemanticChange::creating(
                         new.id.to_string(),
                         new.geometry,
                         new.discriminator.to_string(),
                         new.data,
                         new.effective_width,
-                        new_ids
-                            .map(|id| {
-                                RootedEntityRelationship::new(&id, EntityRelationshipKind::OSMChild)
-                            })
-                            .collect(),
+                        new_relationships.collect(),
                     )),
-                    (Some(old), Some((new, new_ids))) => {
+                    (Some(old), Some((new, new_relationships))) => {
                         let (data_changes, property_changes) =
                             diff_utils::diff_entities(&old, &new)?;
-                        let old_ids = area_db.get_entity_child_ids(&old.id)?;
-                        let old_relationships: Vec<RootedEntityRelationship> = old_ids
-                            .iter()
-                            .map(|id| {
-                                RootedEntityRelationship::new(id, EntityRelationshipKind::OSMChild)
-                            })
-                            .collect();
-                        let new_relationships: Vec<RootedEntityRelationship> = new_ids
-                            .map(|id| {
-                                RootedEntityRelationship::new(&id, EntityRelationshipKind::OSMChild)
-                            })
-                            .collect();
+                        let old_relationships = area_db.get_entity_child_relationships(&old.id)?;
+                        let new_relationships: Vec<RootedEntityRelationship> =
+                            new_relationships.collect();
                         let child_id_changes =
                             diff_utils::diff_lists(&old_relationships, &new_relationships)
                                 .into_iter()
@@ -202,7 +186,24 @@ pub fn update_area(
     //area_db.begin()?;
     infer_additional_relationships(&mut semantic_changes, &area_db)?;
     area_db.commit()?;
-    let mut stream = ChangesStream::new_from_env(area.osm_id)?;
+    let change_count = semantic_changes.len();
+    let semantic_changes = coalesce_changes(semantic_changes);
+    info!(
+        "Coalesced {} semantic changes into {}.",
+        change_count,
+        semantic_changes.len()
+    );
+    publish_changes(area.osm_id, &semantic_changes)?;
+    let size = fs::metadata(AreaDatabase::path_for(area.osm_id, true))?.len() as i64;
+    area.db_size = size;
+    area.state = AreaState::Updated;
+    area.save(&conn.lock().unwrap())?;
+    Ok(record)
+}
+
+/// Publishes the changes applied to the area database to its clients.
+pub fn publish_changes(area_osm_id: i64, semantic_changes: &[SemanticChange]) -> Result<()> {
+    let mut stream = ChangesStream::new_from_env(area_osm_id)?;
     if !stream.exists()? || !stream.should_publish_changes()? {
         info!("Not publishing the changes, because there is either no client to receive them, or all the clients have to redownload the area anyway.");
     } else {
@@ -214,15 +215,11 @@ pub fn update_area(
         info!("Publishing the changes...");
         let mut batch = stream.begin_batch();
         for change in semantic_changes {
-            batch.add_change(&change)?;
+            batch.add_change(change)?;
         }
         info!("Changes published and replies checked.");
     }
-    let size = fs::metadata(AreaDatabase::path_for(area.osm_id, true))?.len() as i64;
-    area.db_size = size;
-    area.state = AreaState::Updated;
-    area.save(&conn.lock().unwrap())?;
-    Ok(record)
+    Ok(())
 }
 
 fn infer_additional_relationships(
@@ -251,10 +248,7 @@ fn infer_additional_relationships(
                 } else {
                     find_or_create_suitable_change(changes, &relationship.parent_id, false)
                 };
-                target.add_rooted_relationship(RootedEntityRelationship::new(
-                    relationship.child_id.as_str(),
-                    relationship.kind,
-                ));
+                target.add_rooted_relationship(relationship.rooted());
             }
         } else if changes[idx].is_update() {
             let entity_id = changes[idx].osm_id();
@@ -274,18 +268,12 @@ fn infer_additional_relationships(
             for difference in differences {
                 let (parent_id, change) = match difference {
                     ListChange::Add(v) => (
-                        v.parent_id,
-                        RelationshipChange::adding(RootedEntityRelationship::new(
-                            v.child_id.as_str(),
-                            v.kind,
-                        )),
+                        v.parent_id.clone(),
+                        RelationshipChange::adding(v.rooted()),
                     ),
                     ListChange::Remove(v) => (
-                        v.parent_id,
-                        RelationshipChange::removing(RootedEntityRelationship::new(
-                            v.child_id.as_str(),
-                            v.kind,
-                        )),
+                        v.parent_id.clone(),
+                        RelationshipChange::removing(v.rooted()),
                     ),
                 };
                 let target = if parent_id == changes[idx].osm_id() {
@@ -304,24 +292,41 @@ pub fn update_area_databases() -> Result<()> {
     info!("Going to perform the area database update for all up-to date areas.");
     let pool = rusty_pool::ThreadPool::default();
     let area_db_conn = Arc::new(Mutex::new(SqliteConnection::establish("server.db")?));
-    let servers = Arc::new(Servers::default());
-    let cache = Arc::new(osm_api::object_manager::open_cache()?);
+    let manager = Arc::new(OSMObjectManager::new()?);
     let areas = Area::all_updated(&area_db_conn.lock().unwrap())?;
-    let now = Utc::now();
-    let mut record = TranslationRecord::new();
     let mut tasks = vec![];
     for area in areas {
         let conn_clone = area_db_conn.clone();
-        let manager = OSMObjectManager::new_multithread(servers.clone(), cache.clone())?;
-        tasks.push(pool.evaluate(move || update_area(area, conn_clone, manager)));
+        let manager = manager.clone();
+        let area_osm_id = area.osm_id;
+        tasks.push((area_osm_id, pool.evaluate(move || update_area(area, conn_clone, manager))));
     }
-    for task in tasks {
+    for (area_osm_id, task) in tasks {
         match task.await_complete() {
-            Ok(rec) => rec.merge_to(&mut record),
+            Ok(mut rec) => {
+                if let Err(e) = StoredTranslationRecord::save(
+                    area_osm_id,
+                    TranslationRunKind::Update,
+                    &rec,
+                    &area_db_conn.lock().unwrap(),
+                ) {
+                    error!(
+                        "Failed to store the translation record of area {}, error: {}",
+                        area_osm_id, e
+                    );
+                }
+                let path =
+                    translation_report::record_file_path(area_osm_id, TranslationRunKind::Update);
+                if let Err(e) = rec.append_to_file(&path) {
+                    error!(
+                        "Failed to append the translation record of area {} to {}, error: {}",
+                        area_osm_id, path, e
+                    );
+                }
+            }
             Err(e) => { error!("Failed to update the area, error: {:?}", e);}
         }
     }
-    record.save_to_file(&format!("area_updates_{}.json", now.to_rfc3339()))?;
     info!("Area updates finished successfully.");
     Ok(())
 }
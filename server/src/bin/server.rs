@@ -29,13 +29,23 @@ fn rocket() -> _ {
                 api_routes::areas,
                 api_routes::maybe_create_area,
                 api_routes::download_area,
+                api_routes::area_tile,
                 api_routes::ping,
                 api_routes::motd,
                 api_routes::create_client,
                 api_routes::osm_object_names,
             ],
         )
-        .mount("/", routes![ui_routes::areas, ui_routes::area_detail])
+        .mount(
+            "/",
+            routes![
+                ui_routes::areas,
+                ui_routes::area_detail,
+                ui_routes::translation_report,
+                ui_routes::unknown_field_detail,
+                ui_routes::missing_enum_member_detail,
+            ],
+        )
         .mount("/", FileServer::from("static"))
         .attach(Template::fairing())
 }
@@ -1,5 +1,6 @@
 use diesel::{Connection, SqliteConnection};
 use osm_api::object_manager::OSMObjectManager;
+use server::translation_report::{StoredTranslationRecord, TranslationRunKind};
 use server::{area::Area, Result};
 use std::sync::{Arc, Mutex};
 
@@ -13,6 +14,12 @@ fn main() -> Result<()> {
         .parse()
         .expect("Area id not an int");
     let area = Area::find_by_osm_id(area_id, &area_db_conn.lock().unwrap())?;
-    let _record = server::background_tasks::area_db_update::update_area(area, area_db_conn, OSMObjectManager::new()?)?;
+    let record = server::background_tasks::area_db_update::update_area(area, area_db_conn.clone(), Arc::new(OSMObjectManager::new()?))?;
+    StoredTranslationRecord::save(
+        area_id,
+        TranslationRunKind::Update,
+        &record,
+        &area_db_conn.lock().unwrap(),
+    )?;
     Ok(())
 }
@@ -24,6 +24,8 @@ pub enum Error {
     DatabaseIntegrityError,
     #[error("The given client is already created")]
     ClientAlreadyExists,
+    #[error("{0}")]
+    UnknownSimplificationLevel(String),
     #[error("Dotenv error: {0}")]
     DotenvError(#[from] dotenv::Error),
     #[error("The system time is before the unix epoch: {0}")]
@@ -32,6 +34,10 @@ pub enum Error {
     RedisApiError(#[from] redis_api::Error),
     #[error("Doitlater error: {0}")]
     DoItLaterError(#[from] doitlater::Error),
+    #[error("The area database writer stopped before all the entities were translated")]
+    AreaDatabaseWriterGone,
+    #[error("Blocking task error: {0}")]
+    BlockingTaskError(#[from] rocket::tokio::task::JoinError),
 }
 
 impl<'r, 'o> rocket::response::Responder<'r, 'o> for Error
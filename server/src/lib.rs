@@ -13,8 +13,11 @@ pub mod area;
 pub mod background_tasks;
 mod diff_utils;
 mod error;
+mod mvt;
 mod names_cache;
 mod schema;
+pub mod tiles;
+pub mod translation_report;
 pub mod ui_routes;
 
 use diesel::SqliteConnection;
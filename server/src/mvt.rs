@@ -0,0 +1,321 @@
+//! A minimal encoder of the Mapbox vector tiles, as described by the version 2.1 of the specification.
+use geo_types::{Coordinate, Geometry, LineString, Polygon};
+use std::collections::HashMap;
+
+pub const EXTENT: u32 = 4096;
+
+const MOVE_TO: u32 = 1;
+const LINE_TO: u32 = 2;
+const CLOSE_PATH: u32 = 7;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum GeometryType {
+    Point = 1,
+    LineString = 2,
+    Polygon = 3,
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_key(buf: &mut Vec<u8>, field: u32, wire_type: u8) {
+    write_varint(buf, u64::from((field << 3) | u32::from(wire_type)));
+}
+
+fn write_varint_field(buf: &mut Vec<u8>, field: u32, value: u64) {
+    write_key(buf, field, 0);
+    write_varint(buf, value);
+}
+
+fn write_bytes_field(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_key(buf, field, 2);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_packed_field(buf: &mut Vec<u8>, field: u32, values: &[u32]) {
+    let mut packed = Vec::with_capacity(values.len());
+    for value in values {
+        write_varint(&mut packed, u64::from(*value));
+    }
+    write_bytes_field(buf, field, &packed);
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+fn command(id: u32, count: u32) -> u32 {
+    (id & 0x7) | (count << 3)
+}
+
+/// Encodes the geometry commands of one feature, the coordinates are relative to the previous ones, even across the parts of a multi geometry.
+struct GeometryEncoder<'a, F: Fn(f64, f64) -> (i32, i32)> {
+    project: &'a F,
+    cursor: (i32, i32),
+    commands: Vec<u32>,
+}
+
+impl<'a, F: Fn(f64, f64) -> (i32, i32)> GeometryEncoder<'a, F> {
+    fn new(project: &'a F) -> Self {
+        Self {
+            project,
+            cursor: (0, 0),
+            commands: vec![],
+        }
+    }
+
+    fn push_point(&mut self, point: (i32, i32)) {
+        self.commands.push(zigzag(point.0 - self.cursor.0));
+        self.commands.push(zigzag(point.1 - self.cursor.1));
+        self.cursor = point;
+    }
+
+    /// Projects the coordinates, dropping the ones which fall on the same tile pixel as their predecessor.
+    fn project_line(&self, line: &LineString<f64>) -> Vec<(i32, i32)> {
+        let mut points: Vec<(i32, i32)> = Vec::with_capacity(line.0.len());
+        for coord in &line.0 {
+            let point = (self.project)(coord.x, coord.y);
+            if points.last() != Some(&point) {
+                points.push(point);
+            }
+        }
+        points
+    }
+
+    fn add_points(&mut self, coords: &[Coordinate<f64>]) {
+        let points: Vec<(i32, i32)> = coords.iter().map(|c| (self.project)(c.x, c.y)).collect();
+        if points.is_empty() {
+            return;
+        }
+        self.commands.push(command(MOVE_TO, points.len() as u32));
+        for point in points {
+            self.push_point(point);
+        }
+    }
+
+    fn add_line(&mut self, line: &LineString<f64>) {
+        let points = self.project_line(line);
+        if points.len() < 2 {
+            return;
+        }
+        self.commands.push(command(MOVE_TO, 1));
+        self.push_point(points[0]);
+        self.commands
+            .push(command(LINE_TO, points.len() as u32 - 1));
+        for point in &points[1..] {
+            self.push_point(*point);
+        }
+    }
+
+    /// The exterior rings must have a positive area in the tile coordinates, whose y axis goes down, the interior ones a negative one.
+    fn add_ring(&mut self, ring: &LineString<f64>, exterior: bool) -> bool {
+        let mut points = self.project_line(ring);
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if points.len() < 3 {
+            return false;
+        }
+        let mut area = 0i64;
+        for (i, (x1, y1)) in points.iter().enumerate() {
+            let (x2, y2) = points[(i + 1) % points.len()];
+            area += i64::from(*x1) * i64::from(y2) - i64::from(x2) * i64::from(*y1);
+        }
+        if area == 0 {
+            return false;
+        }
+        if (area > 0) != exterior {
+            points.reverse();
+        }
+        self.commands.push(command(MOVE_TO, 1));
+        self.push_point(points[0]);
+        self.commands
+            .push(command(LINE_TO, points.len() as u32 - 1));
+        for point in &points[1..] {
+            self.push_point(*point);
+        }
+        self.commands.push(command(CLOSE_PATH, 1));
+        true
+    }
+
+    fn add_polygon(&mut self, polygon: &Polygon<f64>) {
+        // The interiors of a collapsed exterior would be attached to the previous polygon.
+        if self.add_ring(polygon.exterior(), true) {
+            for interior in polygon.interiors() {
+                self.add_ring(interior, false);
+            }
+        }
+    }
+}
+
+/// Splits a geometry into the parts which can be encoded as a single feature, i.e. the points, the lines and the polygons.
+fn collect_parts<'g>(
+    geometry: &'g Geometry<f64>,
+    parts: &mut HashMap<GeometryType, Vec<&'g Geometry<f64>>>,
+) {
+    let geometry_type = match geometry {
+        Geometry::Point(_) | Geometry::MultiPoint(_) => GeometryType::Point,
+        Geometry::Line(_) | Geometry::LineString(_) | Geometry::MultiLineString(_) => {
+            GeometryType::LineString
+        }
+        Geometry::Polygon(_)
+        | Geometry::MultiPolygon(_)
+        | Geometry::Rect(_)
+        | Geometry::Triangle(_) => GeometryType::Polygon,
+        Geometry::GeometryCollection(collection) => {
+            for geometry in &collection.0 {
+                collect_parts(geometry, parts);
+            }
+            return;
+        }
+    };
+    parts.entry(geometry_type).or_default().push(geometry);
+}
+
+fn encode_part<F: Fn(f64, f64) -> (i32, i32)>(
+    encoder: &mut GeometryEncoder<F>,
+    geometry: &Geometry<f64>,
+) {
+    match geometry {
+        Geometry::Point(point) => encoder.add_points(&[point.0]),
+        Geometry::MultiPoint(points) => {
+            let coords: Vec<Coordinate<f64>> = points.0.iter().map(|p| p.0).collect();
+            encoder.add_points(&coords)
+        }
+        Geometry::Line(line) => encoder.add_line(&LineString(vec![line.start, line.end])),
+        Geometry::LineString(line) => encoder.add_line(line),
+        Geometry::MultiLineString(lines) => {
+            for line in &lines.0 {
+                encoder.add_line(line);
+            }
+        }
+        Geometry::Polygon(polygon) => encoder.add_polygon(polygon),
+        Geometry::MultiPolygon(polygons) => {
+            for polygon in &polygons.0 {
+                encoder.add_polygon(polygon);
+            }
+        }
+        Geometry::Rect(rect) => encoder.add_polygon(&rect.to_polygon()),
+        Geometry::Triangle(triangle) => encoder.add_polygon(&triangle.to_polygon()),
+        Geometry::GeometryCollection(_) => unreachable!("Collections are split by collect_parts"),
+    }
+}
+
+pub struct Layer {
+    name: String,
+    features: Vec<Vec<u8>>,
+    keys: Vec<String>,
+    key_indices: HashMap<String, u32>,
+    values: Vec<String>,
+    value_indices: HashMap<String, u32>,
+}
+
+impl Layer {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            features: vec![],
+            keys: vec![],
+            key_indices: HashMap::new(),
+            values: vec![],
+            value_indices: HashMap::new(),
+        }
+    }
+
+    fn intern(strings: &mut Vec<String>, indices: &mut HashMap<String, u32>, s: &str) -> u32 {
+        if let Some(idx) = indices.get(s) {
+            return *idx;
+        }
+        let idx = strings.len() as u32;
+        strings.push(s.to_string());
+        indices.insert(s.to_string(), idx);
+        idx
+    }
+
+    /// Adds the geometry, projected by the given function to the tile coordinates, as one feature per geometry type.
+    pub fn add_feature<F: Fn(f64, f64) -> (i32, i32)>(
+        &mut self,
+        geometry: &Geometry<f64>,
+        properties: &[(&str, &str)],
+        project: &F,
+    ) {
+        let mut tags = vec![];
+        for (key, value) in properties {
+            tags.push(Self::intern(&mut self.keys, &mut self.key_indices, key));
+            tags.push(Self::intern(
+                &mut self.values,
+                &mut self.value_indices,
+                value,
+            ));
+        }
+        let mut parts = HashMap::new();
+        collect_parts(geometry, &mut parts);
+        for (geometry_type, geometries) in parts {
+            let mut encoder = GeometryEncoder::new(project);
+            for geometry in geometries {
+                encode_part(&mut encoder, geometry);
+            }
+            if encoder.commands.is_empty() {
+                continue;
+            }
+            let mut feature = vec![];
+            write_packed_field(&mut feature, 2, &tags);
+            write_varint_field(&mut feature, 3, geometry_type as u64);
+            write_packed_field(&mut feature, 4, &encoder.commands);
+            self.features.push(feature);
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_varint_field(buf, 15, 2);
+        write_bytes_field(buf, 1, self.name.as_bytes());
+        for feature in &self.features {
+            write_bytes_field(buf, 2, feature);
+        }
+        for key in &self.keys {
+            write_bytes_field(buf, 3, key.as_bytes());
+        }
+        for value in &self.values {
+            let mut encoded = vec![];
+            write_bytes_field(&mut encoded, 1, value.as_bytes());
+            write_bytes_field(buf, 4, &encoded);
+        }
+        write_varint_field(buf, 5, u64::from(EXTENT));
+    }
+}
+
+#[derive(Default)]
+pub struct Tile {
+    layers: Vec<Layer>,
+}
+
+impl Tile {
+    pub fn layer_mut(&mut self, name: &str) -> &mut Layer {
+        match self.layers.iter().position(|l| l.name == name) {
+            Some(pos) => &mut self.layers[pos],
+            None => {
+                self.layers.push(Layer::new(name));
+                self.layers.last_mut().unwrap()
+            }
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        for layer in &self.layers {
+            if layer.features.is_empty() {
+                continue;
+            }
+            let mut encoded = vec![];
+            layer.encode(&mut encoded);
+            write_bytes_field(&mut buf, 3, &encoded);
+        }
+        buf
+    }
+}
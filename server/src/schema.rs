@@ -13,3 +13,15 @@ table! {
         parent_osm_ids -> Nullable<Text>,
     }
 }
+
+table! {
+    use diesel::sql_types::{Integer, BigInt, Text, Timestamp};
+    use crate::translation_report::TranslationRunKindMapping;
+    translation_records (id) {
+        id -> Integer,
+        area_osm_id -> BigInt,
+        kind -> TranslationRunKindMapping,
+        created_at -> Timestamp,
+        record -> Text,
+    }
+}
@@ -0,0 +1,137 @@
+//! Rendering of the area database entities into vector tiles in the usual web mercator tiling scheme.
+use crate::mvt::{Tile, EXTENT};
+use crate::Result;
+use osm_db::entities_query::EntitiesQuery;
+use osm_db::entity_metadata::all_known_discriminators;
+use osm_db::simplification::SimplificationLevel;
+use osm_db::AreaDatabase;
+use serde_json::Value;
+use std::f64::consts::PI;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub const MAX_ZOOM: u32 = 20;
+// From this zoom on, every entity kind is shown.
+const ALL_ENTITIES_ZOOM: u32 = 16;
+// The geometries are clipped slightly outside the tile, so the line and outline ends are not drawn at its edges.
+const BUFFER: f64 = 64.0;
+
+fn lon_of(x: f64, z: u32) -> f64 {
+    x / f64::from(1u32 << z) * 360.0 - 180.0
+}
+
+fn lat_of(y: f64, z: u32) -> f64 {
+    let n = PI - 2.0 * PI * y / f64::from(1u32 << z);
+    n.sinh().atan().to_degrees()
+}
+
+fn mercator_x(lon: f64) -> f64 {
+    (lon + 180.0) / 360.0
+}
+
+fn mercator_y(lat: f64) -> f64 {
+    let lat = lat.to_radians();
+    (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0
+}
+
+/// The smallest zoom at which the entities of the given kind are shown, so the tiles of the whole area stay small.
+fn min_zoom(discriminator: &str) -> u32 {
+    match discriminator {
+        "Boundary" | "Region" | "Border" => 0,
+        "Place" | "WaterArea" => 10,
+        "Route" | "ProtectedArea" | "Natural" => 11,
+        "Road" | "RailWay" | "WaterWay" | "Leisure" | "Land" | "Street" | "Graveyard"
+        | "GolfCourse" | "AerialWay" | "PowerLine" => 12,
+        "Building" | "ServiceRoad" | "Track" | "Parking" | "Platform" | "Stop" | "Garden"
+        | "Steps" | "Amenity" | "Shop" | "Tourism" | "Sport" => 14,
+        _ => ALL_ENTITIES_ZOOM,
+    }
+}
+
+/// Less detailed geometries are good enough for the lower zooms, where a tile pixel spans many meters.
+fn level_for_zoom(z: u32) -> SimplificationLevel {
+    match z {
+        0..=10 => SimplificationLevel::Coarse,
+        11..=13 => SimplificationLevel::Medium,
+        14..=15 => SimplificationLevel::Fine,
+        _ => SimplificationLevel::Full,
+    }
+}
+
+fn tile_path(area: i64, z: u32, x: u32, y: u32) -> PathBuf {
+    PathBuf::from(format!("tiles/{}/{}/{}/{}.mvt", area, z, x, y))
+}
+
+/// Returns the rendered tile, rendering it again only when the area database changed since the last time.
+pub fn cached_tile(area: i64, z: u32, x: u32, y: u32) -> Result<Vec<u8>> {
+    let path = tile_path(area, z, x, y);
+    let db_modified = fs::metadata(AreaDatabase::path_for(area, true))?.modified()?;
+    if let Ok(meta) = fs::metadata(&path) {
+        if meta.modified()? >= db_modified {
+            return Ok(fs::read(&path)?);
+        }
+    }
+    let tile = render_tile(&AreaDatabase::open_existing(area, true)?, z, x, y)?;
+    let dir = path.parent().expect("A tile path has a parent");
+    fs::create_dir_all(dir)?;
+    // A concurrent request for the same tile must not read it half written.
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    temp.write_all(&tile)?;
+    temp.persist(&path).map_err(|e| e.error)?;
+    Ok(tile)
+}
+
+pub fn is_valid_tile(z: u32, x: u32, y: u32) -> bool {
+    z <= MAX_ZOOM && x < (1 << z) && y < (1 << z)
+}
+
+/// Renders the entities of the area intersecting the tile, with a layer for every discriminator.
+pub fn render_tile(area_db: &AreaDatabase, z: u32, x: u32, y: u32) -> Result<Vec<u8>> {
+    let buffer = BUFFER / f64::from(EXTENT);
+    let min_x = lon_of(f64::from(x) - buffer, z);
+    let max_x = lon_of(f64::from(x + 1) + buffer, z);
+    // The tile rows go from the north.
+    let min_y = lat_of(f64::from(y + 1) + buffer, z);
+    let max_y = lat_of(f64::from(y) - buffer, z);
+    let mut query = EntitiesQuery::default();
+    query.set_rectangle_of_interest(min_x, max_x, min_y, max_y);
+    query.set_simplification_level(level_for_zoom(z));
+    if z < ALL_ENTITIES_ZOOM {
+        query.set_included_discriminators(
+            all_known_discriminators()
+                .into_iter()
+                .filter(|d| min_zoom(d) <= z)
+                .cloned()
+                .collect(),
+        );
+    }
+    let scale = f64::from(1u32 << z) * f64::from(EXTENT);
+    let project = |lon: f64, lat: f64| {
+        (
+            (mercator_x(lon) * scale - f64::from(x) * f64::from(EXTENT)).round() as i32,
+            (mercator_y(lat) * scale - f64::from(y) * f64::from(EXTENT)).round() as i32,
+        )
+    };
+    let mut tile = Tile::default();
+    for mut entity in area_db.get_entities_clipped_to(&query, min_x, max_x, min_y, max_y)? {
+        let geometry = match wkb::wkb_to_geom(&mut entity.geometry.as_slice()) {
+            Ok(geometry) => geometry,
+            Err(e) => {
+                warn!("Failed to parse the geometry of {}: {:?}", entity.id, e);
+                continue;
+            }
+        };
+        let name = match entity.value_of_field("name") {
+            Value::String(name) => name.clone(),
+            _ => String::new(),
+        };
+        let mut properties = vec![("id", entity.id.as_str())];
+        if !name.is_empty() {
+            properties.push(("name", &name));
+        }
+        tile.layer_mut(&entity.discriminator)
+            .add_feature(&geometry, &properties, &project);
+    }
+    Ok(tile.encode())
+}
@@ -0,0 +1,348 @@
+use crate::schema::translation_records;
+use crate::Result;
+use chrono::NaiveDateTime;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use diesel::SqliteConnection;
+use diesel_derive_enum::DbEnum;
+use osm_db::translation::record::TranslationRecord;
+use rocket::form::{self, FromFormField, ValueField};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+const MAX_EXAMPLE_VALUES: usize = 5;
+// How many of the newest runs of each kind are kept for an area, the older records are deleted.
+const KEPT_RUNS: i64 = 30;
+pub const RUNS_PER_PAGE: i64 = 10;
+
+#[derive(PartialEq, Serialize, DbEnum, Debug, Clone, Copy)]
+pub enum TranslationRunKind {
+    Creation,
+    Update,
+}
+
+impl<'v> FromFormField<'v> for TranslationRunKind {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        match field.value {
+            "creation" => Ok(Self::Creation),
+            "update" => Ok(Self::Update),
+            _ => Err(form::Error::validation("expected creation or update").into()),
+        }
+    }
+}
+
+/// The file the records of the translation runs of the area are appended to as they are produced, `oesc suggest-schema` accepts it as well.
+pub fn record_file_path(area_osm_id: i64, kind: TranslationRunKind) -> String {
+    match kind {
+        TranslationRunKind::Creation => format!("creation_{}.jsonl", area_osm_id),
+        TranslationRunKind::Update => format!("area_updates_{}.jsonl", area_osm_id),
+    }
+}
+
+#[derive(Queryable)]
+pub struct StoredTranslationRecord {
+    pub id: i32,
+    pub area_osm_id: i64,
+    pub kind: TranslationRunKind,
+    pub created_at: NaiveDateTime,
+    pub record: String,
+}
+
+impl StoredTranslationRecord {
+    pub fn save(
+        area_osm_id: i64,
+        kind: TranslationRunKind,
+        record: &TranslationRecord,
+        conn: &SqliteConnection,
+    ) -> Result<usize> {
+        let inserted = diesel::insert_into(translation_records::table)
+            .values((
+                translation_records::area_osm_id.eq(area_osm_id),
+                translation_records::kind.eq(kind),
+                translation_records::created_at.eq(now),
+                translation_records::record.eq(record.to_json_string()?),
+            ))
+            .execute(conn)?;
+        Self::prune(area_osm_id, kind, conn)?;
+        Ok(inserted)
+    }
+
+    /// Deletes the records of the runs older than the kept ones.
+    fn prune(
+        area_osm_id: i64,
+        kind: TranslationRunKind,
+        conn: &SqliteConnection,
+    ) -> QueryResult<usize> {
+        let of_run = translation_records::area_osm_id
+            .eq(area_osm_id)
+            .and(translation_records::kind.eq(kind));
+        let oldest_kept: Option<i32> = translation_records::table
+            .filter(of_run.clone())
+            .select(translation_records::id)
+            .order(translation_records::id.desc())
+            .offset(KEPT_RUNS - 1)
+            .first(conn)
+            .optional()?;
+        match oldest_kept {
+            Some(oldest_kept) => diesel::delete(
+                translation_records::table
+                    .filter(of_run)
+                    .filter(translation_records::id.lt(oldest_kept)),
+            )
+            .execute(conn),
+            None => Ok(0),
+        }
+    }
+
+    pub fn all(conn: &SqliteConnection) -> QueryResult<Vec<Self>> {
+        translation_records::table
+            .order(translation_records::created_at.asc())
+            .load(conn)
+    }
+
+    /// The records of a page of the runs, optionally only of an area and of a kind of the run, the newest first.
+    pub fn page(
+        area_osm_id: Option<i64>,
+        kind: Option<TranslationRunKind>,
+        page: i64,
+        conn: &SqliteConnection,
+    ) -> QueryResult<Vec<Self>> {
+        let mut query = translation_records::table.into_boxed();
+        if let Some(area_osm_id) = area_osm_id {
+            query = query.filter(translation_records::area_osm_id.eq(area_osm_id));
+        }
+        if let Some(kind) = kind {
+            query = query.filter(translation_records::kind.eq(kind));
+        }
+        query
+            .order(translation_records::id.desc())
+            .limit(RUNS_PER_PAGE)
+            .offset(page * RUNS_PER_PAGE)
+            .load(conn)
+    }
+
+    pub fn parsed(&self) -> Result<TranslationRecord> {
+        Ok(TranslationRecord::load_from_str(&self.record)?)
+    }
+}
+
+#[derive(Serialize)]
+pub struct RunSummary {
+    pub area_osm_id: i64,
+    pub kind: TranslationRunKind,
+    pub created_at: NaiveDateTime,
+    pub unknown_field_occurrences: u64,
+    pub missing_enum_member_occurrences: u64,
+    pub type_violation_occurrences: u64,
+    pub missing_required_field_occurrences: u64,
+    pub potentially_interesting_objects: u64,
+    pub geometry_fix_occurrences: u64,
+}
+
+#[derive(Serialize)]
+pub struct UnknownFieldSummary {
+    pub discriminator: String,
+    pub field: String,
+    pub occurrences: u64,
+    pub runs: usize,
+    pub example_values: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct MissingEnumMemberSummary {
+    pub enum_name: String,
+    pub member: String,
+    pub occurrences: u64,
+    pub runs: usize,
+}
+
+/// The translation problems aggregated across the stored runs, the most frequent ones first.
+#[derive(Serialize)]
+pub struct TranslationReport {
+    pub runs: Vec<RunSummary>,
+    pub unknown_fields: Vec<UnknownFieldSummary>,
+    pub missing_enum_members: Vec<MissingEnumMemberSummary>,
+}
+
+impl TranslationReport {
+    pub fn from_records(records: &[StoredTranslationRecord], limit: usize) -> Result<Self> {
+        let mut runs = Vec::with_capacity(records.len());
+        let mut unknown_fields: HashMap<(String, String), UnknownFieldSummary> = HashMap::new();
+        let mut missing_enum_members: HashMap<(String, String), MissingEnumMemberSummary> =
+            HashMap::new();
+        for stored in records {
+            let record = stored.parsed()?;
+            let mut run = RunSummary {
+                area_osm_id: stored.area_osm_id,
+                kind: stored.kind,
+                created_at: stored.created_at,
+                unknown_field_occurrences: 0,
+                missing_enum_member_occurrences: 0,
+                type_violation_occurrences: record
+                    .type_violations()
+                    .values()
+                    .flat_map(|f| f.values())
+                    .map(|r| r.count())
+                    .sum(),
+                missing_required_field_occurrences: record
+                    .missing_required_fields()
+                    .values()
+                    .flat_map(|f| f.values())
+                    .map(|r| r.count())
+                    .sum(),
+                potentially_interesting_objects: record
+                    .potentially_interesting_objects()
+                    .values()
+                    .map(|r| r.count())
+                    .sum(),
+                geometry_fix_occurrences: record
+                    .geometry_fixes()
+                    .values()
+                    .flat_map(|f| f.values())
+                    .map(|r| r.count())
+                    .sum(),
+            };
+            for (discriminator, fields) in record.unknown_fields() {
+                for (field, reservoir) in fields {
+                    run.unknown_field_occurrences += reservoir.count();
+                    let summary = unknown_fields
+                        .entry((discriminator.clone(), field.clone()))
+                        .or_insert_with(|| UnknownFieldSummary {
+                            discriminator: discriminator.clone(),
+                            field: field.clone(),
+                            occurrences: 0,
+                            runs: 0,
+                            example_values: vec![],
+                        });
+                    summary.occurrences += reservoir.count();
+                    summary.runs += 1;
+                    for example in reservoir.sample() {
+                        if summary.example_values.len() >= MAX_EXAMPLE_VALUES {
+                            break;
+                        }
+                        if !summary.example_values.contains(&example.value) {
+                            summary.example_values.push(example.value.clone());
+                        }
+                    }
+                }
+            }
+            for (enum_name, members) in record.missing_enum_members() {
+                for (member, reservoir) in members {
+                    run.missing_enum_member_occurrences += reservoir.count();
+                    let summary = missing_enum_members
+                        .entry((enum_name.clone(), member.clone()))
+                        .or_insert_with(|| MissingEnumMemberSummary {
+                            enum_name: enum_name.clone(),
+                            member: member.clone(),
+                            occurrences: 0,
+                            runs: 0,
+                        });
+                    summary.occurrences += reservoir.count();
+                    summary.runs += 1;
+                }
+            }
+            runs.push(run);
+        }
+        let mut unknown_fields: Vec<_> = unknown_fields.into_values().collect();
+        unknown_fields.sort_by_key(|s| Reverse(s.occurrences));
+        unknown_fields.truncate(limit);
+        let mut missing_enum_members: Vec<_> = missing_enum_members.into_values().collect();
+        missing_enum_members.sort_by_key(|s| Reverse(s.occurrences));
+        missing_enum_members.truncate(limit);
+        Ok(Self {
+            runs,
+            unknown_fields,
+            missing_enum_members,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct ProblemOccurrence {
+    pub area_osm_id: i64,
+    pub created_at: NaiveDateTime,
+    pub occurrences: u64,
+}
+
+/// The occurrences of a single problem over time, together with some of the affected OSM objects.
+#[derive(Serialize)]
+pub struct ProblemDetail {
+    pub occurrences: Vec<ProblemOccurrence>,
+    pub example_ids: Vec<String>,
+    pub example_values: Vec<String>,
+}
+
+impl ProblemDetail {
+    pub fn for_unknown_field(
+        records: &[StoredTranslationRecord],
+        discriminator: &str,
+        field: &str,
+    ) -> Result<Self> {
+        let mut detail = Self::empty();
+        for stored in records {
+            let record = stored.parsed()?;
+            let reservoir = record
+                .unknown_fields()
+                .get(discriminator)
+                .and_then(|f| f.get(field));
+            if let Some(reservoir) = reservoir {
+                detail.add_occurrence(stored, reservoir.count());
+                for example in reservoir.sample() {
+                    if !detail.example_values.contains(&example.value) {
+                        detail.example_values.push(example.value.clone());
+                    }
+                    if let Some(id) = &example.object_id {
+                        detail.add_example_id(id);
+                    }
+                }
+            }
+        }
+        Ok(detail)
+    }
+
+    pub fn for_missing_enum_member(
+        records: &[StoredTranslationRecord],
+        enum_name: &str,
+        member: &str,
+    ) -> Result<Self> {
+        let mut detail = Self::empty();
+        for stored in records {
+            let record = stored.parsed()?;
+            let reservoir = record
+                .missing_enum_members()
+                .get(enum_name)
+                .and_then(|m| m.get(member));
+            if let Some(reservoir) = reservoir {
+                detail.add_occurrence(stored, reservoir.count());
+                for id in reservoir.sample() {
+                    detail.add_example_id(id);
+                }
+            }
+        }
+        Ok(detail)
+    }
+
+    fn empty() -> Self {
+        Self {
+            occurrences: vec![],
+            example_ids: vec![],
+            example_values: vec![],
+        }
+    }
+
+    fn add_occurrence(&mut self, stored: &StoredTranslationRecord, occurrences: u64) {
+        self.occurrences.push(ProblemOccurrence {
+            area_osm_id: stored.area_osm_id,
+            created_at: stored.created_at,
+            occurrences,
+        });
+    }
+
+    fn add_example_id(&mut self, id: &str) {
+        // Records loaded from the old format do not know the object ids.
+        if !id.is_empty() && !self.example_ids.iter().any(|i| i == id) {
+            self.example_ids.push(id.to_string());
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 
 use crate::area::Area;
+use crate::translation_report::{
+    ProblemDetail, StoredTranslationRecord, TranslationReport, TranslationRunKind, RUNS_PER_PAGE,
+};
 use crate::{DbConn, Result};
 use osm_db::AreaDatabase;
 use redis_api::ChangesStream;
@@ -51,6 +54,7 @@ pub async fn area_detail(area_id: i32, conn: DbConn) -> Result<Template> {
         entity_relationship_count: usize,
         entity_counts: HashMap<String, usize>,
         relationship_counts: HashMap<String, usize>,
+        bounds: Option<(f64, f64, f64, f64)>,
     }
     let area = conn.run(move |c| Area::find_by_id(area_id, c)).await?;
     let mut stream = ChangesStream::new_from_env(area.osm_id)?;
@@ -61,6 +65,7 @@ pub async fn area_detail(area_id: i32, conn: DbConn) -> Result<Template> {
     let entity_relationship_count = area_db.num_entity_relationships()?;
     let entity_counts = area_db.get_entity_counts_by_discriminator()?;
     let relationship_counts = area_db.get_entity_relationship_counts_by_kind()?;
+    let bounds = area_db.get_bounds()?;
     Ok(Template::render(
         "area_detail",
         Tctxt {
@@ -71,6 +76,155 @@ pub async fn area_detail(area_id: i32, conn: DbConn) -> Result<Template> {
             entity_relationship_count,
             entity_counts,
             relationship_counts,
+            bounds,
+        },
+    ))
+}
+
+// How many of the most frequent problems of each kind are shown in a report.
+const REPORT_LIMIT: usize = 100;
+
+/// The runs a report page aggregates, they are filtered and paged in the database, only the records of the page are parsed.
+#[derive(serde::Serialize)]
+struct RunsFilter {
+    area: Option<Area>,
+    kind: Option<TranslationRunKind>,
+    page: i64,
+    /// The query string selecting the same runs, without the page.
+    query: String,
+}
+
+impl RunsFilter {
+    async fn new(
+        area_id: Option<i32>,
+        kind: Option<TranslationRunKind>,
+        page: Option<i64>,
+        conn: &DbConn,
+    ) -> Result<Self> {
+        let area = match area_id {
+            Some(area_id) => Some(conn.run(move |c| Area::find_by_id(area_id, c)).await?),
+            None => None,
+        };
+        let mut query = vec![];
+        if let Some(area) = &area {
+            query.push(format!("area_id={}", area.id));
+        }
+        match kind {
+            Some(TranslationRunKind::Creation) => query.push("kind=creation".to_string()),
+            Some(TranslationRunKind::Update) => query.push("kind=update".to_string()),
+            None => {}
+        }
+        Ok(Self {
+            area,
+            kind,
+            page: page.unwrap_or(0).max(0),
+            query: query.join("&"),
+        })
+    }
+
+    /// Loads the records of the runs and processes them on the database thread, not in the request handler.
+    async fn process<T, F>(&self, conn: &DbConn, process: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&[StoredTranslationRecord]) -> Result<T> + Send + 'static,
+    {
+        let area_osm_id = self.area.as_ref().map(|a| a.osm_id);
+        let (kind, page) = (self.kind, self.page);
+        conn.run(move |c| process(&StoredTranslationRecord::page(area_osm_id, kind, page, c)?))
+            .await
+    }
+}
+
+#[get("/translation_report?<area_id>&<kind>&<page>")]
+pub async fn translation_report(
+    area_id: Option<i32>,
+    kind: Option<TranslationRunKind>,
+    page: Option<i64>,
+    conn: DbConn,
+) -> Result<Template> {
+    #[derive(serde::Serialize)]
+    struct TCtxt {
+        scope: String,
+        filter: RunsFilter,
+        report: TranslationReport,
+        has_next_page: bool,
+    }
+    let filter = RunsFilter::new(area_id, kind, page, &conn).await?;
+    let report = filter
+        .process(&conn, |records| {
+            TranslationReport::from_records(records, REPORT_LIMIT)
+        })
+        .await?;
+    let scope = match &filter.area {
+        Some(area) => format!("area {}", area.name),
+        None => "all areas".to_string(),
+    };
+    Ok(Template::render(
+        "translation_report",
+        TCtxt {
+            scope,
+            has_next_page: report.runs.len() as i64 == RUNS_PER_PAGE,
+            filter,
+            report,
+        },
+    ))
+}
+
+#[derive(serde::Serialize)]
+struct ProblemCtxt {
+    filter: RunsFilter,
+    title: String,
+    detail: ProblemDetail,
+}
+
+#[get("/translation_report/unknown_fields/<discriminator>/<field>?<area_id>&<kind>&<page>")]
+pub async fn unknown_field_detail(
+    discriminator: String,
+    field: String,
+    area_id: Option<i32>,
+    kind: Option<TranslationRunKind>,
+    page: Option<i64>,
+    conn: DbConn,
+) -> Result<Template> {
+    let filter = RunsFilter::new(area_id, kind, page, &conn).await?;
+    let title = format!("Unknown field {} of {}", field, discriminator);
+    let detail = filter
+        .process(&conn, move |records| {
+            ProblemDetail::for_unknown_field(records, &discriminator, &field)
+        })
+        .await?;
+    Ok(Template::render(
+        "translation_problem",
+        ProblemCtxt {
+            filter,
+            title,
+            detail,
+        },
+    ))
+}
+
+#[get("/translation_report/enum_members/<enum_name>/<member>?<area_id>&<kind>&<page>")]
+pub async fn missing_enum_member_detail(
+    enum_name: String,
+    member: String,
+    area_id: Option<i32>,
+    kind: Option<TranslationRunKind>,
+    page: Option<i64>,
+    conn: DbConn,
+) -> Result<Template> {
+    let filter = RunsFilter::new(area_id, kind, page, &conn).await?;
+    let title = format!("Value {} missing in enum {}", member, enum_name);
+    let detail = filter
+        .process(&conn, move |records| {
+            ProblemDetail::for_missing_enum_member(records, &enum_name, &member)
+        })
+        .await?;
+    Ok(Template::render(
+        "translation_problem",
+        ProblemCtxt {
+            filter,
+            title,
+            detail,
         },
     ))
 }
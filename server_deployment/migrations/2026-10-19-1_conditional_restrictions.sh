@@ -0,0 +1,12 @@
+#!/bin/sh
+# Moves the access conditional restrictions, stored under their tag names until now, to their fields and parses all the conditional restrictions of the roads.
+# The values which can't be parsed stay the raw strings, the clients still read them.
+set -e
+for entity in Road ServiceRoad Track; do
+    for mode in access foot bicycle wheelchair horse motorcar motorcycle hgv psv; do
+        oesc remove-field "$entity" "$mode:conditional" "${mode}_conditional"
+    done
+    for prefix in access foot bicycle wheelchair horse motorcar motorcycle hgv psv vehicle motor_vehicle maxspeed; do
+        oesc change-field-type --force "$entity" "${prefix}_conditional" conditional
+    done
+done
@@ -0,0 +1,7 @@
+#!/bin/sh
+# Parses the opening hours of all the entities, change-field-type converts the entities inheriting the field as well.
+# The values which can't be parsed stay the raw strings, the force flag skips the ones already converted through an ancestor.
+set -e
+for entity in Addressable Barrier Craft Fuel Graveyard Land Natural Parking Recycling Road Route Stop Tower; do
+    oesc change-field-type --force "$entity" opening_hours opening_hours
+done
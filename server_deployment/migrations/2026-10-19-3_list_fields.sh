@@ -0,0 +1,17 @@
+#!/bin/sh
+# Splits the semicolon separated values of the list fields into their items.
+# The sports and trades with more values were dropped by the translation until now, they come back when their objects change or after a redownload.
+set -e
+oesc change-field-type Amenity cuisine 'list[str]'
+oesc change-field-type Building cuisine 'list[str]'
+oesc change-field-type Pub cuisine 'list[str]'
+oesc change-field-type Amenity vending 'list[str]'
+oesc change-field-type Building healthcare_speciality 'list[str]'
+oesc change-field-type Land crop 'list[str]'
+oesc change-field-type Platform route_ref 'list[str]'
+oesc change-field-type Road destination 'list[str]'
+oesc change-field-type Road destination_ref 'list[str]'
+for entity in Amenity Area Building Leisure Natural Pub Road WaterArea WaterWay; do
+    oesc change-field-type --force "$entity" sport 'list[SportType]'
+done
+oesc change-field-type Shop trade 'list[TradeType]'
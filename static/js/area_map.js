@@ -0,0 +1,97 @@
+'use strict';
+// Shows the vector tiles of an area, with a layer for every entity type, over the OpenStreetMap raster tiles.
+(function () {
+    var container = document.getElementById('area-map');
+    if (!container) {
+        return;
+    }
+    var entityTypes = Object.keys(JSON.parse(container.dataset.entityCounts)).sort();
+    var bounds = JSON.parse(container.dataset.bounds);
+
+    // A stable color for every entity type, so the same types look the same across the areas.
+    function colorOf(entityType) {
+        var hash = 0;
+        for (var i = 0; i < entityType.length; i++) {
+            hash = (hash * 31 + entityType.charCodeAt(i)) % 360;
+        }
+        return 'hsl(' + hash + ', 70%, 45%)';
+    }
+
+    var layers = [{id: 'osm', type: 'raster', source: 'osm'}];
+    var interactiveLayers = [];
+    entityTypes.forEach(function (entityType) {
+        var color = colorOf(entityType);
+        var variants = [
+            {suffix: 'fill', type: 'fill', geometry: 'Polygon', paint: {'fill-color': color, 'fill-opacity': 0.3, 'fill-outline-color': color}},
+            {suffix: 'line', type: 'line', geometry: 'LineString', paint: {'line-color': color, 'line-width': 2}},
+            {suffix: 'point', type: 'circle', geometry: 'Point', paint: {'circle-color': color, 'circle-radius': 4}}
+        ];
+        variants.forEach(function (variant) {
+            var id = entityType + '-' + variant.suffix;
+            layers.push({
+                id: id,
+                type: variant.type,
+                source: 'area',
+                'source-layer': entityType,
+                filter: ['==', ['geometry-type'], variant.geometry],
+                paint: variant.paint
+            });
+            interactiveLayers.push(id);
+        });
+    });
+
+    var map = new maplibregl.Map({
+        container: container,
+        style: {
+            version: 8,
+            sources: {
+                osm: {
+                    type: 'raster',
+                    tiles: ['https://tile.openstreetmap.org/{z}/{x}/{y}.png'],
+                    tileSize: 256,
+                    attribution: '&copy; OpenStreetMap contributors'
+                },
+                area: {
+                    type: 'vector',
+                    tiles: [window.location.origin + container.dataset.tilesUrl],
+                    maxzoom: 20
+                }
+            },
+            layers: layers
+        },
+        center: [0, 0],
+        zoom: 1
+    });
+    map.addControl(new maplibregl.NavigationControl());
+    if (bounds) {
+        // The bounds are min_x, max_x, min_y and max_y.
+        map.fitBounds([[bounds[0], bounds[2]], [bounds[1], bounds[3]]], {padding: 20, animate: false});
+    }
+
+    map.on('click', function (e) {
+        var features = map.queryRenderedFeatures(e.point, {layers: interactiveLayers});
+        if (!features.length) {
+            return;
+        }
+        var feature = features[0];
+        var content = document.createElement('div');
+        var title = document.createElement('strong');
+        title.textContent = feature.sourceLayer;
+        content.appendChild(title);
+        content.appendChild(document.createElement('br'));
+        content.appendChild(document.createTextNode(feature.properties.id));
+        if (feature.properties.name) {
+            content.appendChild(document.createElement('br'));
+            content.appendChild(document.createTextNode(feature.properties.name));
+        }
+        new maplibregl.Popup().setLngLat(e.lngLat).setDOMContent(content).addTo(map);
+    });
+    interactiveLayers.forEach(function (id) {
+        map.on('mouseenter', id, function () {
+            map.getCanvas().style.cursor = 'pointer';
+        });
+        map.on('mouseleave', id, function () {
+            map.getCanvas().style.cursor = '';
+        });
+    });
+})();